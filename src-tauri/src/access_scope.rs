@@ -0,0 +1,460 @@
+use serde::Serialize;
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// A location the webview may read from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum ScopeEntry {
+    /// A folder the user opened, including everything below it.
+    Tree(PathBuf),
+    /// A folder and its direct children, e.g. the folder of an opened or followed document.
+    Folder(PathBuf),
+    /// A single file the user followed a link to.
+    File(PathBuf),
+}
+
+impl ScopeEntry {
+    fn allows(&self, canonical_path: &Path) -> bool {
+        match self {
+            Self::Tree(root) => canonical_path.starts_with(root),
+            Self::Folder(folder) => {
+                canonical_path == folder || canonical_path.parent() == Some(folder.as_path())
+            }
+            Self::File(file) => canonical_path == file,
+        }
+    }
+
+    /// Media a document embeds may sit in subfolders of the document's folder.
+    fn allows_embedded(&self, canonical_path: &Path) -> bool {
+        match self {
+            Self::Folder(folder) => canonical_path.starts_with(folder),
+            _ => self.allows(canonical_path),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum ScopedReadError {
+    PermissionDenied { path: String, message: String },
+    Failed { message: String },
+}
+
+impl fmt::Display for ScopedReadError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PermissionDenied { message, .. } | Self::Failed { message } => {
+                formatter.write_str(message)
+            }
+        }
+    }
+}
+
+impl From<String> for ScopedReadError {
+    fn from(message: String) -> Self {
+        Self::Failed { message }
+    }
+}
+
+/// Paths the user granted through dialogs, the CLI or OS open events, plus the targets the
+/// viewer asked to follow from documents inside them. Rendering a document never grants
+/// anything. Commands that take a path from the webview check it here.
+#[derive(Default)]
+pub struct AccessScope {
+    entries: Mutex<Vec<ScopeEntry>>,
+}
+
+impl AccessScope {
+    fn grant(&self, entry: ScopeEntry) {
+        if let Ok(mut entries) = self.entries.lock() {
+            if !entries.contains(&entry) {
+                entries.push(entry);
+            }
+        }
+    }
+
+    /// Grants an opened folder recursively, or an opened file together with its siblings.
+    pub fn grant_opened(&self, path: &Path) {
+        let Ok(canonical_path) = fs::canonicalize(path) else {
+            return;
+        };
+
+        if canonical_path.is_dir() {
+            self.grant(ScopeEntry::Tree(canonical_path));
+        } else if let Some(parent) = canonical_path.parent() {
+            self.grant(ScopeEntry::Folder(parent.to_path_buf()));
+        }
+    }
+
+    /// Grants the target of a link in `document_path`, after checking that the document is in
+    /// scope and really links there. No user gesture is verified: any target an in-scope
+    /// document links to can be granted this way. A linked document brings its siblings, a
+    /// folder its direct children, anything else only itself. Returns the canonical target.
+    pub fn grant_followed_link(
+        &self,
+        document_path: &Path,
+        target: &Path,
+    ) -> Result<PathBuf, ScopedReadError> {
+        let document_path = self.check(document_path)?;
+        let canonical_target = resolve(target)?;
+        if self.is_allowed(&canonical_target) {
+            return Ok(canonical_target);
+        }
+
+        let content =
+            fs::read_to_string(&document_path).map_err(|err| ScopedReadError::Failed {
+                message: format!("Failed to read '{}': {err}", document_path.display()),
+            })?;
+        let document_dir = document_path.parent().unwrap_or(&document_path);
+        let is_linked = local_link_targets(&content).into_iter().any(|link| {
+            fs::canonicalize(document_dir.join(link)).is_ok_and(|linked| linked == canonical_target)
+        });
+        if !is_linked {
+            return Err(permission_denied(
+                &canonical_target,
+                format!(
+                    "'{}' does not link to '{}'.",
+                    document_path.display(),
+                    canonical_target.display()
+                ),
+            ));
+        }
+
+        if canonical_target.is_dir() {
+            self.grant(ScopeEntry::Folder(canonical_target.clone()));
+        } else if crate::is_markdown_path(&canonical_target) {
+            if let Some(parent) = canonical_target.parent() {
+                self.grant(ScopeEntry::Folder(parent.to_path_buf()));
+            }
+        } else {
+            self.grant(ScopeEntry::File(canonical_target.clone()));
+        }
+        Ok(canonical_target)
+    }
+
+    pub fn is_allowed(&self, canonical_path: &Path) -> bool {
+        self.entries
+            .lock()
+            .map(|entries| entries.iter().any(|entry| entry.allows(canonical_path)))
+            .unwrap_or(false)
+    }
+
+    /// Resolves `path` (following `..` and symlinks) and returns it only when it is in scope.
+    pub fn check(&self, path: &Path) -> Result<PathBuf, ScopedReadError> {
+        let canonical_path = resolve(path)?;
+        if self.is_allowed(&canonical_path) {
+            return Ok(canonical_path);
+        }
+
+        Err(not_granted(&canonical_path))
+    }
+
+    /// Like `check`, but also accepts files in subfolders of an opened document's folder, for
+    /// the images and other media it embeds. Only the asset protocol, which serves nothing
+    /// but media, uses this.
+    pub fn check_embedded(&self, path: &Path) -> Result<PathBuf, ScopedReadError> {
+        let canonical_path = resolve(path)?;
+        let allowed = self
+            .entries
+            .lock()
+            .map(|entries| {
+                entries
+                    .iter()
+                    .any(|entry| entry.allows_embedded(&canonical_path))
+            })
+            .unwrap_or(false);
+        if allowed {
+            return Ok(canonical_path);
+        }
+
+        Err(not_granted(&canonical_path))
+    }
+}
+
+fn resolve(path: &Path) -> Result<PathBuf, ScopedReadError> {
+    fs::canonicalize(path).map_err(|err| ScopedReadError::Failed {
+        message: format!("Failed to resolve path '{}': {err}", path.display()),
+    })
+}
+
+fn permission_denied(canonical_path: &Path, message: String) -> ScopedReadError {
+    ScopedReadError::PermissionDenied {
+        path: canonical_path.to_string_lossy().to_string(),
+        message,
+    }
+}
+
+fn not_granted(canonical_path: &Path) -> ScopedReadError {
+    permission_denied(
+        canonical_path,
+        format!(
+            "Access to '{}' was not granted. Open it through Mudkip first.",
+            canonical_path.display()
+        ),
+    )
+}
+
+pub fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0usize;
+
+    while index < bytes.len() {
+        if bytes[index] == b'%' && index + 2 < bytes.len() {
+            let high = (bytes[index + 1] as char).to_digit(16);
+            let low = (bytes[index + 2] as char).to_digit(16);
+            if let (Some(high), Some(low)) = (high, low) {
+                decoded.push((high * 16 + low) as u8);
+                index += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[index]);
+        index += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+fn has_url_scheme(target: &str) -> bool {
+    let Some((scheme, _)) = target.split_once(':') else {
+        return false;
+    };
+    // A single letter is a Windows drive (`C:\docs`), not a scheme.
+    scheme.len() > 1
+        && scheme.starts_with(|ch: char| ch.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '+' | '-' | '.'))
+}
+
+/// Turns a link destination into a local relative/absolute path, or `None` for URLs and anchors.
+pub fn local_link_path(destination: &str) -> Option<String> {
+    let destination = destination.trim();
+    if destination.is_empty()
+        || destination.starts_with('#')
+        || destination.starts_with("//")
+        || has_url_scheme(destination)
+    {
+        return None;
+    }
+
+    let without_fragment = destination.split(['#', '?']).next().unwrap_or_default();
+    if without_fragment.is_empty() {
+        return None;
+    }
+
+    Some(percent_decode(without_fragment))
+}
+
+fn inline_link_destination(rest: &str) -> Option<&str> {
+    let rest = rest.trim_start();
+    if let Some(bracketed) = rest.strip_prefix('<') {
+        return bracketed.split('>').next();
+    }
+
+    let mut depth = 0usize;
+    for (offset, ch) in rest.char_indices() {
+        match ch {
+            '(' => depth += 1,
+            ')' if depth == 0 => return Some(&rest[..offset]),
+            ')' => depth -= 1,
+            ch if ch.is_whitespace() => return Some(&rest[..offset]),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Collects local link and image destinations from markdown (`[x](dest)`, `[id]: dest`) and
+/// raw HTML (`src="dest"`, `href="dest"`).
+fn local_link_targets(content: &str) -> Vec<String> {
+    let mut destinations = Vec::new();
+
+    for (offset, _) in content.match_indices("](") {
+        if let Some(destination) = inline_link_destination(&content[offset + 2..]) {
+            destinations.push(destination);
+        }
+    }
+
+    for line in content.lines() {
+        let trimmed = line.trim_start();
+        if line.len() - trimmed.len() > 3 || !trimmed.starts_with('[') {
+            continue;
+        }
+        if let Some((_, rest)) = trimmed.split_once("]:") {
+            if let Some(destination) = rest.split_whitespace().next() {
+                destinations.push(destination.trim_start_matches('<').trim_end_matches('>'));
+            }
+        }
+    }
+
+    for attribute in ["src=", "href="] {
+        for (offset, _) in content.match_indices(attribute) {
+            let rest = &content[offset + attribute.len()..];
+            let Some(quote) = rest.chars().next().filter(|ch| *ch == '"' || *ch == '\'') else {
+                continue;
+            };
+            if let Some(destination) = rest[1..].split(quote).next() {
+                destinations.push(destination);
+            }
+        }
+    }
+
+    destinations
+        .into_iter()
+        .filter_map(local_link_path)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::create_temp_directory;
+
+    fn create_temp_tree() -> PathBuf {
+        let root = create_temp_directory("access-scope");
+        fs::create_dir_all(root.join("docs/guides")).expect("should create docs");
+        fs::create_dir_all(root.join("private")).expect("should create private");
+        fs::create_dir_all(root.join("shared")).expect("should create shared");
+        fs::write(root.join("docs/index.md"), "# Index\n").expect("should write index");
+        fs::write(root.join("docs/guides/setup.md"), "# Setup\n").expect("should write guide");
+        fs::write(root.join("private/secret.md"), "# Secret\n").expect("should write secret");
+        fs::write(root.join("shared/linked.md"), "# Linked\n").expect("should write linked");
+        root
+    }
+
+    #[test]
+    fn check_rejects_parent_traversal_out_of_opened_folder() {
+        let root = create_temp_tree();
+        let scope = AccessScope::default();
+        scope.grant_opened(&root.join("docs"));
+
+        assert!(scope.check(&root.join("docs/guides/setup.md")).is_ok());
+        assert!(matches!(
+            scope.check(&root.join("docs/../private/secret.md")),
+            Err(ScopedReadError::PermissionDenied { .. })
+        ));
+        assert!(matches!(
+            scope.check(&root.join("docs/guides/../../private/secret.md")),
+            Err(ScopedReadError::PermissionDenied { .. })
+        ));
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn opened_file_grants_siblings_but_not_subfolders() {
+        let root = create_temp_tree();
+        let scope = AccessScope::default();
+        scope.grant_opened(&root.join("docs/index.md"));
+
+        assert!(scope.check(&root.join("docs/index.md")).is_ok());
+        assert!(scope.check(&root.join("docs/guides/setup.md")).is_err());
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn check_rejects_symlinks_pointing_out_of_scope() {
+        let root = create_temp_tree();
+        std::os::unix::fs::symlink(
+            root.join("private/secret.md"),
+            root.join("docs/innocent.md"),
+        )
+        .expect("should create symlink");
+        std::os::unix::fs::symlink(root.join("private"), root.join("docs/private-link"))
+            .expect("should create folder symlink");
+
+        let scope = AccessScope::default();
+        scope.grant_opened(&root.join("docs"));
+
+        assert!(matches!(
+            scope.check(&root.join("docs/innocent.md")),
+            Err(ScopedReadError::PermissionDenied { .. })
+        ));
+        assert!(matches!(
+            scope.check(&root.join("docs/private-link/secret.md")),
+            Err(ScopedReadError::PermissionDenied { .. })
+        ));
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn following_a_link_grants_the_folder_of_the_linked_document_only() {
+        let root = create_temp_tree();
+        let scope = AccessScope::default();
+        let document = root.join("docs/index.md");
+        fs::write(
+            &document,
+            "See [linked](../shared/linked.md#intro) and [web](https://example.com/a.md).",
+        )
+        .expect("should write index");
+        scope.grant_opened(&document);
+
+        assert!(scope.check(&root.join("shared/linked.md")).is_err());
+        assert_eq!(
+            scope.grant_followed_link(&document, &root.join("shared/linked.md")),
+            Ok(root.join("shared/linked.md"))
+        );
+        assert!(scope.check(&root.join("shared/linked.md")).is_ok());
+
+        assert!(matches!(
+            scope.grant_followed_link(&document, &root.join("private/secret.md")),
+            Err(ScopedReadError::PermissionDenied { .. })
+        ));
+        assert!(scope.check(&root.join("private/secret.md")).is_err());
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn links_in_an_opened_document_stay_out_of_scope_until_followed() {
+        let root = create_temp_tree();
+        let document = root.join("docs/index.md");
+        let secret = root.join("private/secret.md");
+        fs::write(
+            &document,
+            format!(
+                "![]({})\n[folder]({}/)\n<img src=\"../shared/linked.md\">\n",
+                secret.display(),
+                root.join("private").display()
+            ),
+        )
+        .expect("should write index");
+        fs::write(root.join("docs/guides/diagram.png"), b"png").expect("should write image");
+
+        let scope = AccessScope::default();
+        scope.grant_opened(&document);
+
+        for path in [&secret, &root.join("shared/linked.md")] {
+            assert!(scope.check(path).is_err());
+            assert!(scope.check_embedded(path).is_err());
+        }
+        assert!(scope.check(&root.join("private")).is_err());
+        // Embedded media below the document's own folder stays loadable.
+        assert!(scope.check(&root.join("docs/guides/diagram.png")).is_err());
+        assert!(scope
+            .check_embedded(&root.join("docs/guides/diagram.png"))
+            .is_ok());
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn local_link_targets_skips_urls_and_anchors() {
+        let targets = local_link_targets(
+            "[a](./a.md) [b](<my doc.md> \"t\") [c](#top) [d](mailto:x@y.z)\n[ref]: ../ref%20doc.md\n<img src=\"img/x.png\">",
+        );
+        assert_eq!(
+            targets,
+            vec!["./a.md", "my doc.md", "../ref doc.md", "img/x.png"]
+        );
+    }
+}
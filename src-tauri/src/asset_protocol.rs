@@ -0,0 +1,335 @@
+use crate::access_scope::{percent_decode, AccessScope, ScopedReadError};
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+use tauri::http::{header, Method, Request, Response, StatusCode};
+use url::Url;
+
+pub const ASSET_SCHEME: &str = "mudkip-asset";
+
+/// Webviews on Windows and Android only route custom schemes through `http://<scheme>.localhost`.
+#[cfg(any(windows, target_os = "android"))]
+const ASSET_ORIGIN: &str = "http://mudkip-asset.localhost";
+#[cfg(not(any(windows, target_os = "android")))]
+const ASSET_ORIGIN: &str = "mudkip-asset://localhost";
+
+/// Largest chunk served for open-ended range requests (`bytes=N-`), so seeking in a large
+/// video does not read the rest of the file into memory.
+const MAX_RANGE_CHUNK_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Builds the `<base href>` that makes relative links in a document resolve through the
+/// asset protocol.
+pub fn asset_base_href(directory: &Path) -> Result<String, String> {
+    let directory_url = Url::from_directory_path(directory)
+        .map_err(|_| "Unable to convert parent directory to asset URL.".to_string())?;
+    Ok(format!("{ASSET_ORIGIN}{}", directory_url.path()))
+}
+
+fn asset_path_from_uri_path(uri_path: &str) -> PathBuf {
+    let decoded = percent_decode(uri_path);
+
+    // `/C:/docs/a.png` -> `C:/docs/a.png`
+    #[cfg(windows)]
+    let decoded = decoded.strip_prefix('/').unwrap_or(&decoded).to_string();
+
+    PathBuf::from(decoded)
+}
+
+/// The media types a rendered document embeds. Anything else, including markdown and other
+/// text, is never served so a page cannot read documents or arbitrary files through `fetch`.
+fn mime_type_for_path(path: &Path) -> Option<&'static str> {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase())
+        .unwrap_or_default();
+
+    let mime_type = match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "bmp" => "image/bmp",
+        "tif" | "tiff" => "image/tiff",
+        "mp4" | "m4v" => "video/mp4",
+        "webm" => "video/webm",
+        "ogv" => "video/ogg",
+        "mov" => "video/quicktime",
+        "mp3" => "audio/mpeg",
+        "m4a" => "audio/mp4",
+        "wav" => "audio/wav",
+        "ogg" | "oga" => "audio/ogg",
+        "flac" => "audio/flac",
+        "css" => "text/css",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        _ => return None,
+    };
+    Some(mime_type)
+}
+
+/// Parses a single `bytes=start-end` range against a file of `length` bytes into an inclusive
+/// `(start, end)` pair. Returns `Err(())` when the range cannot be satisfied.
+fn parse_byte_range(value: &str, length: u64) -> Result<(u64, u64), ()> {
+    let spec = value.trim().strip_prefix("bytes=").ok_or(())?;
+    // Multipart ranges are rare for media; serve the first one.
+    let spec = spec.split(',').next().unwrap_or_default().trim();
+    let (start, end) = spec.split_once('-').ok_or(())?;
+    let (start, end) = (start.trim(), end.trim());
+
+    if length == 0 {
+        return Err(());
+    }
+
+    let (start, end) = if start.is_empty() {
+        let suffix_length: u64 = end.parse().map_err(|_| ())?;
+        if suffix_length == 0 {
+            return Err(());
+        }
+        (length.saturating_sub(suffix_length), length - 1)
+    } else {
+        let start: u64 = start.parse().map_err(|_| ())?;
+        let end = if end.is_empty() {
+            start.saturating_add(MAX_RANGE_CHUNK_BYTES - 1)
+        } else {
+            end.parse().map_err(|_| ())?
+        };
+        (start, end.min(length - 1))
+    };
+
+    if start > end || start >= length {
+        return Err(());
+    }
+
+    Ok((start, end))
+}
+
+fn empty_response(status: StatusCode) -> Response<Vec<u8>> {
+    Response::builder()
+        .status(status)
+        .body(Vec::new())
+        .unwrap_or_default()
+}
+
+fn read_asset(
+    path: &Path,
+    mime_type: &str,
+    range_header: Option<&str>,
+    include_body: bool,
+) -> Result<Response<Vec<u8>>, String> {
+    let mut file = File::open(path)
+        .map_err(|err| format!("Failed to open asset '{}': {err}", path.display()))?;
+    let length = file
+        .metadata()
+        .map_err(|err| format!("Failed to read asset metadata '{}': {err}", path.display()))?
+        .len();
+
+    let builder = Response::builder()
+        .header(header::CONTENT_TYPE, mime_type)
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::CACHE_CONTROL, "no-cache");
+
+    let Some(range_header) = range_header else {
+        let mut body = Vec::new();
+        if include_body {
+            file.read_to_end(&mut body)
+                .map_err(|err| format!("Failed to read asset '{}': {err}", path.display()))?;
+        }
+        return builder
+            .header(header::CONTENT_LENGTH, length)
+            .body(body)
+            .map_err(|err| err.to_string());
+    };
+
+    let Ok((start, end)) = parse_byte_range(range_header, length) else {
+        return builder
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .header(header::CONTENT_RANGE, format!("bytes */{length}"))
+            .body(Vec::new())
+            .map_err(|err| err.to_string());
+    };
+
+    let chunk_length = end - start + 1;
+    let mut body = Vec::new();
+    if include_body {
+        file.seek(SeekFrom::Start(start))
+            .map_err(|err| format!("Failed to seek asset '{}': {err}", path.display()))?;
+        file.take(chunk_length)
+            .read_to_end(&mut body)
+            .map_err(|err| format!("Failed to read asset '{}': {err}", path.display()))?;
+    }
+
+    builder
+        .status(StatusCode::PARTIAL_CONTENT)
+        .header(
+            header::CONTENT_RANGE,
+            format!("bytes {start}-{end}/{length}"),
+        )
+        .header(header::CONTENT_LENGTH, chunk_length)
+        .body(body)
+        .map_err(|err| err.to_string())
+}
+
+/// Serves a `mudkip-asset://localhost/<absolute path>` request, limited to media files in paths
+/// the access scope allows.
+pub fn handle_asset_request(scope: &AccessScope, request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+    let include_body = match *request.method() {
+        Method::GET => true,
+        Method::HEAD => false,
+        _ => return empty_response(StatusCode::METHOD_NOT_ALLOWED),
+    };
+
+    let requested_path = asset_path_from_uri_path(request.uri().path());
+    let Some(mime_type) = mime_type_for_path(&requested_path) else {
+        log::warn!(
+            "Refusing to serve '{}': not a media file.",
+            requested_path.display()
+        );
+        return empty_response(StatusCode::FORBIDDEN);
+    };
+    let canonical_path = match scope.check_embedded(&requested_path) {
+        Ok(path) => path,
+        Err(ScopedReadError::PermissionDenied { message, .. }) => {
+            log::warn!("{message}");
+            return empty_response(StatusCode::FORBIDDEN);
+        }
+        Err(ScopedReadError::Failed { .. }) => return empty_response(StatusCode::NOT_FOUND),
+    };
+
+    if !canonical_path.is_file() {
+        return empty_response(StatusCode::NOT_FOUND);
+    }
+    // A symlink named `a.png` may point at any file; judge the type by the real name too.
+    if mime_type_for_path(&canonical_path) != Some(mime_type) {
+        return empty_response(StatusCode::FORBIDDEN);
+    }
+
+    let range_header = request
+        .headers()
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok());
+
+    read_asset(&canonical_path, mime_type, range_header, include_body).unwrap_or_else(|err| {
+        log::warn!("{err}");
+        empty_response(StatusCode::INTERNAL_SERVER_ERROR)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::create_temp_directory;
+    use std::fs;
+
+    fn create_temp_tree() -> PathBuf {
+        let root = create_temp_directory("asset-protocol");
+        fs::create_dir_all(root.join("docs/media")).expect("should create docs");
+        fs::create_dir_all(root.join("private")).expect("should create private");
+        root
+    }
+
+    fn asset_request(path: &Path, range: Option<&str>) -> Request<Vec<u8>> {
+        let file_url = Url::from_file_path(path).expect("asset path should be absolute");
+        let mut builder = Request::builder().uri(format!("{ASSET_ORIGIN}{}", file_url.path()));
+        if let Some(range) = range {
+            builder = builder.header(header::RANGE, range);
+        }
+        builder.body(Vec::new()).expect("should build request")
+    }
+
+    #[test]
+    fn parse_byte_range_handles_open_suffix_and_invalid_ranges() {
+        assert_eq!(parse_byte_range("bytes=0-99", 1000), Ok((0, 99)));
+        assert_eq!(parse_byte_range("bytes=900-", 1000), Ok((900, 999)));
+        assert_eq!(parse_byte_range("bytes=-100", 1000), Ok((900, 999)));
+        assert_eq!(parse_byte_range("bytes=500-5000", 1000), Ok((500, 999)));
+        assert_eq!(parse_byte_range("bytes=1000-", 1000), Err(()));
+        assert_eq!(parse_byte_range("bytes=9-3", 1000), Err(()));
+        assert_eq!(parse_byte_range("items=0-1", 1000), Err(()));
+        assert_eq!(
+            parse_byte_range("bytes=0-", 100 * 1024 * 1024),
+            Ok((0, MAX_RANGE_CHUNK_BYTES - 1))
+        );
+    }
+
+    #[test]
+    fn handle_asset_request_serves_ranges_with_mime_type() {
+        let root = create_temp_tree();
+        let video = root.join("docs/media/clip one.mp4");
+        fs::write(&video, b"0123456789").expect("should write video");
+
+        let scope = AccessScope::default();
+        scope.grant_opened(&root.join("docs"));
+
+        let full = handle_asset_request(&scope, &asset_request(&video, None));
+        assert_eq!(full.status(), StatusCode::OK);
+        assert_eq!(full.headers()[header::CONTENT_TYPE], "video/mp4");
+        assert_eq!(full.body(), b"0123456789");
+
+        let partial = handle_asset_request(&scope, &asset_request(&video, Some("bytes=2-5")));
+        assert_eq!(partial.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(partial.headers()[header::CONTENT_RANGE], "bytes 2-5/10");
+        assert_eq!(partial.body(), b"2345");
+
+        let unsatisfiable =
+            handle_asset_request(&scope, &asset_request(&video, Some("bytes=20-30")));
+        assert_eq!(unsatisfiable.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn handle_asset_request_rejects_paths_outside_scope() {
+        let root = create_temp_tree();
+        let secret = root.join("private/secret.png");
+        fs::write(&secret, b"secret").expect("should write secret");
+
+        let scope = AccessScope::default();
+        scope.grant_opened(&root.join("docs"));
+
+        let response = handle_asset_request(&scope, &asset_request(&secret, None));
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert!(response.body().is_empty());
+
+        let missing =
+            handle_asset_request(&scope, &asset_request(&root.join("docs/nope.png"), None));
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn handle_asset_request_serves_only_media_types() {
+        let root = create_temp_tree();
+        let notes = root.join("docs/notes.md");
+        let key = root.join("docs/id_rsa");
+        fs::write(&notes, "# Notes\n").expect("should write notes");
+        fs::write(&key, "secret").expect("should write key");
+
+        let scope = AccessScope::default();
+        scope.grant_opened(&root.join("docs"));
+
+        for path in [&notes, &key] {
+            let response = handle_asset_request(&scope, &asset_request(path, None));
+            assert_eq!(response.status(), StatusCode::FORBIDDEN);
+            assert!(response.body().is_empty());
+        }
+
+        #[cfg(unix)]
+        {
+            let disguised = root.join("docs/media/key.png");
+            std::os::unix::fs::symlink(&key, &disguised).expect("should create symlink");
+            let response = handle_asset_request(&scope, &asset_request(&disguised, None));
+            assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        }
+
+        let _ = fs::remove_dir_all(root);
+    }
+}
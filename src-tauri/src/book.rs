@@ -0,0 +1,374 @@
+use serde::Deserialize;
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+/// Explicit chapter order for Mudkip, e.g. `chapters = ["intro.md", { path = "setup.md",
+/// title = "Setup", depth = 1 }]`.
+pub const ORDER_FILE_NAME: &str = ".mudkip.toml";
+/// mdBook's table of contents, in the folder itself or in `src/` next to `book.toml`.
+pub const SUMMARY_FILE_NAME: &str = "SUMMARY.md";
+/// docsify's sidebar.
+pub const SIDEBAR_FILE_NAME: &str = "_sidebar.md";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BookChapter {
+    pub path: PathBuf,
+    pub title: Option<String>,
+    /// Nesting level, 0 for top-level chapters.
+    pub depth: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Book {
+    /// The ordering file relative to the folder, e.g. `src/SUMMARY.md`.
+    pub source: String,
+    pub chapters: Vec<BookChapter>,
+}
+
+/// A chapter as written in the ordering file, before its target is resolved.
+#[derive(Debug, PartialEq, Eq)]
+struct ChapterLink {
+    title: Option<String>,
+    target: String,
+    depth: usize,
+}
+
+#[derive(Deserialize)]
+struct OrderFile {
+    #[serde(default)]
+    chapters: Vec<OrderEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OrderEntry {
+    Path(String),
+    Chapter {
+        path: String,
+        #[serde(default)]
+        title: Option<String>,
+        #[serde(default)]
+        depth: usize,
+    },
+}
+
+fn parse_order_file(source: &str) -> Result<Vec<ChapterLink>, String> {
+    let order: OrderFile = toml::from_str(source).map_err(|err| err.to_string())?;
+    Ok(order
+        .chapters
+        .into_iter()
+        .map(|entry| match entry {
+            OrderEntry::Path(target) => ChapterLink {
+                title: None,
+                target,
+                depth: 0,
+            },
+            OrderEntry::Chapter { path, title, depth } => ChapterLink {
+                title,
+                target: path,
+                depth,
+            },
+        })
+        .collect())
+}
+
+/// `[Title](target)` at the start of `text`.
+fn parse_link(text: &str) -> Option<(String, String)> {
+    let rest = text.strip_prefix('[')?;
+    let (title, rest) = rest.split_once("](")?;
+    let (target, _) = rest.split_once(')')?;
+    Some((title.trim().to_string(), target.trim().to_string()))
+}
+
+/// Reads the nested link lists of mdBook's `SUMMARY.md` and docsify's `_sidebar.md`. Unlinked
+/// entries such as part titles, drafts with an empty target and separators are skipped.
+fn parse_link_list(source: &str) -> Vec<ChapterLink> {
+    let mut chapters = Vec::new();
+    let mut indent_stack: Vec<usize> = Vec::new();
+    let mut in_code_block = false;
+
+    for line in source.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if in_code_block {
+            continue;
+        }
+
+        let indent: usize = line[..line.len() - trimmed.len()]
+            .chars()
+            .map(|ch| if ch == '\t' { 4 } else { 1 })
+            .sum();
+        let (depth, item) = match trimmed
+            .strip_prefix("- ")
+            .or_else(|| trimmed.strip_prefix("* "))
+            .or_else(|| trimmed.strip_prefix("+ "))
+        {
+            Some(item) => {
+                while indent_stack.last().is_some_and(|&top| top >= indent) {
+                    indent_stack.pop();
+                }
+                indent_stack.push(indent);
+                (indent_stack.len() - 1, item.trim())
+            }
+            // mdBook's prefix and suffix chapters are plain links outside the list.
+            None if indent == 0 && trimmed.starts_with('[') => {
+                indent_stack.clear();
+                (0, trimmed)
+            }
+            None => continue,
+        };
+
+        if let Some((title, target)) = parse_link(item).filter(|(_, target)| !target.is_empty()) {
+            chapters.push(ChapterLink {
+                title: (!title.is_empty()).then_some(title),
+                target,
+                depth,
+            });
+        }
+    }
+
+    chapters
+}
+
+/// Resolves a chapter target against the ordering file's folder. docsify-style targets are
+/// accepted too: `/guide` is relative to the book root, `guide/` means `guide/README.md` and
+/// a missing extension means `.md`. Returns `None` for web links, missing files and anything
+/// outside `root`.
+fn resolve_target(root: &Path, base: &Path, target: &str) -> Option<PathBuf> {
+    if target.contains("://") || target.starts_with("mailto:") {
+        return None;
+    }
+
+    let target = target
+        .split(['#', '?'])
+        .next()
+        .filter(|target| !target.is_empty())?;
+    let mut relative = target.to_string();
+    if relative.ends_with('/') {
+        relative.push_str("README.md");
+    } else if Path::new(&relative).extension().is_none() {
+        relative.push_str(".md");
+    }
+
+    let relative_path = Path::new(&relative);
+    if relative_path
+        .components()
+        .any(|component| matches!(component, Component::Prefix(_)))
+    {
+        return None;
+    }
+    let joined = match relative.strip_prefix('/') {
+        Some(from_root) => root.join(from_root),
+        None => base.join(relative_path),
+    };
+
+    let canonical = fs::canonicalize(joined).ok()?;
+    (canonical.is_file() && canonical.starts_with(root)).then_some(canonical)
+}
+
+fn resolve_chapters(root: &Path, base: &Path, links: Vec<ChapterLink>) -> Vec<BookChapter> {
+    let mut chapters: Vec<BookChapter> = Vec::new();
+    for link in links {
+        let Some(path) = resolve_target(root, base, &link.target) else {
+            continue;
+        };
+        if chapters.iter().any(|chapter| chapter.path == path) {
+            continue;
+        }
+        chapters.push(BookChapter {
+            path,
+            title: link.title,
+            depth: link.depth,
+        });
+    }
+    chapters
+}
+
+/// The chapter order of `folder` (canonical), taken from the first ordering file found:
+/// `.mudkip.toml`, `SUMMARY.md`, `src/SUMMARY.md` for an mdBook project, then `_sidebar.md`.
+pub fn load(folder: &Path) -> Option<Book> {
+    let candidates = [
+        PathBuf::from(ORDER_FILE_NAME),
+        PathBuf::from(SUMMARY_FILE_NAME),
+        Path::new("src").join(SUMMARY_FILE_NAME),
+        PathBuf::from(SIDEBAR_FILE_NAME),
+    ];
+
+    for candidate in candidates {
+        let path = folder.join(&candidate);
+        if candidate.starts_with("src") && !folder.join("book.toml").is_file() {
+            continue;
+        }
+        let Ok(source) = fs::read_to_string(&path) else {
+            continue;
+        };
+
+        let links = if candidate.as_os_str() == ORDER_FILE_NAME {
+            match parse_order_file(&source) {
+                Ok(links) => links,
+                Err(err) => {
+                    log::warn!("Ignoring invalid '{}': {err}", path.display());
+                    continue;
+                }
+            }
+        } else {
+            parse_link_list(&source)
+        };
+        let base = path.parent().unwrap_or(folder);
+        let chapters = resolve_chapters(folder, base, links);
+        if chapters.is_empty() {
+            continue;
+        }
+
+        return Some(Book {
+            source: candidate.to_string_lossy().replace('\\', "/"),
+            chapters,
+        });
+    }
+
+    None
+}
+
+/// Subdirectories of `folder` whose changes can alter its book: mdBook's `src/`, where
+/// `SUMMARY.md` may appear later, and every directory holding the ordering file or a chapter.
+pub fn watch_directories(folder: &Path, book: Option<&Book>) -> BTreeSet<PathBuf> {
+    let mut directories = BTreeSet::new();
+    if folder.join("book.toml").is_file() {
+        directories.insert(folder.join("src"));
+    }
+    if let Some(book) = book {
+        directories.extend(folder.join(&book.source).parent().map(Path::to_path_buf));
+        directories.extend(
+            book.chapters
+                .iter()
+                .filter_map(|chapter| chapter.path.parent())
+                .map(Path::to_path_buf),
+        );
+    }
+    directories.retain(|directory| directory != folder && directory.is_dir());
+    directories
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::create_temp_directory;
+
+    fn link(title: Option<&str>, target: &str, depth: usize) -> ChapterLink {
+        ChapterLink {
+            title: title.map(str::to_string),
+            target: target.to_string(),
+            depth,
+        }
+    }
+
+    #[test]
+    fn parse_link_list_reads_mdbook_summary_nesting() {
+        let summary = "# Summary\n\n[Introduction](README.md)\n\n# User Guide\n\n- [Installation](guide/installation.md)\n    - [Linux](guide/linux.md)\n        - [Arch](guide/arch.md)\n    - [Draft]()\n- [Reading](guide/reading.md)\n\n---\n\n[Contributors](misc/contributors.md)\n";
+
+        assert_eq!(
+            parse_link_list(summary),
+            vec![
+                link(Some("Introduction"), "README.md", 0),
+                link(Some("Installation"), "guide/installation.md", 0),
+                link(Some("Linux"), "guide/linux.md", 1),
+                link(Some("Arch"), "guide/arch.md", 2),
+                link(Some("Reading"), "guide/reading.md", 0),
+                link(Some("Contributors"), "misc/contributors.md", 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_order_file_accepts_paths_and_detailed_entries() {
+        let order = "chapters = [\n  \"intro.md\",\n  { path = \"setup.md\", title = \"Setup\", depth = 1 },\n]\n";
+
+        assert_eq!(
+            parse_order_file(order),
+            Ok(vec![
+                link(None, "intro.md", 0),
+                link(Some("Setup"), "setup.md", 1),
+            ])
+        );
+        assert!(parse_order_file("chapters = 3").is_err());
+    }
+
+    #[test]
+    fn load_resolves_docsify_targets_inside_the_folder() {
+        let root = create_temp_directory("book");
+        fs::create_dir_all(root.join("guide")).expect("should create guide");
+        fs::write(root.join("README.md"), "# Home\n").expect("should write readme");
+        fs::write(root.join("guide/README.md"), "# Guide\n").expect("should write guide");
+        fs::write(root.join("guide/setup.md"), "# Setup\n").expect("should write setup");
+        fs::write(
+            root.join(SIDEBAR_FILE_NAME),
+            "* [Home](/)\n* [Guide](guide/)\n  * [Setup](/guide/setup)\n  * [Missing](guide/missing.md)\n* [Escape](../outside.md)\n* [Web](https://example.com)\n",
+        )
+        .expect("should write sidebar");
+
+        let book = load(&root).expect("sidebar should define a book");
+        assert_eq!(book.source, SIDEBAR_FILE_NAME);
+        assert_eq!(
+            watch_directories(&root, Some(&book)),
+            BTreeSet::from([root.join("guide")])
+        );
+        let chapters: Vec<(PathBuf, usize)> = book
+            .chapters
+            .into_iter()
+            .map(|chapter| (chapter.path, chapter.depth))
+            .collect();
+        assert_eq!(
+            chapters,
+            vec![
+                (root.join("README.md"), 0),
+                (root.join("guide/README.md"), 0),
+                (root.join("guide/setup.md"), 1),
+            ]
+        );
+
+        fs::write(
+            root.join(ORDER_FILE_NAME),
+            "chapters = [\"guide/setup.md\"]\n",
+        )
+        .expect("should write order file");
+        let book = load(&root).expect("order file should define a book");
+        assert_eq!(book.source, ORDER_FILE_NAME);
+        assert_eq!(book.chapters.len(), 1);
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn watch_directories_cover_the_mdbook_source_folder() {
+        let root = create_temp_directory("book");
+        fs::create_dir_all(root.join("src/advanced")).expect("should create src");
+        fs::write(root.join("book.toml"), "[book]\n").expect("should write book.toml");
+        assert_eq!(load(&root), None);
+        assert_eq!(
+            watch_directories(&root, None),
+            BTreeSet::from([root.join("src")])
+        );
+
+        fs::write(root.join("src/intro.md"), "# Intro\n").expect("should write intro");
+        fs::write(root.join("src/advanced/tips.md"), "# Tips\n").expect("should write tips");
+        fs::write(
+            root.join("src").join(SUMMARY_FILE_NAME),
+            "- [Intro](intro.md)\n  - [Tips](advanced/tips.md)\n",
+        )
+        .expect("should write summary");
+        let book = load(&root).expect("summary should define a book");
+        assert_eq!(book.source, "src/SUMMARY.md");
+        assert_eq!(
+            watch_directories(&root, Some(&book)),
+            BTreeSet::from([root.join("src"), root.join("src/advanced")])
+        );
+
+        let _ = fs::remove_dir_all(root);
+    }
+}
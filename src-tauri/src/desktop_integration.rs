@@ -0,0 +1,466 @@
+#[cfg(all(unix, not(target_os = "macos")))]
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+#[cfg(all(unix, not(target_os = "macos")))]
+pub const DESKTOP_FILE_NAME: &str = "mudkip.desktop";
+#[cfg(all(unix, not(target_os = "macos")))]
+const ICON_NAME: &str = "mudkip";
+#[cfg(all(unix, not(target_os = "macos")))]
+const MIME_PACKAGE_FILE_NAME: &str = "mudkip.xml";
+#[cfg(all(unix, not(target_os = "macos")))]
+const MIMEAPPS_FILE_NAME: &str = "mimeapps.list";
+#[cfg(all(unix, not(target_os = "macos")))]
+const DEFAULT_APPLICATIONS_SECTION: &str = "[Default Applications]";
+#[cfg(all(unix, not(target_os = "macos")))]
+const ADDED_ASSOCIATIONS_SECTION: &str = "[Added Associations]";
+
+/// Types the desktop entry claims, matching the bundled `linux/mudkip.desktop` template.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub const MIME_TYPES: [&str; 3] = [
+    "text/markdown",
+    "text/x-markdown",
+    "x-scheme-handler/mudkip",
+];
+
+#[cfg(all(unix, not(target_os = "macos")))]
+const ICON_PNG: &[u8] = include_bytes!("../icons/128x128.png");
+
+/// Declares `text/markdown` for systems whose shared-mime-info predates it.
+#[cfg(all(unix, not(target_os = "macos")))]
+const MIME_PACKAGE_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<mime-info xmlns="http://www.freedesktop.org/standards/shared-mime-info">
+  <mime-type type="text/markdown">
+    <comment>Markdown document</comment>
+    <sub-class-of type="text/plain"/>
+    <alias type="text/x-markdown"/>
+    <glob pattern="*.md"/>
+    <glob pattern="*.markdown"/>
+    <glob pattern="*.mdown"/>
+    <glob pattern="*.mkd"/>
+  </mime-type>
+</mime-info>
+"#;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DesktopAction {
+    Register,
+    Unregister,
+}
+
+/// Where `--register-desktop` installs its files for one user.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub struct DesktopIntegration {
+    data_home: PathBuf,
+    config_home: PathBuf,
+}
+
+/// `$<var>` when it is an absolute path, otherwise `$HOME/<fallback>`, as the XDG base
+/// directory spec requires.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn xdg_dir(var: &str, fallback: &str) -> Option<PathBuf> {
+    env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(fallback)))
+}
+
+/// Quotes one `Exec=` argument per the desktop entry spec, then applies the string escape
+/// that the spec layers on top of it.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn quote_exec_arg(arg: &str) -> String {
+    const RESERVED: &[char] = &[
+        ' ', '\t', '\n', '"', '\'', '\\', '>', '<', '~', '|', '&', ';', '$', '*', '?', '#', '(',
+        ')', '`',
+    ];
+
+    let quoted = if arg.contains(RESERVED) {
+        let mut quoted = String::from("\"");
+        for ch in arg.chars() {
+            if matches!(ch, '"' | '`' | '$' | '\\') {
+                quoted.push('\\');
+            }
+            quoted.push(ch);
+        }
+        quoted.push('"');
+        quoted
+    } else {
+        arg.to_string()
+    };
+
+    quoted.replace('\\', "\\\\").replace('%', "%%")
+}
+
+/// The executable to put in `Exec=`: the AppImage itself when running from one, since the
+/// mounted binary path changes on every launch.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn current_executable() -> Result<PathBuf, String> {
+    if let Some(app_image) = env::var_os("APPIMAGE").map(PathBuf::from) {
+        if app_image.is_absolute() {
+            return Ok(app_image);
+        }
+    }
+
+    env::current_exe().map_err(|err| format!("Failed to locate the mudkip executable: {err}"))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn desktop_entry(executable: &Path) -> String {
+    let wm_class = executable
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| ICON_NAME.to_string());
+
+    format!(
+        "[Desktop Entry]\n\
+         Type=Application\n\
+         Name=Mudkip\n\
+         Comment=Read-only markdown viewer\n\
+         Categories=Office;Viewer;\n\
+         Exec={} %U\n\
+         StartupWMClass={wm_class}\n\
+         Icon={ICON_NAME}\n\
+         Terminal=false\n\
+         MimeType={};\n",
+        quote_exec_arg(&executable.to_string_lossy()),
+        MIME_TYPES.join(";")
+    )
+}
+
+/// Adds (or removes) `mudkip.desktop` as the preferred handler for [`MIME_TYPES`] in a
+/// `mimeapps.list`, leaving other sections and handlers untouched.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn update_mimeapps_list(source: &str, action: DesktopAction) -> String {
+    let mut sections: Vec<(String, Vec<String>)> = vec![(String::new(), Vec::new())];
+    for line in source.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') && trimmed.ends_with(']') {
+            sections.push((trimmed.to_string(), Vec::new()));
+        } else if let Some((_, lines)) = sections.last_mut() {
+            lines.push(line.to_string());
+        }
+    }
+
+    for header in [DEFAULT_APPLICATIONS_SECTION, ADDED_ASSOCIATIONS_SECTION] {
+        if !sections.iter().any(|(name, _)| name == header) {
+            if action == DesktopAction::Unregister {
+                continue;
+            }
+            if let Some((_, lines)) = sections.last_mut() {
+                if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+                    lines.push(String::new());
+                }
+            }
+            sections.push((header.to_string(), Vec::new()));
+        }
+
+        let Some((_, lines)) = sections.iter_mut().find(|(name, _)| name == header) else {
+            continue;
+        };
+
+        for mime in MIME_TYPES {
+            let existing = lines.iter().position(|line| {
+                line.split_once('=')
+                    .is_some_and(|(key, _)| key.trim() == mime)
+            });
+            let mut handlers: Vec<String> = existing
+                .and_then(|index| lines[index].split_once('='))
+                .map(|(_, value)| {
+                    value
+                        .split(';')
+                        .map(str::trim)
+                        .filter(|handler| !handler.is_empty() && *handler != DESKTOP_FILE_NAME)
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default();
+            if action == DesktopAction::Register {
+                handlers.insert(0, DESKTOP_FILE_NAME.to_string());
+            }
+
+            let entry = (!handlers.is_empty()).then(|| format!("{mime}={};", handlers.join(";")));
+            match (existing, entry) {
+                (Some(index), Some(entry)) => lines[index] = entry,
+                (Some(index), None) => {
+                    lines.remove(index);
+                }
+                (None, Some(entry)) => {
+                    let insert_at = lines
+                        .iter()
+                        .rposition(|line| !line.trim().is_empty())
+                        .map_or(0, |index| index + 1);
+                    lines.insert(insert_at, entry);
+                }
+                (None, None) => {}
+            }
+        }
+    }
+
+    if action == DesktopAction::Unregister {
+        sections.retain(|(name, lines)| {
+            let managed =
+                name == DEFAULT_APPLICATIONS_SECTION || name == ADDED_ASSOCIATIONS_SECTION;
+            !managed || lines.iter().any(|line| !line.trim().is_empty())
+        });
+    }
+
+    let mut output: Vec<String> = Vec::new();
+    for (header, lines) in sections {
+        if !header.is_empty() {
+            output.push(header);
+        }
+        output.extend(lines);
+    }
+    // Drop the blank line that separated a section removed from the end.
+    if action == DesktopAction::Unregister {
+        while output.last().is_some_and(|line| line.trim().is_empty()) {
+            output.pop();
+        }
+    }
+
+    if output.iter().all(|line| line.trim().is_empty()) {
+        return String::new();
+    }
+    output.join("\n") + "\n"
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn write_file(path: &Path, contents: &[u8]) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|err| format!("Failed to create '{}': {err}", parent.display()))?;
+    }
+    fs::write(path, contents).map_err(|err| format!("Failed to write '{}': {err}", path.display()))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn remove_file_if_present(path: &Path) -> Result<(), String> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(format!("Failed to remove '{}': {err}", path.display())),
+    }
+}
+
+/// Runs a cache refresh tool if it is installed; desktops pick the files up on their own
+/// eventually when it is not.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn refresh_cache(command: &str, dir: &Path) {
+    if let Err(err) = Command::new(command).arg(dir).status() {
+        log::info!("Skipping {command}: {err}");
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+impl DesktopIntegration {
+    pub fn new(data_home: PathBuf, config_home: PathBuf) -> Self {
+        Self {
+            data_home,
+            config_home,
+        }
+    }
+
+    /// The current user's `$XDG_DATA_HOME` and `$XDG_CONFIG_HOME`.
+    pub fn for_current_user() -> Result<Self, String> {
+        let data_home = xdg_dir("XDG_DATA_HOME", ".local/share");
+        let config_home = xdg_dir("XDG_CONFIG_HOME", ".config");
+        match (data_home, config_home) {
+            (Some(data_home), Some(config_home)) => Ok(Self::new(data_home, config_home)),
+            _ => Err("Cannot locate the XDG data directories: HOME is not set.".to_string()),
+        }
+    }
+
+    fn applications_dir(&self) -> PathBuf {
+        self.data_home.join("applications")
+    }
+
+    fn mime_dir(&self) -> PathBuf {
+        self.data_home.join("mime")
+    }
+
+    pub fn desktop_entry_path(&self) -> PathBuf {
+        self.applications_dir().join(DESKTOP_FILE_NAME)
+    }
+
+    pub fn icon_path(&self) -> PathBuf {
+        self.data_home
+            .join("icons/hicolor/128x128/apps")
+            .join(format!("{ICON_NAME}.png"))
+    }
+
+    pub fn mime_package_path(&self) -> PathBuf {
+        self.mime_dir()
+            .join("packages")
+            .join(MIME_PACKAGE_FILE_NAME)
+    }
+
+    pub fn mimeapps_path(&self) -> PathBuf {
+        self.config_home.join(MIMEAPPS_FILE_NAME)
+    }
+
+    fn update_mimeapps(&self, action: DesktopAction) -> Result<(), String> {
+        let path = self.mimeapps_path();
+        let source = match fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(format!("Failed to read '{}': {err}", path.display())),
+        };
+
+        let updated = update_mimeapps_list(&source, action);
+        if updated == source {
+            return Ok(());
+        }
+        write_file(&path, updated.as_bytes())
+    }
+
+    /// Installs the desktop entry, icon and MIME package, and makes Mudkip the default handler.
+    pub fn install(&self, executable: &Path) -> Result<(), String> {
+        write_file(
+            &self.desktop_entry_path(),
+            desktop_entry(executable).as_bytes(),
+        )?;
+        write_file(&self.icon_path(), ICON_PNG)?;
+        write_file(&self.mime_package_path(), MIME_PACKAGE_XML.as_bytes())?;
+        self.update_mimeapps(DesktopAction::Register)
+    }
+
+    /// Removes everything [`Self::install`] added, keeping other handlers in `mimeapps.list`.
+    pub fn uninstall(&self) -> Result<(), String> {
+        remove_file_if_present(&self.desktop_entry_path())?;
+        remove_file_if_present(&self.icon_path())?;
+        remove_file_if_present(&self.mime_package_path())?;
+        self.update_mimeapps(DesktopAction::Unregister)
+    }
+
+    fn refresh_caches(&self) {
+        refresh_cache("update-desktop-database", &self.applications_dir());
+        refresh_cache("update-mime-database", &self.mime_dir());
+    }
+}
+
+/// Runs `--register-desktop` or `--unregister-desktop` and returns the process exit code.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn run(action: DesktopAction) -> i32 {
+    let result = DesktopIntegration::for_current_user().and_then(|integration| {
+        match action {
+            DesktopAction::Register => integration.install(&current_executable()?)?,
+            DesktopAction::Unregister => integration.uninstall()?,
+        }
+        integration.refresh_caches();
+        Ok(integration)
+    });
+
+    match (result, action) {
+        (Ok(integration), DesktopAction::Register) => {
+            println!(
+                "Registered {} as the handler for markdown files.",
+                integration.desktop_entry_path().display()
+            );
+            0
+        }
+        (Ok(_), DesktopAction::Unregister) => {
+            println!("Removed the Mudkip desktop entry and file associations.");
+            0
+        }
+        (Err(err), _) => {
+            eprintln!("{err}");
+            1
+        }
+    }
+}
+
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+pub fn run(_action: DesktopAction) -> i32 {
+    eprintln!("Desktop registration is only supported on Linux and other XDG desktops.");
+    1
+}
+
+#[cfg(all(test, unix, not(target_os = "macos")))]
+mod tests {
+    use super::*;
+    use crate::test_support::create_temp_directory;
+
+    #[test]
+    fn exec_arguments_are_quoted_and_escaped() {
+        assert_eq!(quote_exec_arg("/usr/bin/mudkip"), "/usr/bin/mudkip");
+        assert_eq!(
+            quote_exec_arg("/home/me/My Apps/mudkip"),
+            "\"/home/me/My Apps/mudkip\""
+        );
+        assert_eq!(
+            quote_exec_arg("/opt/$weird\\\"path"),
+            "\"/opt/\\\\$weird\\\\\\\\\\\\\"path\""
+        );
+        assert_eq!(quote_exec_arg("/opt/100%/mudkip"), "/opt/100%%/mudkip");
+
+        let entry = desktop_entry(Path::new("/home/me/My Apps/mudkip"));
+        assert!(entry.contains("Exec=\"/home/me/My Apps/mudkip\" %U\n"));
+        assert!(entry.contains("MimeType=text/markdown;text/x-markdown;x-scheme-handler/mudkip;\n"));
+    }
+
+    #[test]
+    fn mimeapps_list_prefers_mudkip_and_restores_other_handlers() {
+        let source = "[Default Applications]\n\
+                      text/markdown=org.gnome.TextEditor.desktop;\n\
+                      image/png=org.gnome.Loupe.desktop;\n";
+
+        let registered = update_mimeapps_list(source, DesktopAction::Register);
+        assert_eq!(
+            registered,
+            "[Default Applications]\n\
+             text/markdown=mudkip.desktop;org.gnome.TextEditor.desktop;\n\
+             image/png=org.gnome.Loupe.desktop;\n\
+             text/x-markdown=mudkip.desktop;\n\
+             x-scheme-handler/mudkip=mudkip.desktop;\n\
+             \n\
+             [Added Associations]\n\
+             text/markdown=mudkip.desktop;\n\
+             text/x-markdown=mudkip.desktop;\n\
+             x-scheme-handler/mudkip=mudkip.desktop;\n"
+        );
+        assert_eq!(
+            update_mimeapps_list(&registered, DesktopAction::Register),
+            registered
+        );
+
+        let unregistered = update_mimeapps_list(&registered, DesktopAction::Unregister);
+        assert_eq!(
+            unregistered,
+            "[Default Applications]\n\
+             text/markdown=org.gnome.TextEditor.desktop;\n\
+             image/png=org.gnome.Loupe.desktop;\n"
+        );
+        assert_eq!(update_mimeapps_list("", DesktopAction::Unregister), "");
+    }
+
+    #[test]
+    fn install_and_uninstall_manage_xdg_files() {
+        let root = create_temp_directory("desktop");
+        let integration = DesktopIntegration::new(root.join("data"), root.join("config"));
+
+        integration
+            .install(Path::new("/usr/bin/mudkip"))
+            .expect("install should succeed");
+        let entry = fs::read_to_string(integration.desktop_entry_path())
+            .expect("desktop entry should be written");
+        assert!(entry.contains("Exec=/usr/bin/mudkip %U\n"));
+        assert!(integration.icon_path().is_file());
+        assert!(integration.mime_package_path().is_file());
+        let mimeapps =
+            fs::read_to_string(integration.mimeapps_path()).expect("mimeapps.list should exist");
+        assert!(mimeapps.contains("text/markdown=mudkip.desktop;"));
+
+        integration.uninstall().expect("uninstall should succeed");
+        assert!(!integration.desktop_entry_path().exists());
+        assert!(!integration.icon_path().exists());
+        assert!(!integration.mime_package_path().exists());
+        let mimeapps =
+            fs::read_to_string(integration.mimeapps_path()).expect("mimeapps.list should remain");
+        assert!(!mimeapps.contains(DESKTOP_FILE_NAME));
+
+        let _ = fs::remove_dir_all(root);
+    }
+}
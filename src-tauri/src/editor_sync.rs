@@ -0,0 +1,169 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+#[cfg(unix)]
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+/// Keeps a stalled editor from blocking preview position updates to the others.
+#[cfg(unix)]
+const CLIENT_WRITE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// A cursor or viewport position exchanged with an editor, one JSON object per line:
+/// `{"path":"/repo/docs/api.md","line":42}`. Lines are 1-based.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CursorMessage {
+    pub path: String,
+    pub line: u32,
+}
+
+pub fn editor_sync_socket_path() -> PathBuf {
+    crate::remote_control::runtime_socket_path("mudkip-sync")
+}
+
+/// Connected editors. Cursor messages they send go to the handler passed to `spawn_server`;
+/// `broadcast` sends the preview position to all of them.
+#[derive(Default)]
+pub struct EditorSyncHub {
+    #[cfg(unix)]
+    clients: Mutex<Vec<UnixStream>>,
+}
+
+impl EditorSyncHub {
+    #[cfg(unix)]
+    pub fn broadcast(&self, message: &CursorMessage) {
+        let Ok(serialized) = serde_json::to_string(message) else {
+            return;
+        };
+        if let Ok(mut clients) = self.clients.lock() {
+            clients.retain_mut(|client| writeln!(client, "{serialized}").is_ok());
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn broadcast(&self, _message: &CursorMessage) {}
+}
+
+#[cfg(unix)]
+fn read_cursor_messages<F>(stream: UnixStream, handler: &F)
+where
+    F: Fn(CursorMessage),
+{
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<CursorMessage>(&line) {
+            Ok(message) => handler(message),
+            Err(err) => log::warn!("Ignoring invalid editor sync message: {err}"),
+        }
+    }
+}
+
+#[cfg(unix)]
+pub fn spawn_server<F>(socket_path: PathBuf, handler: F) -> Result<Arc<EditorSyncHub>, String>
+where
+    F: Fn(CursorMessage) + Send + Sync + 'static,
+{
+    let listener = crate::remote_control::bind_socket(&socket_path)?;
+    let hub = Arc::new(EditorSyncHub::default());
+    let handler = Arc::new(handler);
+
+    let server_hub = hub.clone();
+    thread::Builder::new()
+        .name("editor-sync".to_string())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                match stream.try_clone() {
+                    Ok(writer) => {
+                        let _ = writer.set_write_timeout(Some(CLIENT_WRITE_TIMEOUT));
+                        if let Ok(mut clients) = server_hub.clients.lock() {
+                            clients.push(writer);
+                        }
+                    }
+                    Err(err) => log::warn!("Failed to track editor sync client: {err}"),
+                }
+
+                let handler = handler.clone();
+                thread::spawn(move || read_cursor_messages(stream, handler.as_ref()));
+            }
+        })
+        .map_err(|err| format!("Failed to start editor sync server: {err}"))?;
+
+    Ok(hub)
+}
+
+#[cfg(not(unix))]
+pub fn spawn_server<F>(
+    _socket_path: PathBuf,
+    _handler: F,
+) -> Result<std::sync::Arc<EditorSyncHub>, String>
+where
+    F: Fn(CursorMessage) + Send + Sync + 'static,
+{
+    Err("Editor sync is only available on platforms with Unix domain sockets.".to_string())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::test_support::create_temp_directory;
+    use std::{fs, os::unix::fs::PermissionsExt, sync::mpsc};
+
+    #[test]
+    fn editor_sync_exchanges_cursor_messages_with_a_script_client() {
+        let socket_directory = create_temp_directory("sync");
+        fs::set_permissions(&socket_directory, fs::Permissions::from_mode(0o700))
+            .expect("should make socket directory private");
+        let socket_path = socket_directory.join("sync.sock");
+
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let hub = spawn_server(socket_path.clone(), move |message| {
+            let _ = sender.lock().expect("sender lock").send(message);
+        })
+        .expect("server should start");
+
+        // Stands in for an editor extension, the same way `nc -U` would.
+        let mut editor = UnixStream::connect(&socket_path).expect("should connect");
+        editor
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .expect("should set timeout");
+        writeln!(editor, "not json").expect("should write");
+        writeln!(editor, r#"{{"path":"/docs/a.md","line":42}}"#).expect("should write");
+
+        let received = receiver
+            .recv_timeout(Duration::from_secs(5))
+            .expect("cursor message should arrive");
+        assert_eq!(
+            received,
+            CursorMessage {
+                path: "/docs/a.md".to_string(),
+                line: 42
+            }
+        );
+
+        hub.broadcast(&CursorMessage {
+            path: "/docs/a.md".to_string(),
+            line: 7,
+        });
+        let mut reply = String::new();
+        BufReader::new(&editor)
+            .read_line(&mut reply)
+            .expect("preview position should arrive");
+        assert_eq!(reply.trim(), r#"{"path":"/docs/a.md","line":7}"#);
+
+        let _ = fs::remove_dir_all(socket_directory);
+    }
+}
@@ -0,0 +1,224 @@
+use crate::front_matter::{front_matter_end, front_matter_values};
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Ordering,
+    fs::File,
+    io::Read,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Only the start of each file is read for its title, so large folders list quickly.
+const TITLE_SCAN_BYTES: u64 = 16 * 1024;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FolderSortMode {
+    /// Case-insensitive file name.
+    #[default]
+    Name,
+    /// File name with digit runs compared as numbers, so `2-setup.md` precedes `10-intro.md`.
+    Natural,
+    /// Most recently modified first.
+    Modified,
+    /// Document title, falling back to the file name.
+    Title,
+}
+
+/// The fields a folder listing is sorted by.
+pub struct SortKey<'a> {
+    pub file_name: &'a str,
+    pub title: Option<&'a str>,
+    pub modified: Option<u64>,
+}
+
+pub fn modified_millis(time: SystemTime) -> Option<u64> {
+    time.duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|duration| duration.as_millis() as u64)
+}
+
+/// Compares digit runs by value and everything else case-insensitively.
+pub fn natural_cmp(left: &str, right: &str) -> Ordering {
+    let mut left_chars = left.chars().peekable();
+    let mut right_chars = right.chars().peekable();
+
+    loop {
+        match (left_chars.peek().copied(), right_chars.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(l), Some(r)) if l.is_ascii_digit() && r.is_ascii_digit() => {
+                let take_number = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut digits = String::new();
+                    while let Some(ch) = chars.next_if(char::is_ascii_digit) {
+                        digits.push(ch);
+                    }
+                    digits
+                };
+                let left_digits = take_number(&mut left_chars);
+                let right_digits = take_number(&mut right_chars);
+                let left_value = left_digits.trim_start_matches('0');
+                let right_value = right_digits.trim_start_matches('0');
+                let ordering = left_value
+                    .len()
+                    .cmp(&right_value.len())
+                    .then_with(|| left_value.cmp(right_value));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(l), Some(r)) => {
+                let ordering = l.to_lowercase().cmp(r.to_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                left_chars.next();
+                right_chars.next();
+            }
+        }
+    }
+}
+
+fn name_cmp(left: &str, right: &str) -> Ordering {
+    left.to_ascii_lowercase()
+        .cmp(&right.to_ascii_lowercase())
+        .then_with(|| left.cmp(right))
+}
+
+pub fn compare(mode: FolderSortMode, left: &SortKey, right: &SortKey) -> Ordering {
+    let by_name = || name_cmp(left.file_name, right.file_name);
+    match mode {
+        FolderSortMode::Name => by_name(),
+        FolderSortMode::Natural => natural_cmp(left.file_name, right.file_name).then_with(by_name),
+        FolderSortMode::Modified => right.modified.cmp(&left.modified).then_with(by_name),
+        FolderSortMode::Title => natural_cmp(
+            left.title.unwrap_or(left.file_name),
+            right.title.unwrap_or(right.file_name),
+        )
+        .then_with(by_name),
+    }
+}
+
+/// The `title:` front-matter value, otherwise the first level-one heading.
+pub fn markdown_title(content: &str) -> Option<String> {
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+    let body = match front_matter_end(content) {
+        Some(end) => {
+            if let Some(title) = front_matter_values(content, "title")
+                .into_iter()
+                .next()
+                .filter(|title| !title.trim().is_empty())
+            {
+                return Some(title.trim().to_string());
+            }
+            &content[end..]
+        }
+        // An unterminated front-matter block was cut off by the scan limit; its lines are
+        // not headings.
+        None if content.lines().next().map(str::trim_end) == Some("---") => return None,
+        None => content,
+    };
+
+    let mut in_code_block = false;
+    let mut previous_line: Option<&str> = None;
+    for line in body.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code_block = !in_code_block;
+            previous_line = None;
+            continue;
+        }
+        if in_code_block {
+            continue;
+        }
+
+        let indent = line.len() - line.trim_start().len();
+        if indent < 4 {
+            if let Some(heading) = trimmed
+                .strip_prefix('#')
+                .filter(|rest| rest.is_empty() || rest.starts_with(' ') || rest.starts_with('\t'))
+            {
+                let heading = heading.trim().trim_end_matches('#').trim_end();
+                if !heading.is_empty() {
+                    return Some(heading.to_string());
+                }
+            }
+            // Setext heading: a paragraph line underlined with `=`.
+            if !trimmed.is_empty() && trimmed.chars().all(|ch| ch == '=') {
+                if let Some(heading) = previous_line {
+                    return Some(heading.to_string());
+                }
+            }
+        }
+
+        previous_line = Some(trimmed).filter(|line| !line.is_empty() && indent < 4);
+    }
+
+    None
+}
+
+pub fn read_markdown_title(path: &Path) -> Option<String> {
+    let mut bytes = Vec::new();
+    File::open(path)
+        .ok()?
+        .take(TITLE_SCAN_BYTES)
+        .read_to_end(&mut bytes)
+        .ok()?;
+    markdown_title(&String::from_utf8_lossy(&bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markdown_title_prefers_front_matter_then_first_h1() {
+        assert_eq!(
+            markdown_title("---\ntitle: \"Guide\"\n---\n# Heading\n"),
+            Some("Guide".to_string())
+        );
+        assert_eq!(
+            markdown_title("---\ntags: [a]\n---\n\n```\n# not a title\n```\n## Sub\n# Setup #\n"),
+            Some("Setup".to_string())
+        );
+        assert_eq!(
+            markdown_title("Overview\n========\n\ntext\n"),
+            Some("Overview".to_string())
+        );
+        assert_eq!(markdown_title("---\ntitle: cut off\n# x\n"), None);
+        assert_eq!(markdown_title("#hashtag\n\ntext\n"), None);
+    }
+
+    #[test]
+    fn compare_orders_by_each_sort_mode() {
+        let key = |file_name, title, modified| SortKey {
+            file_name,
+            title,
+            modified,
+        };
+        let mut files = [
+            key("10-intro.md", Some("Zebra"), Some(3)),
+            key("2-setup.md", None, Some(1)),
+            key("1-Basics.md", Some("Apple"), Some(2)),
+        ];
+        let order = |files: &[SortKey]| -> Vec<String> {
+            files
+                .iter()
+                .map(|file| file.file_name.to_string())
+                .collect()
+        };
+
+        files.sort_by(|left, right| compare(FolderSortMode::Name, left, right));
+        assert_eq!(order(&files), ["1-Basics.md", "10-intro.md", "2-setup.md"]);
+        files.sort_by(|left, right| compare(FolderSortMode::Natural, left, right));
+        assert_eq!(order(&files), ["1-Basics.md", "2-setup.md", "10-intro.md"]);
+        files.sort_by(|left, right| compare(FolderSortMode::Modified, left, right));
+        assert_eq!(order(&files), ["10-intro.md", "1-Basics.md", "2-setup.md"]);
+        files.sort_by(|left, right| compare(FolderSortMode::Title, left, right));
+        assert_eq!(order(&files), ["2-setup.md", "1-Basics.md", "10-intro.md"]);
+
+        assert_eq!(natural_cmp("file007.md", "file7.md"), Ordering::Equal);
+        assert_eq!(natural_cmp("a2b", "a10a"), Ordering::Less);
+    }
+}
@@ -0,0 +1,131 @@
+/// Finds a leading YAML front-matter block and returns its body together with the offset in
+/// `content` just past the closing fence's line.
+fn locate_front_matter(content: &str) -> Option<(&str, usize)> {
+    let start = if content.starts_with('\u{feff}') {
+        '\u{feff}'.len_utf8()
+    } else {
+        0
+    };
+    let content = &content[start..];
+    let first_line_end = content.find('\n')?;
+    if content[..first_line_end].trim_end() != "---" {
+        return None;
+    }
+
+    let body_start = first_line_end + 1;
+    let body = &content[body_start..];
+    let mut offset = 0usize;
+    for line in body.split_inclusive('\n') {
+        let trimmed = line.trim_end();
+        if trimmed == "---" || trimmed == "..." {
+            return Some((&body[..offset], start + body_start + offset + line.len()));
+        }
+        offset += line.len();
+    }
+
+    None
+}
+
+/// Returns the body of a leading YAML front-matter block (between `---` fences), if any.
+pub fn front_matter_block(content: &str) -> Option<&str> {
+    locate_front_matter(content).map(|(block, _)| block)
+}
+
+/// Offset in `content` where the document continues after its front-matter block, if any.
+pub fn front_matter_end(content: &str) -> Option<usize> {
+    locate_front_matter(content).map(|(_, end)| end)
+}
+
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    for quote in ['"', '\''] {
+        if let Some(inner) = value
+            .strip_prefix(quote)
+            .and_then(|rest| rest.strip_suffix(quote))
+        {
+            return inner;
+        }
+    }
+    value
+}
+
+/// Reads a top-level front-matter key as a list of strings. Scalars, inline lists
+/// (`[a, b]`) and block lists (`- a`) are supported; nested mappings are not.
+pub fn front_matter_values(content: &str, key: &str) -> Vec<String> {
+    let Some(block) = front_matter_block(content) else {
+        return Vec::new();
+    };
+
+    let mut lines = block.lines();
+    while let Some(line) = lines.next() {
+        let Some(raw_value) = line
+            .strip_prefix(key)
+            .and_then(|rest| rest.strip_prefix(':'))
+        else {
+            continue;
+        };
+
+        let raw_value = raw_value.split(" #").next().unwrap_or_default().trim();
+
+        if raw_value.is_empty() {
+            return lines
+                .take_while(|item| item.starts_with(' ') || item.starts_with('\t'))
+                .filter_map(|item| item.trim().strip_prefix("- "))
+                .map(|item| unquote(item).to_string())
+                .filter(|item| !item.is_empty())
+                .collect();
+        }
+
+        if let Some(inner) = raw_value
+            .strip_prefix('[')
+            .and_then(|rest| rest.strip_suffix(']'))
+        {
+            return inner
+                .split(',')
+                .map(|item| unquote(item).to_string())
+                .filter(|item| !item.is_empty())
+                .collect();
+        }
+
+        return vec![unquote(raw_value).to_string()];
+    }
+
+    Vec::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn front_matter_values_reads_scalars_and_lists() {
+        let content = "---\ntitle: \"Guide\"\ncss:\n  - wide.css\n  - 'print.css'\ntags: [a, b]\n---\n# Body\n";
+
+        assert_eq!(front_matter_values(content, "title"), vec!["Guide"]);
+        assert_eq!(
+            front_matter_values(content, "css"),
+            vec!["wide.css", "print.css"]
+        );
+        assert_eq!(front_matter_values(content, "tags"), vec!["a", "b"]);
+        assert!(front_matter_values(content, "missing").is_empty());
+    }
+
+    #[test]
+    fn front_matter_block_requires_leading_fence() {
+        assert_eq!(front_matter_block("# Title\n---\ncss: a.css\n---\n"), None);
+        assert_eq!(front_matter_block("---\ncss: a.css\n"), None);
+        assert_eq!(
+            front_matter_block("---\r\ncss: a.css\r\n---\r\n"),
+            Some("css: a.css\r\n")
+        );
+    }
+
+    #[test]
+    fn front_matter_end_points_past_the_closing_fence() {
+        let content = "\u{feff}---\ntitle: x\n...\n# Body\n";
+        let end = front_matter_end(content).expect("block should be found");
+        assert_eq!(&content[end..], "# Body\n");
+        assert_eq!(front_matter_end("---\ntitle: x\n---"), Some(16));
+        assert_eq!(front_matter_end("# Body\n"), None);
+    }
+}
@@ -0,0 +1,765 @@
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+/// A hooks directory that cannot hold any hooks.
+#[cfg(windows)]
+const NO_HOOKS_PATH: &str = "core.hooksPath=NUL";
+#[cfg(not(windows))]
+const NO_HOOKS_PATH: &str = "core.hooksPath=/dev/null";
+
+const FIELD_SEPARATOR: char = '\u{1f}';
+const RECORD_SEPARATOR: char = '\u{1e}';
+/// One record per commit: `%x1e` starts it, fields are `%x1f`-separated and `--name-only`
+/// appends the file's path in that commit after the last separator.
+const LOG_FORMAT: &str = "--format=%x1e%H%x1f%h%x1f%an%x1f%ae%x1f%aI%x1f%s%x1f%b%x1f";
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GitCommitPayload {
+    pub hash: String,
+    pub short_hash: String,
+    pub author_name: String,
+    pub author_email: String,
+    /// Author date in strict ISO 8601.
+    pub date: String,
+    pub subject: String,
+    pub body: String,
+    /// Repository-relative path of the file in this commit; differs from the current path
+    /// before a rename.
+    pub path: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GitFileHistoryPayload {
+    pub repository_root: String,
+    pub relative_path: String,
+    /// Newest first, so the first entry is the last commit that touched the file.
+    pub commits: Vec<GitCommitPayload>,
+}
+
+/// Consecutive lines of the working copy last changed by the same commit, 1-based and
+/// inclusive like `data-source-line`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GitBlameHunk {
+    pub start_line: usize,
+    pub end_line: usize,
+    /// All zeros for lines that are not committed yet.
+    pub hash: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GitBlamePayload {
+    pub hunks: Vec<GitBlameHunk>,
+    /// Every commit referenced by `hunks`, with its full message.
+    pub commits: Vec<GitCommitPayload>,
+}
+
+/// Working-tree state of a file as shown in the folder list; clean files have none.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum GitFileStatus {
+    Modified,
+    Added,
+    Untracked,
+    Ignored,
+    Conflicted,
+}
+
+impl GitFileStatus {
+    /// Maps the two-letter `XY` code of `git status --porcelain`.
+    fn from_porcelain(code: &str) -> Option<Self> {
+        match code {
+            "??" => Some(Self::Untracked),
+            "!!" => Some(Self::Ignored),
+            "DD" | "AU" | "UD" | "UA" | "DU" | "AA" | "UU" => Some(Self::Conflicted),
+            code if code.starts_with('A') => Some(Self::Added),
+            code if code.trim().is_empty() => None,
+            _ => Some(Self::Modified),
+        }
+    }
+}
+
+/// Statuses keyed by repository-relative path. Untracked or ignored directories are reported
+/// once with a trailing `/` and apply to everything below them.
+#[derive(Debug, Default)]
+pub struct GitStatusSnapshot {
+    root: PathBuf,
+    entries: HashMap<String, GitFileStatus>,
+}
+
+impl GitStatusSnapshot {
+    /// Whether `path` lies inside the repository this snapshot was taken in.
+    pub fn covers(&self, path: &Path) -> bool {
+        path.starts_with(&self.root)
+    }
+
+    pub fn status_of(&self, path: &Path) -> Option<GitFileStatus> {
+        let relative_path = relative_to(&self.root, path)?;
+        if let Some(status) = self.entries.get(&relative_path) {
+            return Some(*status);
+        }
+
+        relative_path
+            .match_indices('/')
+            .map(|(index, _)| &relative_path[..=index])
+            .find_map(|directory| self.entries.get(directory).copied())
+    }
+}
+
+fn parse_status(output: &str) -> HashMap<String, GitFileStatus> {
+    let mut entries = HashMap::new();
+    // With `-z`, a rename or copy is followed by an extra entry holding the original path.
+    let mut records = output.split('\0');
+    while let Some(record) = records.next() {
+        if record.len() < 4 {
+            continue;
+        }
+        let (code, path) = record.split_at(2);
+        if code.contains(['R', 'C']) {
+            records.next();
+        }
+        if let Some(status) = GitFileStatus::from_porcelain(code) {
+            entries.insert(path[1..].to_string(), status);
+        }
+    }
+    entries
+}
+
+/// Reads the line headers of `git blame --porcelain` (`<hash> <orig> <final> [<count>]`) and
+/// merges runs of lines from the same commit.
+fn parse_blame(output: &str) -> Vec<GitBlameHunk> {
+    let mut hunks: Vec<GitBlameHunk> = Vec::new();
+
+    for line in output.lines().filter(|line| !line.starts_with('\t')) {
+        let mut fields = line.split(' ');
+        let (Some(hash), Some(_), Some(final_line)) = (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        if hash.len() < 40 || !is_commit_hash(hash) {
+            continue;
+        }
+        let Ok(final_line) = final_line.parse::<usize>() else {
+            continue;
+        };
+
+        match hunks.last_mut() {
+            Some(last) if last.hash == hash && last.end_line + 1 == final_line => {
+                last.end_line = final_line;
+            }
+            _ => hunks.push(GitBlameHunk {
+                start_line: final_line,
+                end_line: final_line,
+                hash: hash.to_string(),
+            }),
+        }
+    }
+
+    hunks
+}
+
+fn escape_glob(path: &str) -> String {
+    path.chars()
+        .flat_map(|ch| {
+            let escape = matches!(ch, '*' | '?' | '[' | ']' | '\\').then_some('\\');
+            escape.into_iter().chain([ch])
+        })
+        .collect()
+}
+
+fn relative_to(root: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+    let components: Vec<String> = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().to_string())
+        .collect();
+    (!components.is_empty()).then(|| components.join("/"))
+}
+
+pub struct GitRepository {
+    root: PathBuf,
+}
+
+/// Accepts full or abbreviated object names only, so a revision from the webview can never be
+/// read as a git option or an arbitrary revision expression.
+pub fn is_commit_hash(value: &str) -> bool {
+    (4..=64).contains(&value.len()) && value.chars().all(|ch| ch.is_ascii_hexdigit())
+}
+
+fn parse_log(output: &str, relative_path: &str) -> Vec<GitCommitPayload> {
+    let mut commits = Vec::new();
+    let mut newer_path = relative_path.to_string();
+
+    for record in output.split(RECORD_SEPARATOR) {
+        let mut fields = record.splitn(8, FIELD_SEPARATOR);
+        let Some(hash) = fields.next().map(str::trim).filter(|hash| !hash.is_empty()) else {
+            continue;
+        };
+        let mut next_field = || fields.next().unwrap_or_default().to_string();
+        let short_hash = next_field();
+        let author_name = next_field();
+        let author_email = next_field();
+        let date = next_field();
+        let subject = next_field();
+        let body = next_field().trim().to_string();
+        // Merge commits list no files; they keep the path of the newer commit.
+        let path = next_field()
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .map(str::to_string)
+            .unwrap_or_else(|| newer_path.clone());
+
+        newer_path = path.clone();
+        commits.push(GitCommitPayload {
+            hash: hash.to_string(),
+            short_hash,
+            author_name,
+            author_email,
+            date,
+            subject,
+            body,
+            path,
+        });
+    }
+
+    commits
+}
+
+impl GitRepository {
+    /// Finds the repository containing `path` by walking up to a `.git` directory, or the
+    /// `.git` file of a worktree or submodule. Only the local filesystem is consulted.
+    pub fn discover(path: &Path) -> Option<Self> {
+        path.ancestors()
+            .find(|dir| dir.join(".git").exists())
+            .map(|root| Self {
+                root: root.to_path_buf(),
+            })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// `path` relative to the repository root with `/` separators, as git expects.
+    pub fn relative_path(&self, path: &Path) -> Option<String> {
+        relative_to(&self.root, path)
+    }
+
+    /// The index file git rewrites on `add`, `commit`, `checkout` and friends, also for
+    /// worktrees whose git directory lives elsewhere.
+    pub fn index_path(&self) -> Result<PathBuf, String> {
+        let output = self.run(&["rev-parse", "--git-path", "index"])?;
+        let index_path = PathBuf::from(String::from_utf8_lossy(&output).trim());
+        Ok(self.root.join(index_path))
+    }
+
+    /// Who last changed each line of the working copy of `path`, with the commits involved.
+    pub fn blame(&self, path: &Path) -> Result<GitBlamePayload, String> {
+        let relative_path = self
+            .relative_path(path)
+            .ok_or_else(|| format!("'{}' is not inside the repository.", path.display()))?;
+        let output = self.run(&["blame", "--porcelain", "--", &relative_path])?;
+        let hunks = parse_blame(&String::from_utf8_lossy(&output));
+
+        let mut hashes: Vec<&str> = hunks
+            .iter()
+            .map(|hunk| hunk.hash.as_str())
+            .filter(|hash| hash.chars().any(|ch| ch != '0'))
+            .collect();
+        hashes.sort_unstable();
+        hashes.dedup();
+
+        let commits = if hashes.is_empty() {
+            Vec::new()
+        } else {
+            let mut args = vec!["log", "--no-walk=unsorted", LOG_FORMAT];
+            args.extend(hashes);
+            let output = self.run(&args)?;
+            parse_log(&String::from_utf8_lossy(&output), &relative_path)
+        };
+
+        Ok(GitBlamePayload { hunks, commits })
+    }
+
+    /// Working-tree status of the files directly inside each of `folders`, including ignored
+    /// ones, from a single `git status`.
+    pub fn folder_status(&self, folders: &[&Path]) -> Result<GitStatusSnapshot, String> {
+        // `*` does not cross `/` in glob pathspecs, so subfolders are not scanned.
+        let pathspecs: Vec<String> = folders
+            .iter()
+            .map(|folder| match self.relative_path(folder) {
+                Some(relative_path) => format!(":(glob){}/*", escape_glob(&relative_path)),
+                None => ":(glob)*".to_string(),
+            })
+            .collect();
+        let mut args = vec![
+            "status",
+            "--porcelain=v1",
+            "-z",
+            "--untracked-files=all",
+            "--ignored=matching",
+            "--",
+        ];
+        args.extend(pathspecs.iter().map(String::as_str));
+        let output = self.run(&args)?;
+
+        Ok(GitStatusSnapshot {
+            root: self.root.clone(),
+            entries: parse_status(&String::from_utf8_lossy(&output)),
+        })
+    }
+
+    /// Runs git in the repository. The repository's own config must not be able to run
+    /// programs, so the file system monitor and hooks are switched off.
+    fn run(&self, args: &[&str]) -> Result<Vec<u8>, String> {
+        let output = Command::new("git")
+            .arg("--no-pager")
+            .arg("-C")
+            .arg(&self.root)
+            .args(["-c", "core.quotepath=off"])
+            .args(["-c", "core.fsmonitor=false"])
+            .args(["-c", NO_HOOKS_PATH])
+            .args(args)
+            .env("GIT_OPTIONAL_LOCKS", "0")
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|err| format!("Failed to run git: {err}"))?;
+
+        if !output.status.success() {
+            return Err(format!(
+                "git {} failed: {}",
+                args.first().copied().unwrap_or_default(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(output.stdout)
+    }
+
+    /// Resolves a branch, tag or other revision the user typed to a full commit hash. Names
+    /// starting with `-` are refused so they cannot be read as options.
+    pub fn resolve_revision(&self, revision: &str) -> Result<String, String> {
+        let revision = revision.trim();
+        if revision.is_empty() || revision.starts_with('-') {
+            return Err(format!("'{revision}' is not a valid revision."));
+        }
+
+        let output = self
+            .run(&[
+                "rev-parse",
+                "--verify",
+                "--quiet",
+                &format!("{revision}^{{commit}}"),
+            ])
+            .map_err(|_| format!("Unknown revision '{revision}'."))?;
+        let hash = String::from_utf8_lossy(&output).trim().to_string();
+        if is_commit_hash(&hash) {
+            Ok(hash)
+        } else {
+            Err(format!("Unknown revision '{revision}'."))
+        }
+    }
+
+    /// Every commit that changed the file, following renames.
+    pub fn file_history(&self, path: &Path) -> Result<Vec<GitCommitPayload>, String> {
+        let relative_path = self
+            .relative_path(path)
+            .ok_or_else(|| format!("'{}' is not inside the repository.", path.display()))?;
+        let output = self.run(&[
+            "log",
+            "--follow",
+            "--name-only",
+            LOG_FORMAT,
+            "--",
+            &relative_path,
+        ])?;
+        Ok(parse_log(&String::from_utf8_lossy(&output), &relative_path))
+    }
+
+    /// The file's contents in `commit`, looking up its older path when it was renamed since.
+    pub fn file_at_revision(&self, path: &Path, commit: &str) -> Result<String, String> {
+        if !is_commit_hash(commit) {
+            return Err(format!("'{commit}' is not a commit hash."));
+        }
+
+        let relative_path = self
+            .relative_path(path)
+            .ok_or_else(|| format!("'{}' is not inside the repository.", path.display()))?;
+        let blob = match self.run(&["cat-file", "blob", &format!("{commit}:{relative_path}")]) {
+            Ok(blob) => blob,
+            Err(err) => {
+                let renamed_path = self
+                    .file_history(path)?
+                    .into_iter()
+                    .find(|entry| entry.hash.starts_with(commit) || commit.starts_with(&entry.hash))
+                    .map(|entry| entry.path)
+                    .filter(|renamed_path| *renamed_path != relative_path)
+                    .ok_or(err)?;
+                self.run(&["cat-file", "blob", &format!("{commit}:{renamed_path}")])?
+            }
+        };
+
+        Ok(String::from_utf8_lossy(&blob).to_string())
+    }
+}
+
+/// History for the file at `path`, or `None` when it is not tracked in a git repository or
+/// git is not installed.
+pub fn file_history_payload(path: &Path) -> Option<GitFileHistoryPayload> {
+    let repository = GitRepository::discover(path)?;
+    let commits = repository
+        .file_history(path)
+        .map_err(|err| log::info!("No git history for '{}': {err}", path.display()))
+        .ok()?;
+    if commits.is_empty() {
+        return None;
+    }
+
+    Some(GitFileHistoryPayload {
+        repository_root: repository.root().to_string_lossy().to_string(),
+        relative_path: repository.relative_path(path)?,
+        commits,
+    })
+}
+
+/// Blame for the file at `path`, or `None` when it is not tracked in a git repository or git
+/// is not installed.
+pub fn blame_payload(path: &Path) -> Option<GitBlamePayload> {
+    GitRepository::discover(path)?
+        .blame(path)
+        .map_err(|err| log::info!("No git blame for '{}': {err}", path.display()))
+        .ok()
+}
+
+/// Status of the files in `folder`, empty when it is not inside a git repository or git is not
+/// installed.
+pub fn folder_status(folder: &Path) -> GitStatusSnapshot {
+    let Some(repository) = GitRepository::discover(folder) else {
+        return GitStatusSnapshot::default();
+    };
+
+    repository
+        .folder_status(&[folder])
+        .map_err(|err| log::info!("No git status for '{}': {err}", folder.display()))
+        .unwrap_or_default()
+}
+
+/// Status of the files in each of `folders`, running `git status` once per repository. The
+/// innermost repository comes first, so the first snapshot that covers a path is the one for
+/// the repository the path belongs to.
+pub fn folders_status(folders: &[&Path]) -> Vec<GitStatusSnapshot> {
+    let mut repositories: Vec<(GitRepository, Vec<&Path>)> = Vec::new();
+    for folder in folders {
+        let Some(repository) = GitRepository::discover(folder) else {
+            continue;
+        };
+        match repositories
+            .iter_mut()
+            .find(|(known, _)| known.root == repository.root)
+        {
+            Some((_, repository_folders)) => repository_folders.push(folder),
+            None => repositories.push((repository, vec![folder])),
+        }
+    }
+    repositories.sort_by_key(|(repository, _)| std::cmp::Reverse(repository.root.clone()));
+
+    repositories
+        .into_iter()
+        .filter_map(|(repository, repository_folders)| {
+            repository
+                .folder_status(&repository_folders)
+                .map_err(|err| {
+                    log::info!("No git status for '{}': {err}", repository.root().display())
+                })
+                .ok()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::create_temp_directory;
+    use std::fs;
+
+    fn git(root: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(root)
+            .args([
+                "-c",
+                "user.name=Mudkip Test",
+                "-c",
+                "user.email=test@example.com",
+            ])
+            .args(args)
+            .stdout(Stdio::null())
+            .status()
+            .expect("git should run");
+        assert!(status.success(), "git {args:?} should succeed");
+    }
+
+    #[test]
+    fn parse_log_reads_commits_and_paths_across_renames() {
+        let output = "\u{1e}bbbb\u{1f}bb\u{1f}Ada\u{1f}ada@example.com\u{1f}2024-05-02T10:00:00+00:00\u{1f}Rename guide\u{1f}Longer\nbody\n\u{1f}\n\ndocs/guide.md\n\
+                      \u{1e}cccc\u{1f}cc\u{1f}Ada\u{1f}ada@example.com\u{1f}2024-05-01T10:00:00+00:00\u{1f}Merge\u{1f}\u{1f}\n\
+                      \u{1e}aaaa\u{1f}aa\u{1f}Bob\u{1f}bob@example.com\u{1f}2024-04-01T09:00:00+00:00\u{1f}Add notes\u{1f}\u{1f}\n\nnotes.md\n";
+
+        let commits = parse_log(output, "docs/guide.md");
+
+        assert_eq!(commits.len(), 3);
+        assert_eq!(commits[0].hash, "bbbb");
+        assert_eq!(commits[0].author_name, "Ada");
+        assert_eq!(commits[0].subject, "Rename guide");
+        assert_eq!(commits[0].body, "Longer\nbody");
+        assert_eq!(commits[0].path, "docs/guide.md");
+        assert_eq!(commits[1].path, "docs/guide.md");
+        assert_eq!(commits[2].path, "notes.md");
+        assert_eq!(commits[2].date, "2024-04-01T09:00:00+00:00");
+    }
+
+    #[test]
+    fn parse_status_maps_porcelain_codes_and_skips_rename_sources() {
+        let entries = parse_status(
+            " M docs/a.md\0A  docs/b.md\0R  docs/new.md\0docs/old.md\0?? docs/drafts/\0!! build/out.md\0UU docs/c.md\0",
+        );
+
+        assert_eq!(entries.get("docs/a.md"), Some(&GitFileStatus::Modified));
+        assert_eq!(entries.get("docs/b.md"), Some(&GitFileStatus::Added));
+        assert_eq!(entries.get("docs/new.md"), Some(&GitFileStatus::Modified));
+        assert_eq!(entries.get("docs/old.md"), None);
+        assert_eq!(entries.get("docs/drafts/"), Some(&GitFileStatus::Untracked));
+        assert_eq!(entries.get("build/out.md"), Some(&GitFileStatus::Ignored));
+        assert_eq!(entries.get("docs/c.md"), Some(&GitFileStatus::Conflicted));
+
+        let snapshot = GitStatusSnapshot {
+            root: PathBuf::from("/repo"),
+            entries,
+        };
+        assert_eq!(
+            snapshot.status_of(Path::new("/repo/docs/drafts/idea.md")),
+            Some(GitFileStatus::Untracked)
+        );
+        assert_eq!(snapshot.status_of(Path::new("/repo/docs/clean.md")), None);
+    }
+
+    #[test]
+    fn parse_blame_merges_consecutive_lines_of_a_commit() {
+        let first = "a".repeat(40);
+        let second = "b".repeat(40);
+        let uncommitted = "0".repeat(40);
+        let output = format!(
+            "{first} 1 1 2\nauthor Ada\nsummary Add intro\nfilename doc.md\n\t# Intro\n\
+             {first} 2 2\n\t\n\
+             {second} 1 3 1\nauthor Bob\nprevious {first} doc.md\nfilename doc.md\n\tNew line\n\
+             {first} 3 4 1\n\tOld line\n\
+             {uncommitted} 5 5 1\nauthor Not Committed Yet\n\tDraft\n"
+        );
+
+        assert_eq!(
+            parse_blame(&output),
+            vec![
+                GitBlameHunk {
+                    start_line: 1,
+                    end_line: 2,
+                    hash: first.clone(),
+                },
+                GitBlameHunk {
+                    start_line: 3,
+                    end_line: 3,
+                    hash: second,
+                },
+                GitBlameHunk {
+                    start_line: 4,
+                    end_line: 4,
+                    hash: first,
+                },
+                GitBlameHunk {
+                    start_line: 5,
+                    end_line: 5,
+                    hash: uncommitted,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn commit_hashes_reject_revision_expressions() {
+        assert!(is_commit_hash("a1b2c3d"));
+        assert!(!is_commit_hash("HEAD"));
+        assert!(!is_commit_hash("--output=/tmp/x"));
+        assert!(!is_commit_hash("abc"));
+    }
+
+    /// A repository where `notes.md` was committed, then renamed to `docs/guide.md` and
+    /// edited; returns its root and the paragraph both versions share.
+    fn create_repository_with_renamed_guide() -> (PathBuf, String) {
+        let root = create_temp_directory("git");
+        git(&root, &["init", "-q"]);
+        let body = "Shared paragraph that survives the rename.\n".repeat(8);
+        fs::write(root.join("notes.md"), format!("# First\n\n{body}")).expect("should write notes");
+        git(&root, &["add", "notes.md"]);
+        git(&root, &["commit", "-q", "-m", "Add notes"]);
+        fs::create_dir_all(root.join("docs")).expect("should create docs");
+        git(&root, &["mv", "notes.md", "docs/guide.md"]);
+        fs::write(root.join("docs/guide.md"), format!("# Second\n\n{body}"))
+            .expect("should update guide");
+        git(&root, &["commit", "-q", "-am", "Rename to guide"]);
+        (root, body)
+    }
+
+    #[test]
+    fn repository_history_follows_renames_and_reads_old_versions() {
+        let (root, body) = create_repository_with_renamed_guide();
+        let guide = root.join("docs/guide.md");
+        let repository = GitRepository::discover(&guide).expect("repository should be found");
+        assert_eq!(repository.root(), root.as_path());
+
+        let history = file_history_payload(&guide).expect("guide should have history");
+        assert_eq!(history.relative_path, "docs/guide.md");
+        assert_eq!(history.commits.len(), 2);
+        assert_eq!(history.commits[0].subject, "Rename to guide");
+        assert_eq!(history.commits[0].author_name, "Mudkip Test");
+        assert_eq!(history.commits[1].path, "notes.md");
+
+        let oldest = &history.commits[1];
+        assert_eq!(
+            repository.file_at_revision(&guide, &oldest.short_hash),
+            Ok(format!("# First\n\n{body}"))
+        );
+        assert!(repository.file_at_revision(&guide, "HEAD").is_err());
+
+        fs::write(root.join("untracked.md"), "# New\n").expect("should write untracked");
+        assert_eq!(file_history_payload(&root.join("untracked.md")), None);
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn repository_resolves_revisions_but_not_options() {
+        let (root, _) = create_repository_with_renamed_guide();
+        let guide = root.join("docs/guide.md");
+        let repository = GitRepository::discover(&guide).expect("repository should be found");
+        let history = file_history_payload(&guide).expect("guide should have history");
+
+        let head = repository
+            .resolve_revision("HEAD~1")
+            .expect("HEAD~1 should resolve");
+        assert_eq!(head, history.commits[1].hash);
+        assert!(repository.resolve_revision("--all").is_err());
+        assert!(repository.resolve_revision("no-such-branch").is_err());
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn folder_status_reports_modified_untracked_and_ignored_files() {
+        let (root, _) = create_repository_with_renamed_guide();
+        let guide = root.join("docs/guide.md");
+        fs::write(root.join("untracked.md"), "# New\n").expect("should write untracked");
+        fs::write(&guide, "# Edited\n").expect("should edit guide");
+        fs::write(root.join(".gitignore"), "ignored.md\n").expect("should write gitignore");
+        fs::write(root.join("ignored.md"), "# Ignored\n").expect("should write ignored");
+
+        assert_eq!(
+            folder_status(&root.join("docs")).status_of(&guide),
+            Some(GitFileStatus::Modified)
+        );
+        let status = folder_status(&root);
+        assert_eq!(status.status_of(&guide), None);
+        assert_eq!(
+            status.status_of(&root.join("untracked.md")),
+            Some(GitFileStatus::Untracked)
+        );
+        assert_eq!(
+            status.status_of(&root.join("ignored.md")),
+            Some(GitFileStatus::Ignored)
+        );
+
+        let repository = GitRepository::discover(&root).expect("repository should be found");
+        assert_eq!(repository.index_path(), Ok(root.join(".git").join("index")));
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn folders_status_runs_once_per_repository() {
+        let (root, _) = create_repository_with_renamed_guide();
+        let guide = root.join("docs/guide.md");
+        fs::write(root.join("untracked.md"), "# New\n").expect("should write untracked");
+        fs::write(&guide, "# Edited\n").expect("should edit guide");
+
+        let statuses = folders_status(&[root.as_path(), &root.join("docs")]);
+        assert_eq!(statuses.len(), 1);
+        assert_eq!(statuses[0].status_of(&guide), Some(GitFileStatus::Modified));
+        assert_eq!(
+            statuses[0].status_of(&root.join("untracked.md")),
+            Some(GitFileStatus::Untracked)
+        );
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn blame_payload_attributes_lines_to_commits() {
+        let (root, _) = create_repository_with_renamed_guide();
+        let guide = root.join("docs/guide.md");
+        fs::write(&guide, "# Edited\n").expect("should edit guide");
+
+        let blame = blame_payload(&guide).expect("guide should have blame");
+        assert_eq!(blame.hunks.len(), 1);
+        assert!(blame.hunks[0].hash.chars().all(|ch| ch == '0'));
+        assert!(blame.commits.is_empty());
+
+        git(&root, &["commit", "-q", "-am", "Edit guide"]);
+        let blame = blame_payload(&guide).expect("guide should have blame");
+        assert_eq!(blame.commits.len(), 1);
+        assert_eq!(blame.commits[0].subject, "Edit guide");
+        assert_eq!(blame.hunks[0].hash, blame.commits[0].hash);
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn repository_config_cannot_run_programs() {
+        let root = create_temp_directory("git");
+        git(&root, &["init", "-q"]);
+        let marker = root.join("ran");
+        let script = root.join("monitor.sh");
+        fs::write(
+            &script,
+            format!("#!/bin/sh\ntouch '{}'\n", marker.display()),
+        )
+        .expect("should write script");
+        Command::new("chmod")
+            .arg("+x")
+            .arg(&script)
+            .status()
+            .expect("chmod should run");
+        git(
+            &root,
+            &["config", "core.fsmonitor", &script.to_string_lossy()],
+        );
+        fs::write(root.join("notes.md"), "# Notes\n").expect("should write notes");
+
+        assert_eq!(
+            folder_status(&root).status_of(&root.join("notes.md")),
+            Some(GitFileStatus::Untracked)
+        );
+        assert!(
+            !marker.exists(),
+            "fsmonitor from .git/config should not run"
+        );
+
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -1,16 +1,58 @@
+use access_scope::{AccessScope, ScopedReadError};
 use dark_light::Mode;
+use desktop_integration::DesktopAction;
+use editor_sync::{CursorMessage, EditorSyncHub};
+use folder_listing::FolderSortMode;
+use git::{GitBlamePayload, GitFileHistoryPayload, GitFileStatus};
+use line_diff::ChangedLineRange;
 use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use quick_open::{QuickOpenIndex, QuickOpenMatch};
+use remote_control::{RemoteCommand, RemoteResponse, SendError, ViewerState};
+use remote_images::RemoteImageAllowance;
 use rfd::FileDialog;
 use serde::Serialize;
+use settings::SettingsStore;
+use snapshots::{SnapshotEntry, SnapshotStore};
 use std::{
-    collections::VecDeque,
+    collections::{BTreeSet, VecDeque},
     env, fs,
     path::{Path, PathBuf},
     process::Command,
-    sync::Mutex,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
 };
-use tauri::{AppHandle, Emitter, Manager, State};
+use tauri::{AppHandle, DragDropEvent, Emitter, Manager, State, WindowEvent};
 use url::Url;
+use user_styles::{StylesheetSource, UserStylesheetPayload};
+use vscode_appearance::VsCodeAppearancePayload;
+
+mod access_scope;
+mod asset_protocol;
+mod book;
+mod desktop_integration;
+mod editor_sync;
+mod folder_listing;
+mod front_matter;
+mod git;
+mod line_diff;
+mod quick_open;
+mod remote_control;
+mod remote_images;
+mod settings;
+mod snapshots;
+#[cfg(test)]
+mod test_support;
+mod user_styles;
+mod vscode_appearance;
+
+const SYSTEM_THEME_POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// How long preview positions are not sent back to editors after an editor moved the preview,
+/// so the two sides do not chase each other.
+const EDITOR_SYNC_ECHO_SUPPRESSION: Duration = Duration::from_millis(500);
 
 #[derive(Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -19,6 +61,46 @@ struct MarkdownFilePayload {
     file_name: String,
     base_href: String,
     content: String,
+    stylesheets: Vec<UserStylesheetPayload>,
+    trusted: bool,
+    /// Set when `content` is the file as of a git commit rather than the working copy.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    revision: Option<String>,
+    /// The older version to render `content` against in compare mode.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diff_base: Option<DiffBasePayload>,
+    /// Set when `content`, or `diff_base` in compare mode, is the local snapshot with this id.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    snapshot: Option<String>,
+    /// Source lines that changed since the previous version, only on watcher updates.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    changed_lines: Option<Vec<ChangedLineRange>>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DiffBasePayload {
+    /// The revision as the user typed it, e.g. `main` or `HEAD`.
+    revision: String,
+    commit: String,
+    content: String,
+    /// The file did not exist at `commit`, so everything shows as added.
+    missing: bool,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LocalHistoryPayload {
+    enabled: bool,
+    /// Newest first.
+    snapshots: Vec<SnapshotEntry>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StylesheetsChangedPayload {
+    file_path: String,
+    stylesheets: Vec<UserStylesheetPayload>,
 }
 
 #[derive(Clone, Serialize)]
@@ -26,6 +108,19 @@ struct MarkdownFilePayload {
 struct MarkdownFolderFilePayload {
     file_path: String,
     file_name: String,
+    /// Chapter title from the folder's ordering file in book mode, otherwise the document's
+    /// front-matter title or first H1.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    /// Chapter nesting level in book mode, 0 otherwise.
+    depth: usize,
+    size: u64,
+    /// Milliseconds since the Unix epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    modified: Option<u64>,
+    /// Git working-tree status; absent for clean files and outside repositories.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    git_status: Option<GitFileStatus>,
 }
 
 #[derive(Clone, Serialize)]
@@ -33,12 +128,27 @@ struct MarkdownFolderFilePayload {
 struct MarkdownFolderPayload {
     folder_path: String,
     files: Vec<MarkdownFolderFilePayload>,
+    /// The ordering file, e.g. `SUMMARY.md`, when `files` is in book order.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    book: Option<String>,
+    /// How `files` is sorted when the folder is not a book.
+    sort_mode: FolderSortMode,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 enum LaunchTarget {
     File(PathBuf),
     Folder(PathBuf),
+    /// Several markdown files from the command line, shown as an ad-hoc list.
+    Collection(Vec<PathBuf>),
+}
+
+/// Where to scroll after opening a file: a 1-based source line or a heading anchor.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+enum OpenPosition {
+    Line(u32),
+    Heading(String),
 }
 
 #[derive(Clone, Serialize)]
@@ -46,19 +156,54 @@ enum LaunchTarget {
 struct OpenTargetPayload {
     target_type: String,
     path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    position: Option<OpenPosition>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    paths: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    unmatched: Vec<String>,
+    /// Open the file compared with this git revision (`mudkip diff FILE --rev REV`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diff_revision: Option<String>,
+    /// Show this file side by side with `path` (`mudkip compare A B`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    compare_path: Option<String>,
 }
 
 impl LaunchTarget {
-    fn to_payload(&self) -> OpenTargetPayload {
-        match self {
-            Self::File(path) => OpenTargetPayload {
-                target_type: "file".to_string(),
-                path: path.to_string_lossy().to_string(),
-            },
-            Self::Folder(path) => OpenTargetPayload {
-                target_type: "folder".to_string(),
-                path: path.to_string_lossy().to_string(),
-            },
+    /// Builds the open payload; positions only apply to files.
+    fn to_payload(&self, position: Option<OpenPosition>) -> OpenTargetPayload {
+        let (target_type, path, position, paths) = match self {
+            Self::File(path) => (
+                "file",
+                path.to_string_lossy().to_string(),
+                position,
+                Vec::new(),
+            ),
+            Self::Folder(path) => (
+                "folder",
+                path.to_string_lossy().to_string(),
+                None,
+                Vec::new(),
+            ),
+            Self::Collection(paths) => {
+                let paths: Vec<String> = paths
+                    .iter()
+                    .map(|path| path.to_string_lossy().to_string())
+                    .collect();
+                let first = paths.first().cloned().unwrap_or_default();
+                ("collection", first, None, paths)
+            }
+        };
+
+        OpenTargetPayload {
+            target_type: target_type.to_string(),
+            path,
+            position,
+            paths,
+            unmatched: Vec::new(),
+            diff_revision: None,
+            compare_path: None,
         }
     }
 }
@@ -94,10 +239,33 @@ struct FileWatchState {
     inner: Mutex<FileWatchInner>,
 }
 
+/// Watches the second document of a side-by-side comparison.
+#[derive(Default)]
+struct CompareWatchState {
+    inner: Mutex<FileWatchInner>,
+}
+
+#[derive(Default)]
+struct StylesheetWatchInner {
+    watcher: Option<RecommendedWatcher>,
+    document_path: Option<PathBuf>,
+    stylesheet_paths: Vec<PathBuf>,
+}
+
+#[derive(Default)]
+struct StylesheetWatchState {
+    inner: Mutex<StylesheetWatchInner>,
+}
+
 #[derive(Default)]
 struct FolderWatchInner {
     watcher: Option<RecommendedWatcher>,
     watched_folder: Option<PathBuf>,
+    /// Whether the folder was trusted when watching started, i.e. whether its git index is
+    /// watched too.
+    watched_trusted: bool,
+    /// Subdirectories watched because they hold the folder's ordering file or chapters.
+    book_directories: BTreeSet<PathBuf>,
 }
 
 #[derive(Default)]
@@ -114,28 +282,120 @@ struct StartupOptions {
     toc_open: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     auto_refresh: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    safe_mode: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    follow_changes: Option<bool>,
 }
 
 impl StartupOptions {
     fn is_empty(&self) -> bool {
-        self.theme.is_none() && self.toc_open.is_none() && self.auto_refresh.is_none()
+        self.theme.is_none()
+            && self.toc_open.is_none()
+            && self.auto_refresh.is_none()
+            && self.safe_mode.is_none()
+            && self.follow_changes.is_none()
     }
 }
 
+#[derive(Default)]
+struct TrustState {
+    forced_safe_mode: AtomicBool,
+}
+
 struct StartupOptionsState {
     options: StartupOptions,
 }
 
+#[derive(Default)]
+struct ViewerStateStore {
+    state: Mutex<ViewerState>,
+}
+
+struct DocumentWaiter {
+    file_path: String,
+    closed: mpsc::Sender<()>,
+}
+
+/// `--wait` callers blocked until their document stops being shown. A waiter counts as shown
+/// from the moment its open is dispatched: viewer state reports are debounced, so one naming
+/// the document may never arrive if the reader moves on quickly.
+#[derive(Default)]
+struct DocumentWaiters {
+    waiters: Mutex<Vec<DocumentWaiter>>,
+}
+
+impl DocumentWaiters {
+    /// Registers a waiter for a document whose open is being dispatched.
+    fn register(&self, file_path: String) -> mpsc::Receiver<()> {
+        let (closed, receiver) = mpsc::channel();
+        if let Ok(mut waiters) = self.waiters.lock() {
+            waiters.push(DocumentWaiter { file_path, closed });
+        }
+        receiver
+    }
+
+    /// Releases waiters whose document has been replaced.
+    fn update(&self, current_file: Option<&str>) {
+        let Ok(mut waiters) = self.waiters.lock() else {
+            return;
+        };
+        waiters.retain(|waiter| {
+            if current_file == Some(waiter.file_path.as_str()) {
+                return true;
+            }
+            let _ = waiter.closed.send(());
+            false
+        });
+    }
+}
+
+#[derive(Default)]
+struct EditorSyncInner {
+    hub: Option<Arc<EditorSyncHub>>,
+    suppress_until: Option<Instant>,
+}
+
+#[derive(Default)]
+struct EditorSyncState {
+    inner: Mutex<EditorSyncInner>,
+}
+
 #[derive(Default)]
 struct ParsedLaunchArgs {
     launch_target: Option<LaunchTarget>,
+    launch_position: Option<OpenPosition>,
     startup_options: StartupOptions,
+    /// Positional arguments that did not resolve to markdown, with the reason.
+    unmatched_args: Vec<String>,
+    remote_command: Option<RemoteCommand>,
+    desktop_action: Option<DesktopAction>,
+    diff_revision: Option<String>,
+    /// Second file of `mudkip compare A B`, shown next to the launch target.
+    compare_path: Option<PathBuf>,
+    wait: bool,
     exit_after_print: bool,
 }
 
+impl ParsedLaunchArgs {
+    fn launch_payload(&self) -> Option<OpenTargetPayload> {
+        let mut payload = self
+            .launch_target
+            .as_ref()?
+            .to_payload(self.launch_position.clone());
+        payload.unmatched = self.unmatched_args.clone();
+        payload.diff_revision = self.diff_revision.clone();
+        payload.compare_path = self
+            .compare_path
+            .as_ref()
+            .map(|path| path.to_string_lossy().to_string());
+        Some(payload)
+    }
+}
+
 fn print_cli_help() {
     println!(
-    "{} {}\n\nUsage:\n  mudkip [OPTIONS] [FILE_OR_FOLDER]\n\nOptions:\n  --theme <dark|light>      Set startup theme.\n  --dark                    Alias for --theme dark.\n  --light                   Alias for --theme light.\n  --toc[=<open|closed>]     Open TOC drawer on launch (default when no value: open).\n  --toc-open                Open TOC drawer on launch.\n  --toc-closed              Close TOC drawer on launch.\n  --watch[=<on|off>]        Enable auto-refresh watch on launch (default when no value: on).\n  --no-watch                Disable auto-refresh watch on launch.\n  -h, --help                Show this help and exit.\n  -V, --version             Show version and exit.",
+    "{} {}\n\nUsage:\n  mudkip [OPTIONS] [FILE_OR_FOLDER]\n  mudkip [OPTIONS] FILE_OR_GLOB...\n  mudkip [OPTIONS] FILE:LINE\n  mudkip [OPTIONS] FILE#HEADING\n  mudkip [OPTIONS] diff FILE [--rev <REV>]\n  mudkip [OPTIONS] compare FILE FILE\n\nOptions:\n  --theme <THEME>           Set startup theme: dark, light, system or vscode (your VS Code theme).\n  --dark                    Alias for --theme dark.\n  --light                   Alias for --theme light.\n  --toc[=<open|closed>]     Open TOC drawer on launch (default when no value: open).\n  --toc-open                Open TOC drawer on launch.\n  --toc-closed              Close TOC drawer on launch.\n  --watch[=<on|off>]        Enable auto-refresh watch on launch (default when no value: on).\n  --no-watch                Disable auto-refresh watch on launch.\n  --safe                    Render every document in restricted mode, even in trusted folders.\n  --follow-changes          Scroll to the first change when auto-refresh reloads the file.\n  --line <LINE>             Open the file scrolled to a source line.\n  --rev <REV>               Revision for diff to compare against (default: HEAD).\n  --wait                    Block until the opened file is closed, even in an already running instance.\n  --remote <COMMAND> [ARG]  Control the running instance: scroll-to-line <LINE>, reload, set-theme <THEME> or get-state.\n  --register-desktop        Install a desktop entry and make Mudkip the markdown handler (Linux).\n  --unregister-desktop      Remove the desktop entry and file associations (Linux).\n  -h, --help                Show this help and exit.\n  -V, --version             Show version and exit.",
     env!("CARGO_PKG_NAME"),
     env!("CARGO_PKG_VERSION")
   );
@@ -149,6 +409,8 @@ fn parse_theme_value(value: &str) -> Option<String> {
     match value.to_ascii_lowercase().as_str() {
         "dark" | "vscode-dark" => Some("vscode-dark".to_string()),
         "light" | "vscode-light" => Some("vscode-light".to_string()),
+        "vscode" | "editor" | "vscode-user" => Some("vscode-user".to_string()),
+        "system" | "auto" | "follow-system" => Some("system".to_string()),
         _ => None,
     }
 }
@@ -161,7 +423,214 @@ fn parse_toggle_value(value: &str) -> Option<bool> {
     }
 }
 
+fn parse_line_value(value: &str) -> Option<u32> {
+    value.parse::<u32>().ok().filter(|line| *line > 0)
+}
+
+/// Splits `notes.md:120`, `notes.md:120:4` or `notes.md#installation` into a markdown file
+/// and the position to open it at.
+fn split_launch_position(cwd: &Path, raw_arg: &str) -> Option<(LaunchTarget, OpenPosition)> {
+    if let Some((path, heading)) = raw_arg.rsplit_once('#') {
+        if let Some(target @ LaunchTarget::File(_)) = canonicalize_if_launch_target(&cwd.join(path))
+        {
+            if !heading.is_empty() {
+                return Some((target, OpenPosition::Heading(heading.to_string())));
+            }
+        }
+    }
+
+    let (mut path, mut line) = raw_arg.rsplit_once(':')?;
+    if let Some((line_path, line_value)) = path.rsplit_once(':') {
+        if parse_line_value(line_value).is_some() && line.chars().all(|ch| ch.is_ascii_digit()) {
+            (path, line) = (line_path, line_value);
+        }
+    }
+
+    let line = parse_line_value(line)?;
+    match canonicalize_if_launch_target(&cwd.join(path))? {
+        target @ LaunchTarget::File(_) => Some((target, OpenPosition::Line(line))),
+        _ => None,
+    }
+}
+
+/// Decodes `file://` URIs, which file managers pass for `%U` in the desktop entry.
+fn file_uri_path(raw_arg: &str) -> Option<PathBuf> {
+    if !raw_arg.starts_with("file:") {
+        return None;
+    }
+    Url::parse(raw_arg).ok()?.to_file_path().ok()
+}
+
+fn is_glob_pattern(raw_arg: &str) -> bool {
+    raw_arg.contains(['*', '?', '['])
+}
+
+/// Resolves one positional argument relative to `cwd`. Globs (including quoted ones the shell
+/// did not expand, like `'docs/**/*.md'`) can match several files.
+fn expand_launch_argument(
+    cwd: &Path,
+    raw_arg: &str,
+) -> Result<(Vec<LaunchTarget>, Option<OpenPosition>), String> {
+    if raw_arg.starts_with("mudkip:") {
+        return Url::parse(raw_arg)
+            .ok()
+            .as_ref()
+            .and_then(parse_deep_link)
+            .map(|(target, position)| (vec![target], position))
+            .ok_or_else(|| format!("'{raw_arg}': invalid deep link"));
+    }
+
+    if let Some(path) = file_uri_path(raw_arg) {
+        return canonicalize_if_launch_target(&path)
+            .map(|target| (vec![target], None))
+            .ok_or_else(|| unresolved_argument_message(raw_arg, &path));
+    }
+
+    let candidate = cwd.join(raw_arg);
+    if let Some(target) = canonicalize_if_launch_target(&candidate) {
+        return Ok((vec![target], None));
+    }
+
+    if let Some((target, position)) = split_launch_position(cwd, raw_arg) {
+        return Ok((vec![target], Some(position)));
+    }
+
+    if is_glob_pattern(raw_arg) {
+        let pattern = candidate.to_string_lossy().to_string();
+        let matches = glob::glob(&pattern)
+            .map_err(|err| format!("'{raw_arg}': invalid glob pattern ({err})"))?;
+        let files: Vec<LaunchTarget> = matches
+            .filter_map(Result::ok)
+            .filter_map(|path| match canonicalize_if_launch_target(&path) {
+                Some(target @ LaunchTarget::File(_)) => Some(target),
+                _ => None,
+            })
+            .collect();
+        if files.is_empty() {
+            return Err(format!("'{raw_arg}': no markdown files match"));
+        }
+        return Ok((files, None));
+    }
+
+    Err(unresolved_argument_message(raw_arg, &candidate))
+}
+
+fn unresolved_argument_message(raw_arg: &str, path: &Path) -> String {
+    if path.exists() {
+        format!("'{raw_arg}': not a markdown file")
+    } else {
+        format!("'{raw_arg}': no such file or folder")
+    }
+}
+
+/// Turns every positional argument into one launch target: a single file or folder as before,
+/// or a collection of files in argument order.
+fn resolve_launch_arguments(parsed: &mut ParsedLaunchArgs, cwd: &Path, raw_args: &[String]) {
+    let mut targets: Vec<LaunchTarget> = Vec::new();
+    let mut position = None;
+
+    for raw_arg in raw_args {
+        match expand_launch_argument(cwd, raw_arg) {
+            Ok((expanded, expanded_position)) => {
+                position = expanded_position;
+                for target in expanded {
+                    if !targets.contains(&target) {
+                        targets.push(target);
+                    }
+                }
+            }
+            Err(message) => parsed.unmatched_args.push(message),
+        }
+    }
+
+    // A `FILE:LINE` position belongs to that one file, not to the collection opened instead.
+    if targets.len() > 1 {
+        position = None;
+    }
+    parsed.launch_target = combine_launch_targets(targets, &mut parsed.unmatched_args);
+    parsed.launch_position = position;
+}
+
+/// A single target opens as before; several become a collection of their files. Folders cannot
+/// be part of a collection and are reported in `unmatched`.
+fn combine_launch_targets(
+    mut targets: Vec<LaunchTarget>,
+    unmatched: &mut Vec<String>,
+) -> Option<LaunchTarget> {
+    if targets.len() <= 1 {
+        return targets.pop();
+    }
+
+    let mut files = Vec::new();
+    for target in targets {
+        match target {
+            LaunchTarget::File(path) => files.push(path),
+            LaunchTarget::Folder(path) => unmatched.push(format!(
+                "'{}': folders cannot be opened together with other files",
+                path.display()
+            )),
+            LaunchTarget::Collection(paths) => files.extend(paths),
+        }
+    }
+
+    match files.len() {
+        0 => None,
+        1 => files.pop().map(LaunchTarget::File),
+        _ => Some(LaunchTarget::Collection(files)),
+    }
+}
+
+const DEEP_LINK_SCHEME: &str = "mudkip";
+
+/// Parses `mudkip://open?path=/repo/docs/api.md&line=42` (or `&heading=install`) links.
+fn parse_deep_link(url: &Url) -> Option<(LaunchTarget, Option<OpenPosition>)> {
+    if url.scheme() != DEEP_LINK_SCHEME {
+        return None;
+    }
+
+    let action = url
+        .host_str()
+        .filter(|host| !host.is_empty())
+        .or_else(|| url.path().trim_matches('/').split('/').next())
+        .unwrap_or_default();
+    if action != "open" {
+        log::warn!("Ignoring unsupported deep link action '{action}'.");
+        return None;
+    }
+
+    let mut path = None;
+    let mut position = None;
+    for (key, value) in url.query_pairs() {
+        match key.as_ref() {
+            "path" | "file" => path = Some(PathBuf::from(value.as_ref())),
+            "line" => {
+                position = parse_line_value(&value)
+                    .map(OpenPosition::Line)
+                    .or(position)
+            }
+            "heading" if !value.is_empty() => {
+                position = Some(OpenPosition::Heading(value.to_string()))
+            }
+            _ => {}
+        }
+    }
+
+    let path = path.filter(|path| path.is_absolute())?;
+    Some((canonicalize_if_launch_target(&path)?, position))
+}
+
 fn parse_cli_args<I, S>(args: I) -> ParsedLaunchArgs
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let cwd = env::current_dir().unwrap_or_default();
+    parse_cli_args_in(&cwd, args)
+}
+
+/// Parses arguments with relative paths and globs resolved against `cwd`, which is the
+/// invoking process's directory when arguments are forwarded to a running instance.
+fn parse_cli_args_in<I, S>(cwd: &Path, args: I) -> ParsedLaunchArgs
 where
     I: IntoIterator<Item = S>,
     S: AsRef<str>,
@@ -173,6 +642,10 @@ where
     let mut parsed = ParsedLaunchArgs::default();
     let mut index = 0usize;
     let mut positional_only = false;
+    let mut cli_line = None;
+    let mut subcommand: Option<String> = None;
+    let mut cli_revision: Option<String> = None;
+    let mut positional_args: Vec<String> = Vec::new();
 
     while index < args.len() {
         let raw_arg = &args[index];
@@ -222,6 +695,16 @@ where
                     index += 1;
                     continue;
                 }
+                "--safe" | "--safe-mode" => {
+                    parsed.startup_options.safe_mode = Some(true);
+                    index += 1;
+                    continue;
+                }
+                "--follow-changes" => {
+                    parsed.startup_options.follow_changes = Some(true);
+                    index += 1;
+                    continue;
+                }
                 "--theme" => {
                     if let Some(value) = args.get(index + 1) {
                         if value.starts_with('-') {
@@ -232,7 +715,7 @@ where
                                 parsed.startup_options.theme = Some(theme);
                             } else {
                                 log::warn!(
-                  "Ignoring unsupported --theme value '{}'. Expected dark or light.",
+                  "Ignoring unsupported --theme value '{}'. Expected dark, light, system or vscode.",
                   value
                 );
                             }
@@ -244,6 +727,66 @@ where
                     }
                     continue;
                 }
+                "--register-desktop" => {
+                    parsed.desktop_action = Some(DesktopAction::Register);
+                    index += 1;
+                    continue;
+                }
+                "--unregister-desktop" => {
+                    parsed.desktop_action = Some(DesktopAction::Unregister);
+                    index += 1;
+                    continue;
+                }
+                "--wait" | "-w" => {
+                    parsed.wait = true;
+                    index += 1;
+                    continue;
+                }
+                "--remote" => {
+                    let name = args.get(index + 1).map(String::as_str).unwrap_or_default();
+                    let argument = args
+                        .get(index + 2)
+                        .map(String::as_str)
+                        .filter(|_| RemoteCommand::takes_argument(name));
+                    match RemoteCommand::from_cli(name, argument) {
+                        Ok(command) => parsed.remote_command = Some(command),
+                        Err(err) => {
+                            eprintln!("{err}");
+                            parsed.exit_after_print = true;
+                        }
+                    }
+                    index += 2 + usize::from(argument.is_some());
+                    continue;
+                }
+                "--rev" => {
+                    match args.get(index + 1).filter(|value| !value.starts_with('-')) {
+                        Some(revision) => {
+                            cli_revision = Some(revision.clone());
+                            index += 2;
+                        }
+                        None => {
+                            log::warn!("Ignoring --rev without a revision.");
+                            index += 1;
+                        }
+                    }
+                    continue;
+                }
+                "--line" => {
+                    match args
+                        .get(index + 1)
+                        .and_then(|value| parse_line_value(value))
+                    {
+                        Some(line) => {
+                            cli_line = Some(line);
+                            index += 2;
+                        }
+                        None => {
+                            log::warn!("Ignoring --line without a positive line number.");
+                            index += 1;
+                        }
+                    }
+                    continue;
+                }
                 "--toc" => {
                     let mut consumed_value = false;
 
@@ -286,7 +829,30 @@ where
                     parsed.startup_options.theme = Some(theme);
                 } else {
                     log::warn!(
-                        "Ignoring unsupported --theme value '{}'. Expected dark or light.",
+                        "Ignoring unsupported --theme value '{}'. Expected dark, light, system or vscode.",
+                        value
+                    );
+                }
+                index += 1;
+                continue;
+            }
+
+            if let Some(value) = raw_arg.strip_prefix("--rev=") {
+                if value.is_empty() || value.starts_with('-') {
+                    log::warn!("Ignoring unsupported --rev value '{}'.", value);
+                } else {
+                    cli_revision = Some(value.to_string());
+                }
+                index += 1;
+                continue;
+            }
+
+            if let Some(value) = raw_arg.strip_prefix("--line=") {
+                if let Some(line) = parse_line_value(value) {
+                    cli_line = Some(line);
+                } else {
+                    log::warn!(
+                        "Ignoring unsupported --line value '{}'. Expected a positive line number.",
                         value
                     );
                 }
@@ -327,42 +893,67 @@ where
                 index += 1;
                 continue;
             }
-        }
 
-        if parsed.launch_target.is_none() {
-            let candidate = Path::new(raw_arg);
-            parsed.launch_target = canonicalize_if_launch_target(candidate);
+            if matches!(raw_arg.as_str(), "diff" | "compare")
+                && positional_args.is_empty()
+                && subcommand.is_none()
+            {
+                subcommand = Some(raw_arg.clone());
+                index += 1;
+                continue;
+            }
         }
 
+        positional_args.push(raw_arg.clone());
         index += 1;
     }
 
+    resolve_launch_arguments(&mut parsed, cwd, &positional_args);
+
+    match subcommand.as_deref() {
+        Some("diff") => {
+            if matches!(parsed.launch_target, Some(LaunchTarget::File(_))) {
+                parsed.diff_revision = Some(cli_revision.unwrap_or_else(|| "HEAD".to_string()));
+            } else {
+                parsed
+                    .unmatched_args
+                    .push("'diff': expects a single markdown file".to_string());
+            }
+        }
+        Some("compare") => match parsed.launch_target.take() {
+            Some(LaunchTarget::Collection(mut paths)) if paths.len() == 2 => {
+                parsed.compare_path = paths.pop();
+                parsed.launch_target = paths.pop().map(LaunchTarget::File);
+            }
+            other => {
+                parsed.launch_target = other;
+                parsed
+                    .unmatched_args
+                    .push("'compare': expects two markdown files".to_string());
+            }
+        },
+        _ => {}
+    }
+    if cli_revision.is_some() && subcommand.as_deref() != Some("diff") {
+        log::warn!("Ignoring --rev outside of `mudkip diff`.");
+    }
+
+    if let Some(line) =
+        cli_line.filter(|_| matches!(parsed.launch_target, Some(LaunchTarget::File(_))))
+    {
+        parsed.launch_position = Some(OpenPosition::Line(line));
+    }
+
     parsed
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::time::{SystemTime, UNIX_EPOCH};
+    use crate::test_support::{create_temp_directory, create_temp_file};
 
     fn create_temp_markdown_file() -> PathBuf {
-        let unique_suffix = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("system clock should be after unix epoch")
-            .as_nanos();
-        let path = env::temp_dir().join(format!("mudkip-cli-parser-{unique_suffix}.md"));
-        fs::write(&path, "# test\n").expect("should create temp markdown file");
-        path
-    }
-
-    fn create_temp_directory() -> PathBuf {
-        let unique_suffix = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("system clock should be after unix epoch")
-            .as_nanos();
-        let path = env::temp_dir().join(format!("mudkip-cli-parser-dir-{unique_suffix}"));
-        fs::create_dir_all(&path).expect("should create temp directory");
-        path
+        create_temp_file("cli-parser.md", "# test\n")
     }
 
     #[test]
@@ -423,7 +1014,7 @@ mod tests {
 
     #[test]
     fn parse_cli_args_reads_folder_path_as_launch_target() {
-        let temp_path = create_temp_directory();
+        let temp_path = create_temp_directory("cli-parser-dir");
         let path_arg = temp_path.to_string_lossy().to_string();
 
         let parsed = parse_cli_args(["--watch=on", path_arg.as_str()]);
@@ -438,47 +1029,465 @@ mod tests {
 
         let _ = fs::remove_dir_all(temp_path);
     }
-}
-
-fn is_markdown_path(path: &Path) -> bool {
-    path.extension()
-        .and_then(|ext| ext.to_str())
-        .map(|ext| {
-            matches!(
-                ext.to_ascii_lowercase().as_str(),
-                "md" | "markdown" | "mdown" | "mkd" | "txt"
-            )
-        })
-        .unwrap_or(false)
-}
 
-fn canonicalize_if_launch_target(path: &Path) -> Option<LaunchTarget> {
-    let canonical_path = fs::canonicalize(path).ok()?;
+    #[test]
+    fn parse_cli_args_accepts_vscode_theme() {
+        let parsed = parse_cli_args(["--theme=vscode"]);
+        assert_eq!(parsed.startup_options.theme.as_deref(), Some("vscode-user"));
+    }
 
-    if canonical_path.is_dir() {
-        return Some(LaunchTarget::Folder(canonical_path));
+    #[test]
+    fn parse_cli_args_accepts_system_theme() {
+        let parsed = parse_cli_args(["--theme", "system"]);
+        assert_eq!(parsed.startup_options.theme.as_deref(), Some("system"));
     }
 
-    if canonical_path.is_file() && is_markdown_path(&canonical_path) {
-        return Some(LaunchTarget::File(canonical_path));
+    #[test]
+    fn parse_cli_args_reads_safe_flag() {
+        let parsed = parse_cli_args(["--safe"]);
+        assert_eq!(parsed.startup_options.safe_mode, Some(true));
+        assert!(!parsed.startup_options.is_empty());
     }
 
-    None
-}
+    #[test]
+    fn parse_cli_args_reads_line_and_heading_positions() {
+        let temp_path = create_temp_markdown_file();
+        let path_arg = temp_path.to_string_lossy().to_string();
+        let canonical_target =
+            LaunchTarget::File(fs::canonicalize(&temp_path).expect("canonical path should exist"));
 
-fn build_payload(path: &Path) -> Result<MarkdownFilePayload, String> {
-    let canonical_path = fs::canonicalize(path)
-        .map_err(|err| format!("Failed to resolve file path '{}': {err}", path.display()))?;
+        let parsed = parse_cli_args([format!("{path_arg}:120")]);
+        assert_eq!(parsed.launch_target, Some(canonical_target.clone()));
+        assert_eq!(parsed.launch_position, Some(OpenPosition::Line(120)));
 
-    if !is_markdown_path(&canonical_path) {
-        return Err("Requested path does not look like markdown.".to_string());
-    }
+        let parsed = parse_cli_args([format!("{path_arg}:12:4")]);
+        assert_eq!(parsed.launch_position, Some(OpenPosition::Line(12)));
 
-    let bytes = fs::read(&canonical_path)
-        .map_err(|err| format!("Failed to read file '{}': {err}", canonical_path.display()))?;
-    let content = String::from_utf8_lossy(&bytes).to_string();
+        let parsed = parse_cli_args([format!("{path_arg}#installation")]);
+        assert_eq!(parsed.launch_target, Some(canonical_target.clone()));
+        assert_eq!(
+            parsed.launch_position,
+            Some(OpenPosition::Heading("installation".to_string()))
+        );
 
-    let file_name = canonical_path
+        let parsed = parse_cli_args([path_arg.clone(), "--line".to_string(), "80".to_string()]);
+        assert_eq!(parsed.launch_target, Some(canonical_target));
+        assert_eq!(
+            parsed.launch_payload().and_then(|payload| payload.position),
+            Some(OpenPosition::Line(80))
+        );
+
+        let parsed = parse_cli_args([format!("{path_arg}:0")]);
+        assert_eq!(parsed.launch_target, None);
+
+        let _ = fs::remove_file(temp_path);
+    }
+
+    #[test]
+    fn parse_cli_args_reads_deep_links() {
+        let temp_path = create_temp_markdown_file();
+        let canonical_path = fs::canonicalize(&temp_path).expect("canonical path should exist");
+        let encoded_path: String =
+            url::form_urlencoded::byte_serialize(temp_path.to_string_lossy().as_bytes()).collect();
+
+        let parsed = parse_cli_args([format!("mudkip://open?path={encoded_path}&line=42")]);
+        assert_eq!(
+            parsed.launch_target,
+            Some(LaunchTarget::File(canonical_path.clone()))
+        );
+        assert_eq!(parsed.launch_position, Some(OpenPosition::Line(42)));
+
+        let parsed = parse_cli_args([format!("mudkip:///open?file={encoded_path}&heading=setup")]);
+        assert_eq!(
+            parsed.launch_target,
+            Some(LaunchTarget::File(canonical_path))
+        );
+        assert_eq!(
+            parsed.launch_position,
+            Some(OpenPosition::Heading("setup".to_string()))
+        );
+
+        let parsed = parse_cli_args(["mudkip://open?path=relative.md"]);
+        assert_eq!(parsed.launch_target, None);
+
+        let parsed = parse_cli_args([format!("mudkip://delete?path={encoded_path}")]);
+        assert_eq!(parsed.launch_target, None);
+
+        let _ = fs::remove_file(temp_path);
+    }
+
+    #[test]
+    fn parse_cli_args_reads_remote_commands() {
+        let parsed = parse_cli_args(["--remote", "scroll-to-line", "80"]);
+        assert_eq!(
+            parsed.remote_command,
+            Some(RemoteCommand::ScrollToLine { line: 80 })
+        );
+        assert!(parsed.launch_target.is_none());
+
+        let parsed = parse_cli_args(["--remote", "get-state", "--dark"]);
+        assert_eq!(parsed.remote_command, Some(RemoteCommand::GetState));
+        assert_eq!(parsed.startup_options.theme.as_deref(), Some("vscode-dark"));
+
+        let parsed = parse_cli_args(["--remote", "scroll-to-line", "nope"]);
+        assert!(parsed.remote_command.is_none());
+        assert!(parsed.exit_after_print);
+    }
+
+    #[test]
+    fn parse_cli_args_reads_wait_flag() {
+        let parsed = parse_cli_args(["--wait", "--dark"]);
+        assert!(parsed.wait);
+        assert_eq!(parsed.startup_options.theme.as_deref(), Some("vscode-dark"));
+    }
+
+    #[test]
+    fn parse_cli_args_collects_multiple_files_and_globs_in_order() {
+        let root = create_temp_directory("cli-parser-dir");
+        fs::create_dir_all(root.join("docs/nested")).expect("should create docs");
+        fs::write(root.join("CHANGELOG.md"), "# Changes\n").expect("should write changelog");
+        fs::write(root.join("docs/b.md"), "# B\n").expect("should write b");
+        fs::write(root.join("docs/a.md"), "# A\n").expect("should write a");
+        fs::write(root.join("docs/nested/c.md"), "# C\n").expect("should write c");
+        fs::write(root.join("notes.txt.bak"), "x").expect("should write non-markdown");
+        let canonical_root = fs::canonicalize(&root).expect("canonical root should exist");
+
+        let parsed = parse_cli_args_in(
+            &root,
+            [
+                "CHANGELOG.md",
+                "docs/**/*.md",
+                "docs/a.md",
+                "notes.txt.bak",
+                "missing.md",
+                "*.rst",
+            ],
+        );
+
+        assert_eq!(
+            parsed.launch_target,
+            Some(LaunchTarget::Collection(vec![
+                canonical_root.join("CHANGELOG.md"),
+                canonical_root.join("docs/a.md"),
+                canonical_root.join("docs/b.md"),
+                canonical_root.join("docs/nested/c.md"),
+            ]))
+        );
+        assert_eq!(
+            parsed.unmatched_args,
+            vec![
+                "'notes.txt.bak': not a markdown file",
+                "'missing.md': no such file or folder",
+                "'*.rst': no markdown files match",
+            ]
+        );
+
+        let payload = parsed.launch_payload().expect("collection payload");
+        assert_eq!(payload.target_type, "collection");
+        assert_eq!(payload.paths.len(), 4);
+        assert_eq!(payload.unmatched.len(), 3);
+
+        let parsed = parse_cli_args_in(&root, ["docs/a.md:10", "docs/b.md"]);
+        assert_eq!(
+            parsed.launch_target,
+            Some(LaunchTarget::Collection(vec![
+                canonical_root.join("docs/a.md"),
+                canonical_root.join("docs/b.md"),
+            ]))
+        );
+        assert_eq!(parsed.launch_position, None);
+        let parsed = parse_cli_args_in(&root, ["docs/b.md", "docs/a.md:10"]);
+        assert_eq!(parsed.launch_position, None);
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn parse_cli_args_reads_desktop_registration_flags() {
+        let parsed = parse_cli_args(["--register-desktop"]);
+        assert_eq!(parsed.desktop_action, Some(DesktopAction::Register));
+
+        let parsed = parse_cli_args(["--unregister-desktop"]);
+        assert_eq!(parsed.desktop_action, Some(DesktopAction::Unregister));
+    }
+
+    #[test]
+    fn parse_cli_args_decodes_file_uris_from_file_managers() {
+        let root = create_temp_directory("cli-parser-dir");
+        fs::create_dir_all(root.join("My Notes")).expect("should create folder");
+        fs::write(root.join("My Notes/café #1.md"), "# One\n").expect("should write first");
+        fs::write(root.join("second.md"), "# Two\n").expect("should write second");
+        let canonical_root = fs::canonicalize(&root).expect("canonical root should exist");
+        let first_uri = Url::from_file_path(root.join("My Notes/café #1.md"))
+            .expect("absolute path should convert to a file URI")
+            .to_string();
+        assert!(first_uri.contains("My%20Notes/caf%C3%A9%20%231.md"));
+
+        let parsed = parse_cli_args([first_uri.clone()]);
+        assert_eq!(
+            parsed.launch_target,
+            Some(LaunchTarget::File(
+                canonical_root.join("My Notes/café #1.md")
+            ))
+        );
+
+        let second_uri = format!("file://localhost{}", root.join("second.md").display());
+        let missing_uri = format!("file://{}", root.join("missing.md").display());
+        let parsed = parse_cli_args([first_uri, second_uri, missing_uri.clone()]);
+        assert_eq!(
+            parsed.launch_target,
+            Some(LaunchTarget::Collection(vec![
+                canonical_root.join("My Notes/café #1.md"),
+                canonical_root.join("second.md"),
+            ]))
+        );
+        assert_eq!(
+            parsed.unmatched_args,
+            vec![format!("'{missing_uri}': no such file or folder")]
+        );
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn parse_cli_args_reads_diff_subcommand_and_revision() {
+        let temp_path = create_temp_markdown_file();
+        let path_arg = temp_path.to_string_lossy().to_string();
+
+        let parsed = parse_cli_args(["diff", path_arg.as_str(), "--rev", "main"]);
+        assert!(matches!(parsed.launch_target, Some(LaunchTarget::File(_))));
+        assert_eq!(
+            parsed
+                .launch_payload()
+                .and_then(|payload| payload.diff_revision),
+            Some("main".to_string())
+        );
+
+        let parsed = parse_cli_args(["diff", path_arg.as_str()]);
+        assert_eq!(parsed.diff_revision.as_deref(), Some("HEAD"));
+
+        let parsed = parse_cli_args([path_arg.as_str(), "--rev=main"]);
+        assert_eq!(parsed.diff_revision, None);
+
+        let parsed = parse_cli_args(["diff"]);
+        assert_eq!(parsed.launch_target, None);
+        assert_eq!(
+            parsed.unmatched_args,
+            vec!["'diff': expects a single markdown file"]
+        );
+
+        let _ = fs::remove_file(temp_path);
+    }
+
+    #[test]
+    fn parse_cli_args_reads_compare_subcommand() {
+        let root = create_temp_directory("cli-parser-dir");
+        fs::write(root.join("source.md"), "# Source\n").expect("should write source");
+        fs::write(root.join("translation.md"), "# Traduction\n").expect("should write translation");
+        let canonical_root = fs::canonicalize(&root).expect("canonical root should exist");
+
+        let parsed = parse_cli_args_in(&root, ["compare", "source.md", "translation.md"]);
+        assert_eq!(
+            parsed.launch_target,
+            Some(LaunchTarget::File(canonical_root.join("source.md")))
+        );
+        assert_eq!(
+            parsed
+                .launch_payload()
+                .and_then(|payload| payload.compare_path),
+            Some(
+                canonical_root
+                    .join("translation.md")
+                    .to_string_lossy()
+                    .to_string()
+            )
+        );
+
+        let parsed = parse_cli_args_in(&root, ["compare", "source.md"]);
+        assert_eq!(
+            parsed.launch_target,
+            Some(LaunchTarget::File(canonical_root.join("source.md")))
+        );
+        assert_eq!(parsed.compare_path, None);
+        assert_eq!(
+            parsed.unmatched_args,
+            vec!["'compare': expects two markdown files"]
+        );
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn parse_cli_args_reads_follow_changes_flag() {
+        let parsed = parse_cli_args(["--follow-changes"]);
+        assert_eq!(parsed.startup_options.follow_changes, Some(true));
+        assert!(!parsed.startup_options.is_empty());
+    }
+
+    #[test]
+    fn is_path_in_folders_matches_whole_components() {
+        let folders = vec![PathBuf::from("/repo/docs")];
+        assert!(is_path_in_folders(
+            Path::new("/repo/docs/guide.md"),
+            &folders
+        ));
+        assert!(!is_path_in_folders(
+            Path::new("/repo/docs-old/guide.md"),
+            &folders
+        ));
+    }
+
+    #[test]
+    fn system_handler_refuses_programs_and_recognizes_documents() {
+        let temp_dir = create_temp_directory("cli-parser-dir");
+        let write = |name: &str| {
+            let path = temp_dir.join(name);
+            fs::write(&path, "x").expect("should write file");
+            path
+        };
+
+        for launcher in ["setup.EXE", "run.sh", "mudkip.desktop", "tool.AppImage"] {
+            assert_eq!(
+                classify_system_handler_target(&write(launcher)),
+                SystemHandlerTarget::Launcher,
+                "{launcher}"
+            );
+        }
+        let bundle = temp_dir.join("Viewer.app");
+        fs::create_dir_all(&bundle).expect("should create bundle");
+        assert_eq!(
+            classify_system_handler_target(&bundle),
+            SystemHandlerTarget::Launcher
+        );
+
+        assert_eq!(
+            classify_system_handler_target(&write("report.pdf")),
+            SystemHandlerTarget::Document
+        );
+        assert_eq!(
+            classify_system_handler_target(&write("data.sqlite")),
+            SystemHandlerTarget::Other
+        );
+        assert_eq!(
+            classify_system_handler_target(&temp_dir),
+            SystemHandlerTarget::Other
+        );
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let script = write("build");
+            fs::set_permissions(&script, fs::Permissions::from_mode(0o755))
+                .expect("should mark script executable");
+            assert_eq!(
+                classify_system_handler_target(&script),
+                SystemHandlerTarget::Launcher
+            );
+        }
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[test]
+    fn document_waiters_release_after_document_is_replaced() {
+        let waiters = DocumentWaiters::default();
+        let closed = waiters.register("/docs/notes.md".to_string());
+
+        waiters.update(Some("/docs/notes.md"));
+        waiters.update(Some("/docs/notes.md"));
+        assert!(closed.try_recv().is_err());
+
+        waiters.update(None);
+        assert!(closed.try_recv().is_ok());
+        assert!(waiters.waiters.lock().expect("waiters lock").is_empty());
+
+        // No report naming the document is needed: replacing it before the debounced
+        // report arrives still releases the waiter.
+        let closed = waiters.register("/docs/notes.md".to_string());
+        waiters.update(Some("/docs/other.md"));
+        assert!(closed.try_recv().is_ok());
+    }
+
+    #[test]
+    fn dropped_paths_open_as_folder_collection_or_feedback() {
+        let root = create_temp_directory("cli-parser-dir");
+        fs::create_dir_all(root.join("docs")).expect("should create docs");
+        fs::write(root.join("a.md"), "# A\n").expect("should write a");
+        fs::write(root.join("b.md"), "# B\n").expect("should write b");
+        fs::write(root.join("photo.png"), "png").expect("should write image");
+        let canonical_root = fs::canonicalize(&root).expect("canonical root should exist");
+
+        let (folder, unmatched) = launch_target_from_dropped_paths(&[root.join("docs")]);
+        assert_eq!(
+            folder,
+            Some(LaunchTarget::Folder(canonical_root.join("docs")))
+        );
+        assert!(unmatched.is_empty());
+
+        let (collection, unmatched) = launch_target_from_dropped_paths(&[
+            root.join("a.md"),
+            root.join("photo.png"),
+            root.join("b.md"),
+        ]);
+        assert_eq!(
+            collection,
+            Some(LaunchTarget::Collection(vec![
+                canonical_root.join("a.md"),
+                canonical_root.join("b.md"),
+            ]))
+        );
+        assert_eq!(
+            unmatched,
+            vec!["'photo.png': not a markdown file or folder"]
+        );
+
+        let (rejected, unmatched) = launch_target_from_dropped_paths(&[root.join("photo.png")]);
+        assert_eq!(rejected, None);
+        assert_eq!(unmatched.len(), 1);
+
+        let _ = fs::remove_dir_all(root);
+    }
+}
+
+fn is_markdown_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| {
+            matches!(
+                ext.to_ascii_lowercase().as_str(),
+                "md" | "markdown" | "mdown" | "mkd" | "txt"
+            )
+        })
+        .unwrap_or(false)
+}
+
+fn canonicalize_if_launch_target(path: &Path) -> Option<LaunchTarget> {
+    let canonical_path = fs::canonicalize(path).ok()?;
+
+    if canonical_path.is_dir() {
+        return Some(LaunchTarget::Folder(canonical_path));
+    }
+
+    if canonical_path.is_file() && is_markdown_path(&canonical_path) {
+        return Some(LaunchTarget::File(canonical_path));
+    }
+
+    None
+}
+
+fn build_payload(path: &Path) -> Result<MarkdownFilePayload, String> {
+    let canonical_path = fs::canonicalize(path)
+        .map_err(|err| format!("Failed to resolve file path '{}': {err}", path.display()))?;
+
+    if !is_markdown_path(&canonical_path) {
+        return Err("Requested path does not look like markdown.".to_string());
+    }
+
+    let bytes = fs::read(&canonical_path)
+        .map_err(|err| format!("Failed to read file '{}': {err}", canonical_path.display()))?;
+    let content = String::from_utf8_lossy(&bytes).to_string();
+
+    let file_name = canonical_path
         .file_name()
         .and_then(|name| name.to_str())
         .ok_or_else(|| "Unable to determine file name.".to_string())?
@@ -487,20 +1496,206 @@ fn build_payload(path: &Path) -> Result<MarkdownFilePayload, String> {
     let parent_dir = canonical_path
         .parent()
         .ok_or_else(|| "Unable to determine parent directory.".to_string())?;
-    let base_href = Url::from_directory_path(parent_dir)
-        .map_err(|_| "Unable to convert parent directory to file URL.".to_string())?
-        .to_string();
+    let base_href = asset_protocol::asset_base_href(parent_dir)?;
 
     Ok(MarkdownFilePayload {
         file_path: canonical_path.to_string_lossy().to_string(),
         file_name,
         base_href,
         content,
+        stylesheets: Vec::new(),
+        trusted: false,
+        revision: None,
+        diff_base: None,
+        snapshot: None,
+        changed_lines: None,
     })
 }
 
-fn list_markdown_files_in_folder(path: &Path) -> Result<Vec<MarkdownFolderFilePayload>, String> {
+fn is_path_in_folders(path: &Path, folders: &[PathBuf]) -> bool {
+    folders.iter().any(|folder| path.starts_with(folder))
+}
+
+fn is_document_trusted(app: &AppHandle, document_path: &Path) -> bool {
+    if app
+        .state::<TrustState>()
+        .forced_safe_mode
+        .load(Ordering::Relaxed)
+    {
+        return false;
+    }
+
+    app.try_state::<SettingsStore>()
+        .map(|store| is_path_in_folders(document_path, &store.snapshot().trusted_folders))
+        .unwrap_or(false)
+}
+
+fn global_stylesheet_path(app: &AppHandle) -> Option<PathBuf> {
+    app.path()
+        .app_config_dir()
+        .ok()
+        .map(|dir| dir.join(user_styles::USER_STYLESHEET_FILE_NAME))
+}
+
+/// Folder and front-matter stylesheets ship with the document, so they only apply when the
+/// document is trusted; the global user stylesheet always applies.
+fn resolve_document_stylesheets(
+    app: &AppHandle,
+    document_path: &Path,
+    content: &str,
+) -> Vec<(StylesheetSource, PathBuf)> {
+    let trusted = is_document_trusted(app, document_path);
+
+    user_styles::resolve_stylesheet_paths(
+        global_stylesheet_path(app).as_deref(),
+        document_path,
+        content,
+    )
+    .into_iter()
+    .filter(|(source, _)| trusted || *source == StylesheetSource::Global)
+    .collect()
+}
+
+fn build_document_payload(app: &AppHandle, path: &Path) -> Result<MarkdownFilePayload, String> {
+    let mut payload = build_payload(path)?;
+    let document_path = PathBuf::from(&payload.file_path);
+    payload.trusted = is_document_trusted(app, &document_path);
+    let stylesheet_paths = resolve_document_stylesheets(app, &document_path, &payload.content);
+    payload.stylesheets = user_styles::load_stylesheets(&stylesheet_paths);
+    Ok(payload)
+}
+
+/// The document as of `commit`, with the working copy's base href and trust but links and
+/// front-matter stylesheets taken from the historical content.
+fn build_revision_payload(
+    app: &AppHandle,
+    path: &Path,
+    commit: &str,
+) -> Result<MarkdownFilePayload, String> {
+    require_trusted_repository(app, path)?;
+    let repository = git::GitRepository::discover(path)
+        .ok_or_else(|| format!("'{}' is not inside a git repository.", path.display()))?;
+    let content = repository.file_at_revision(path, commit)?;
+
+    let mut payload = build_document_payload(app, path)?;
+    let stylesheet_paths = resolve_document_stylesheets(app, path, &content);
+    payload.stylesheets = user_styles::load_stylesheets(&stylesheet_paths);
+    payload.content = content;
+    payload.revision = Some(commit.to_string());
+    Ok(payload)
+}
+
+/// The working copy with the file as of `revision` attached for a rendered comparison.
+fn build_diff_payload(
+    app: &AppHandle,
+    path: &Path,
+    revision: &str,
+) -> Result<MarkdownFilePayload, String> {
+    require_trusted_repository(app, path)?;
+    let repository = git::GitRepository::discover(path)
+        .ok_or_else(|| format!("'{}' is not inside a git repository.", path.display()))?;
+    let commit = repository.resolve_revision(revision)?;
+    let (content, missing) = match repository.file_at_revision(path, &commit) {
+        Ok(content) => (content, false),
+        Err(err) => {
+            log::info!("Comparing '{}' with an empty base: {err}", path.display());
+            (String::new(), true)
+        }
+    };
+
+    let mut payload = build_document_payload(app, path)?;
+    payload.diff_base = Some(DiffBasePayload {
+        revision: revision.trim().to_string(),
+        commit,
+        content,
+        missing,
+    });
+    Ok(payload)
+}
+
+/// The document as of a local snapshot, shown like a git revision.
+fn build_snapshot_payload(
+    app: &AppHandle,
+    path: &Path,
+    id: &str,
+) -> Result<MarkdownFilePayload, String> {
+    let content = app.state::<SnapshotStore>().read(path, id)?;
+
+    let mut payload = build_document_payload(app, path)?;
+    let stylesheet_paths = resolve_document_stylesheets(app, path, &content);
+    payload.stylesheets = user_styles::load_stylesheets(&stylesheet_paths);
+    payload.content = content;
+    payload.snapshot = Some(id.to_string());
+    Ok(payload)
+}
+
+/// The working copy with a local snapshot attached for a rendered comparison.
+fn build_snapshot_diff_payload(
+    app: &AppHandle,
+    path: &Path,
+    id: &str,
+) -> Result<MarkdownFilePayload, String> {
+    let content = app.state::<SnapshotStore>().read(path, id)?;
+
+    let mut payload = build_document_payload(app, path)?;
+    payload.diff_base = Some(DiffBasePayload {
+        revision: id.to_string(),
+        commit: id.to_string(),
+        content,
+        missing: false,
+    });
+    payload.snapshot = Some(id.to_string());
+    Ok(payload)
+}
+
+/// Saves the version the viewer showed before a change and the new one, when local history is
+/// turned on.
+fn record_snapshots(app: &AppHandle, path: &Path, previous: Option<&str>, content: &str) {
+    let enabled = app
+        .try_state::<SettingsStore>()
+        .is_some_and(|store| store.snapshot().local_history);
+    let Some(store) = app.try_state::<SnapshotStore>().filter(|_| enabled) else {
+        return;
+    };
+
+    for version in previous.into_iter().chain([content]) {
+        if let Err(err) = store.record(path, version) {
+            log::warn!("Failed to save snapshot of '{}': {err}", path.display());
+            return;
+        }
+    }
+}
+
+/// Running git reads the repository's config, which the folder's author controls, so
+/// documents outside trusted folders get no git calls at all.
+fn require_trusted_repository(app: &AppHandle, path: &Path) -> Result<(), String> {
+    if is_document_trusted(app, path) {
+        Ok(())
+    } else {
+        Err(format!(
+            "Trust the folder of '{}' to use its git history.",
+            path.display()
+        ))
+    }
+}
+
+/// Git status of the files in `folder`, only for trusted folders: running git reads the
+/// repository's config, which the folder's author controls.
+fn trusted_folder_status(folder: &Path, trusted: bool) -> git::GitStatusSnapshot {
+    if trusted {
+        git::folder_status(folder)
+    } else {
+        git::GitStatusSnapshot::default()
+    }
+}
+
+fn list_markdown_files_in_folder(
+    path: &Path,
+    sort_mode: FolderSortMode,
+    trusted: bool,
+) -> Result<Vec<MarkdownFolderFilePayload>, String> {
     let mut files = Vec::new();
+    let git_status = trusted_folder_status(path, trusted);
 
     let entries = fs::read_dir(path)
         .map_err(|err| format!("Failed to read folder '{}': {err}", path.display()))?;
@@ -537,24 +1732,114 @@ fn list_markdown_files_in_folder(path: &Path) -> Result<Vec<MarkdownFolderFilePa
             .and_then(|name| name.to_str())
             .ok_or_else(|| "Unable to determine file name.".to_string())?
             .to_string();
+        let metadata = fs::metadata(&canonical_file).ok();
 
         files.push(MarkdownFolderFilePayload {
+            git_status: git_status.status_of(&canonical_file),
+            title: folder_listing::read_markdown_title(&canonical_file),
             file_path: canonical_file.to_string_lossy().to_string(),
             file_name,
+            depth: 0,
+            size: metadata.as_ref().map_or(0, |metadata| metadata.len()),
+            modified: metadata
+                .and_then(|metadata| metadata.modified().ok())
+                .and_then(folder_listing::modified_millis),
         });
     }
 
     files.sort_by(|left, right| {
-        left.file_name
-            .to_ascii_lowercase()
-            .cmp(&right.file_name.to_ascii_lowercase())
-            .then_with(|| left.file_name.cmp(&right.file_name))
+        folder_listing::compare(sort_mode, &left.sort_key(), &right.sort_key())
     });
 
     Ok(files)
 }
 
-fn build_folder_payload(path: &Path) -> Result<MarkdownFolderPayload, String> {
+fn list_book_chapters(
+    folder: &Path,
+    book: &book::Book,
+    trusted: bool,
+) -> Vec<MarkdownFolderFilePayload> {
+    // Chapters can live in subfolders; one `git status` per repository covers them all.
+    let mut directories: Vec<&Path> = book
+        .chapters
+        .iter()
+        .map(|chapter| chapter.path.parent().unwrap_or(folder))
+        .collect();
+    directories.sort();
+    directories.dedup();
+    let git_statuses = if trusted {
+        git::folders_status(&directories)
+    } else {
+        Vec::new()
+    };
+
+    book.chapters
+        .iter()
+        .filter(|chapter| is_markdown_path(&chapter.path))
+        .map(|chapter| {
+            let git_status = git_statuses
+                .iter()
+                .find(|snapshot| snapshot.covers(&chapter.path))
+                .and_then(|snapshot| snapshot.status_of(&chapter.path));
+            let file_name = chapter
+                .path
+                .strip_prefix(folder)
+                .unwrap_or(&chapter.path)
+                .to_string_lossy()
+                .replace('\\', "/");
+            let metadata = fs::metadata(&chapter.path).ok();
+
+            MarkdownFolderFilePayload {
+                file_path: chapter.path.to_string_lossy().to_string(),
+                file_name,
+                title: chapter
+                    .title
+                    .clone()
+                    .or_else(|| folder_listing::read_markdown_title(&chapter.path)),
+                depth: chapter.depth,
+                size: metadata.as_ref().map_or(0, |metadata| metadata.len()),
+                modified: metadata
+                    .and_then(|metadata| metadata.modified().ok())
+                    .and_then(folder_listing::modified_millis),
+                git_status,
+            }
+        })
+        .collect()
+}
+
+impl MarkdownFolderFilePayload {
+    fn sort_key(&self) -> folder_listing::SortKey<'_> {
+        folder_listing::SortKey {
+            file_name: &self.file_name,
+            title: self.title.as_deref(),
+            modified: self.modified,
+        }
+    }
+}
+
+/// The folder's files in book order when it has an ordering file, in `sort_mode` otherwise,
+/// together with the book. Git status is only shown for trusted folders.
+fn list_folder_files(
+    path: &Path,
+    sort_mode: FolderSortMode,
+    trusted: bool,
+) -> Result<(Vec<MarkdownFolderFilePayload>, Option<book::Book>), String> {
+    match book::load(path) {
+        Some(book) => Ok((list_book_chapters(path, &book, trusted), Some(book))),
+        None => Ok((
+            list_markdown_files_in_folder(path, sort_mode, trusted)?,
+            None,
+        )),
+    }
+}
+
+fn folder_sort_mode(app: &AppHandle, folder: &Path) -> FolderSortMode {
+    app.try_state::<SettingsStore>()
+        .and_then(|store| store.snapshot().folder_sort_modes.get(folder).copied())
+        .unwrap_or_default()
+}
+
+fn build_folder_payload(app: &AppHandle, path: &Path) -> Result<MarkdownFolderPayload, String> {
     let canonical_path = fs::canonicalize(path)
         .map_err(|err| format!("Failed to resolve folder path '{}': {err}", path.display()))?;
 
@@ -562,36 +1847,480 @@ fn build_folder_payload(path: &Path) -> Result<MarkdownFolderPayload, String> {
         return Err("Requested path is not a folder.".to_string());
     }
 
-    let files = list_markdown_files_in_folder(&canonical_path)?;
+    let sort_mode = folder_sort_mode(app, &canonical_path);
+    let trusted = is_document_trusted(app, &canonical_path);
+    let (files, book) = list_folder_files(&canonical_path, sort_mode, trusted)?;
 
     Ok(MarkdownFolderPayload {
         folder_path: canonical_path.to_string_lossy().to_string(),
         files,
+        book: book.map(|book| book.source),
+        sort_mode,
     })
 }
 
-fn try_spawn(command: &str, args: &[&str]) -> bool {
-    Command::new(command).args(args).spawn().is_ok()
+fn try_spawn(command: &str, args: &[&str]) -> bool {
+    Command::new(command).args(args).spawn().is_ok()
+}
+
+const SYSTEM_HANDLER_URL_SCHEMES: [&str; 3] = ["http", "https", "mailto"];
+
+/// Files the system handler would run or install rather than show.
+const LAUNCHER_EXTENSIONS: [&str; 45] = [
+    "app",
+    "appimage",
+    "application",
+    "appref-ms",
+    "bash",
+    "bat",
+    "bin",
+    "cmd",
+    "com",
+    "command",
+    "cpl",
+    "csh",
+    "deb",
+    "desktop",
+    "dmg",
+    "exe",
+    "fish",
+    "gadget",
+    "hta",
+    "inf",
+    "jar",
+    "jnlp",
+    "js",
+    "jse",
+    "ksh",
+    "lnk",
+    "mpkg",
+    "msc",
+    "msi",
+    "msp",
+    "pif",
+    "pkg",
+    "pl",
+    "ps1",
+    "py",
+    "pyw",
+    "rb",
+    "reg",
+    "rpm",
+    "run",
+    "scf",
+    "scr",
+    "sh",
+    "url",
+    "vbs",
+];
+
+/// Files that open in a viewer, so following a link to them needs no confirmation.
+const DOCUMENT_EXTENSIONS: [&str; 31] = [
+    "avif", "bmp", "csv", "doc", "docx", "epub", "flac", "gif", "jpeg", "jpg", "m4a", "mov", "mp3",
+    "mp4", "odp", "ods", "odt", "ogg", "pdf", "png", "ppt", "pptx", "rtf", "svg", "tif", "tiff",
+    "txt", "wav", "webm", "webp", "xlsx",
+];
+
+#[derive(Debug, PartialEq, Eq)]
+enum SystemHandlerTarget {
+    Document,
+    /// Anything else the system handler would open; the user confirms it first.
+    Other,
+    Launcher,
+}
+
+fn classify_system_handler_target(path: &Path) -> SystemHandlerTarget {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase)
+        .unwrap_or_default();
+    if LAUNCHER_EXTENSIONS.contains(&extension.as_str()) {
+        return SystemHandlerTarget::Launcher;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let executable = fs::metadata(path)
+            .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0);
+        if executable && !DOCUMENT_EXTENSIONS.contains(&extension.as_str()) {
+            return SystemHandlerTarget::Launcher;
+        }
+    }
+
+    if path.is_file() && DOCUMENT_EXTENSIONS.contains(&extension.as_str()) {
+        SystemHandlerTarget::Document
+    } else {
+        SystemHandlerTarget::Other
+    }
+}
+
+fn confirm_open_with_system_handler(path: &Path) -> bool {
+    let description = format!(
+        "This document links to '{}', which is not a document Mudkip recognizes. Open it with the system default application?",
+        path.display()
+    );
+    rfd::MessageDialog::new()
+        .set_level(rfd::MessageLevel::Warning)
+        .set_title("Open linked file?")
+        .set_description(description)
+        .set_buttons(rfd::MessageButtons::YesNo)
+        .show()
+        == rfd::MessageDialogResult::Yes
+}
+
+fn open_with_default_app(target: &str) -> Result<(), String> {
+    #[cfg(target_os = "macos")]
+    let launched = try_spawn("open", &[target]);
+    #[cfg(windows)]
+    let launched = try_spawn("explorer", &[target]);
+    #[cfg(not(any(target_os = "macos", windows)))]
+    let launched = try_spawn("xdg-open", &[target]);
+
+    if launched {
+        Ok(())
+    } else {
+        Err(format!(
+            "Unable to open '{target}' with the system default handler."
+        ))
+    }
+}
+
+fn focus_main_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+fn queue_external_open(app: &AppHandle, target: OpenTargetPayload, emit_event: bool) {
+    let scope = app.state::<AccessScope>();
+    scope.grant_opened(Path::new(&target.path));
+    for path in target.paths.iter().chain(&target.compare_path) {
+        scope.grant_opened(Path::new(path));
+    }
+
+    let pending_targets = app.state::<PendingOpenTargets>();
+    pending_targets.push(target.clone());
+
+    if !emit_event {
+        return;
+    }
+
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.emit("file:opened-external", target);
+        focus_main_window(app);
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DropHoverPayload {
+    accepted: bool,
+}
+
+/// Dropped folders open in folder mode and several dropped files as a collection, the same way
+/// they would from the command line.
+fn launch_target_from_dropped_paths(paths: &[PathBuf]) -> (Option<LaunchTarget>, Vec<String>) {
+    let mut targets = Vec::new();
+    let mut unmatched = Vec::new();
+
+    for path in paths {
+        match canonicalize_if_launch_target(path) {
+            Some(target) => {
+                if !targets.contains(&target) {
+                    targets.push(target);
+                }
+            }
+            None => unmatched.push(format!(
+                "'{}': not a markdown file or folder",
+                path.file_name()
+                    .map(|name| name.to_string_lossy())
+                    .unwrap_or_else(|| path.to_string_lossy())
+            )),
+        }
+    }
+
+    let target = combine_launch_targets(targets, &mut unmatched);
+    (target, unmatched)
+}
+
+fn open_dropped_paths(app: &AppHandle, paths: &[PathBuf]) {
+    let (target, unmatched) = launch_target_from_dropped_paths(paths);
+    let Some(target) = target else {
+        if let Some(window) = app.get_webview_window("main") {
+            let _ = window.emit("drop:rejected", unmatched);
+        }
+        return;
+    };
+
+    let mut payload = target.to_payload(None);
+    payload.unmatched = unmatched;
+    queue_external_open(app, payload, true);
+}
+
+fn handle_drag_drop_event(app: &AppHandle, event: &DragDropEvent) {
+    let Some(window) = app.get_webview_window("main") else {
+        return;
+    };
+
+    match event {
+        DragDropEvent::Enter { paths, .. } => {
+            let accepted = paths
+                .iter()
+                .any(|path| canonicalize_if_launch_target(path).is_some());
+            let _ = window.emit("drop:hover", DropHoverPayload { accepted });
+        }
+        DragDropEvent::Drop { paths, .. } => {
+            let _ = window.emit("drop:hover-end", ());
+            open_dropped_paths(app, paths);
+        }
+        DragDropEvent::Leave => {
+            let _ = window.emit("drop:hover-end", ());
+        }
+        _ => {}
+    }
+}
+
+fn system_theme_class() -> &'static str {
+    match dark_light::detect() {
+        Ok(Mode::Light) => "vscode-light",
+        _ => "vscode-dark",
+    }
+}
+
+/// Polls the OS appearance and emits `app:system-theme-changed` whenever it flips, so a
+/// frontend that follows the system theme can switch without a restart.
+fn spawn_system_theme_monitor(app: AppHandle) {
+    let spawn_result = thread::Builder::new()
+        .name("system-theme-monitor".to_string())
+        .spawn(move || {
+            let mut current_theme = system_theme_class();
+
+            loop {
+                thread::sleep(SYSTEM_THEME_POLL_INTERVAL);
+
+                let detected_theme = system_theme_class();
+                if detected_theme == current_theme {
+                    continue;
+                }
+
+                current_theme = detected_theme;
+                if let Some(window) = app.get_webview_window("main") {
+                    let _ = window.emit("app:system-theme-changed", detected_theme);
+                }
+            }
+        });
+
+    if let Err(err) = spawn_result {
+        log::warn!("Failed to start system theme monitor: {err}");
+    }
+}
+
+fn emit_to_main_window<S: Serialize + Clone>(
+    app: &AppHandle,
+    event: &str,
+    payload: S,
+) -> RemoteResponse {
+    let Some(window) = app.get_webview_window("main") else {
+        return RemoteResponse::error("The main window is not open.".to_string());
+    };
+
+    match window.emit(event, payload) {
+        Ok(()) => RemoteResponse::ok(),
+        Err(err) => RemoteResponse::error(format!("Failed to emit {event}: {err}")),
+    }
+}
+
+fn handle_remote_command(app: &AppHandle, command: RemoteCommand) -> RemoteResponse {
+    match command {
+        RemoteCommand::ScrollToLine { line } => {
+            emit_to_main_window(app, "remote:scroll-to-line", line)
+        }
+        RemoteCommand::Reload => emit_to_main_window(app, "remote:reload", ()),
+        RemoteCommand::SetTheme { theme } => {
+            let Some(theme) = parse_theme_value(&theme) else {
+                return RemoteResponse::error(format!(
+                    "Unsupported theme '{theme}'. Expected dark, light, system or vscode."
+                ));
+            };
+            let options = StartupOptions {
+                theme: Some(theme),
+                ..StartupOptions::default()
+            };
+            emit_to_main_window(app, "app:startup-options", options)
+        }
+        RemoteCommand::GetState => {
+            let store = app.state::<ViewerStateStore>();
+            let state = store
+                .state
+                .lock()
+                .map(|state| state.clone())
+                .unwrap_or_default();
+            RemoteResponse::with_state(state)
+        }
+        RemoteCommand::OpenAndWait {
+            path,
+            line,
+            heading,
+        } => {
+            let Some(target @ LaunchTarget::File(_)) =
+                canonicalize_if_launch_target(Path::new(&path))
+            else {
+                return RemoteResponse::error(format!(
+                    "--wait needs an existing markdown file, got '{path}'."
+                ));
+            };
+            let position = line
+                .map(OpenPosition::Line)
+                .or(heading.map(OpenPosition::Heading));
+            let payload = target.to_payload(position);
+
+            let closed = app
+                .state::<DocumentWaiters>()
+                .register(payload.path.clone());
+            queue_external_open(app, payload, true);
+            // Errors once the app shuts down, which also closes the document.
+            let _ = closed.recv();
+            RemoteResponse::ok()
+        }
+    }
+}
+
+/// Hands a `--wait` open to an already running instance and blocks until the document is
+/// closed there. Returns `None` when no instance is listening, so this process opens it itself,
+/// and exits with an error when `--wait` cannot be honoured.
+fn wait_in_running_instance(parsed_args: &ParsedLaunchArgs) -> Option<i32> {
+    if !cfg!(unix) {
+        eprintln!("--wait is only available on platforms with Unix domain sockets.");
+        return Some(1);
+    }
+    let Some(payload) = parsed_args
+        .launch_payload()
+        .filter(|payload| payload.target_type == "file")
+    else {
+        eprintln!("--wait needs a single markdown file to open, not a folder or a list of files.");
+        return Some(1);
+    };
+
+    let (line, heading) = match payload.position {
+        Some(OpenPosition::Line(line)) => (Some(line), None),
+        Some(OpenPosition::Heading(heading)) => (None, Some(heading)),
+        None => (None, None),
+    };
+    let command = RemoteCommand::OpenAndWait {
+        path: payload.path,
+        line,
+        heading,
+    };
+
+    let socket_path = remote_control::remote_socket_path();
+    match remote_control::send_command(&socket_path, &command) {
+        Ok(response) if response.ok => Some(0),
+        Ok(response) => {
+            eprintln!(
+                "{}",
+                response
+                    .error
+                    .unwrap_or_else(|| "Failed to open file for --wait.".to_string())
+            );
+            Some(1)
+        }
+        // Otherwise the single-instance plugin would hand the file over and exit right away.
+        Err(SendError::NotListening(_)) if remote_control::is_instance_running(&socket_path) => {
+            eprintln!(
+                "A running Mudkip instance is not listening on '{}', so --wait cannot wait for it.",
+                socket_path.display()
+            );
+            Some(1)
+        }
+        Err(SendError::NotListening(_)) => None,
+        Err(SendError::Failed(err)) => {
+            eprintln!("{err}");
+            Some(1)
+        }
+    }
+}
+
+fn handle_editor_cursor(app: &AppHandle, message: CursorMessage) {
+    let Ok(canonical_path) = fs::canonicalize(&message.path) else {
+        return;
+    };
+    let current_file = app
+        .state::<ViewerStateStore>()
+        .state
+        .lock()
+        .ok()
+        .and_then(|state| state.file_path.clone());
+    if current_file.as_deref() != Some(canonical_path.to_string_lossy().as_ref()) {
+        return;
+    }
+
+    if let Ok(mut inner) = app.state::<EditorSyncState>().inner.lock() {
+        inner.suppress_until = Some(Instant::now() + EDITOR_SYNC_ECHO_SUPPRESSION);
+    }
+    emit_to_main_window(app, "remote:scroll-to-line", message.line.max(1));
 }
 
-fn focus_main_window(app: &AppHandle) {
-    if let Some(window) = app.get_webview_window("main") {
-        let _ = window.show();
-        let _ = window.set_focus();
+fn start_editor_sync_server(app: AppHandle) {
+    let handler_app = app.clone();
+    match editor_sync::spawn_server(editor_sync::editor_sync_socket_path(), move |message| {
+        handle_editor_cursor(&handler_app, message)
+    }) {
+        Ok(hub) => {
+            if let Ok(mut inner) = app.state::<EditorSyncState>().inner.lock() {
+                inner.hub = Some(hub);
+            }
+        }
+        Err(err) => log::warn!("{err}"),
     }
 }
 
-fn queue_external_open(app: &AppHandle, target: OpenTargetPayload, emit_event: bool) {
-    let pending_targets = app.state::<PendingOpenTargets>();
-    pending_targets.push(target.clone());
-
-    if !emit_event {
-        return;
+fn start_remote_control_server(app: AppHandle) {
+    let socket_path = remote_control::remote_socket_path();
+    // Lets `--wait` tell this instance apart from none at all, even if binding fails below.
+    match remote_control::acquire_instance_lock(&socket_path) {
+        Ok(lock) => {
+            app.manage(lock);
+        }
+        Err(err) => log::warn!("{err}"),
+    }
+    if let Err(err) = remote_control::spawn_server(socket_path, move |command| {
+        handle_remote_command(&app, command)
+    }) {
+        log::warn!("{err}");
     }
+}
 
-    if let Some(window) = app.get_webview_window("main") {
-        let _ = window.emit("file:opened-external", target);
-        focus_main_window(app);
+/// Runs `--remote` against the running instance and returns the process exit code.
+fn run_remote_command(command: &RemoteCommand) -> i32 {
+    match remote_control::send_command(&remote_control::remote_socket_path(), command) {
+        Ok(response) if response.ok => {
+            if let Some(state) = response.state {
+                match serde_json::to_string_pretty(&state) {
+                    Ok(serialized) => println!("{serialized}"),
+                    Err(err) => {
+                        eprintln!("Failed to serialize state: {err}");
+                        return 1;
+                    }
+                }
+            }
+            0
+        }
+        Ok(response) => {
+            eprintln!(
+                "{}",
+                response
+                    .error
+                    .unwrap_or_else(|| "Remote command failed.".to_string())
+            );
+            1
+        }
+        Err(err) => {
+            eprintln!("{err}");
+            1
+        }
     }
 }
 
@@ -607,7 +2336,7 @@ fn is_folder_change_event(kind: &EventKind) -> bool {
 }
 
 #[tauri::command]
-fn pick_markdown_file() -> Result<Option<MarkdownFilePayload>, String> {
+fn pick_markdown_file(app: AppHandle) -> Result<Option<MarkdownFilePayload>, String> {
     let selected_file = FileDialog::new()
         .add_filter("Markdown", &["md", "markdown", "mdown", "mkd", "txt"])
         .pick_file();
@@ -617,34 +2346,209 @@ fn pick_markdown_file() -> Result<Option<MarkdownFilePayload>, String> {
             if !is_markdown_path(&path) {
                 return Err("Selected file does not look like markdown.".to_string());
             }
-            Ok(Some(build_payload(&path)?))
+            app.state::<AccessScope>().grant_opened(&path);
+            Ok(Some(build_document_payload(&app, &path)?))
         }
         None => Ok(None),
     }
 }
 
 #[tauri::command]
-fn read_markdown_file(path: String) -> Result<MarkdownFilePayload, String> {
-    build_payload(Path::new(&path))
+fn read_markdown_file(
+    app: AppHandle,
+    scope: State<AccessScope>,
+    path: String,
+) -> Result<MarkdownFilePayload, ScopedReadError> {
+    let canonical_path = scope.check(Path::new(&path))?;
+    build_document_payload(&app, &canonical_path).map_err(ScopedReadError::from)
+}
+
+#[tauri::command(async)]
+fn git_file_history(
+    app: AppHandle,
+    scope: State<AccessScope>,
+    path: String,
+) -> Result<Option<GitFileHistoryPayload>, ScopedReadError> {
+    let canonical_path = scope.check(Path::new(&path))?;
+    if !is_document_trusted(&app, &canonical_path) {
+        return Ok(None);
+    }
+    Ok(git::file_history_payload(&canonical_path))
+}
+
+#[tauri::command(async)]
+fn git_blame(
+    app: AppHandle,
+    scope: State<AccessScope>,
+    path: String,
+) -> Result<Option<GitBlamePayload>, ScopedReadError> {
+    let canonical_path = scope.check(Path::new(&path))?;
+    // Blaming the working copy runs the repository's filter drivers.
+    if !is_document_trusted(&app, &canonical_path) {
+        return Ok(None);
+    }
+    Ok(git::blame_payload(&canonical_path))
+}
+
+#[tauri::command(async)]
+fn git_read_file_revision(
+    app: AppHandle,
+    scope: State<AccessScope>,
+    path: String,
+    commit: String,
+) -> Result<MarkdownFilePayload, ScopedReadError> {
+    let canonical_path = scope.check(Path::new(&path))?;
+    build_revision_payload(&app, &canonical_path, &commit).map_err(ScopedReadError::from)
+}
+
+#[tauri::command(async)]
+fn git_read_file_diff(
+    app: AppHandle,
+    scope: State<AccessScope>,
+    path: String,
+    revision: String,
+) -> Result<MarkdownFilePayload, ScopedReadError> {
+    let canonical_path = scope.check(Path::new(&path))?;
+    build_diff_payload(&app, &canonical_path, &revision).map_err(ScopedReadError::from)
+}
+
+#[tauri::command(async)]
+fn snapshot_list(
+    settings: State<SettingsStore>,
+    snapshots: State<SnapshotStore>,
+    scope: State<AccessScope>,
+    path: String,
+) -> Result<LocalHistoryPayload, ScopedReadError> {
+    let canonical_path = scope.check(Path::new(&path))?;
+    Ok(LocalHistoryPayload {
+        enabled: settings.snapshot().local_history,
+        snapshots: snapshots.list(&canonical_path),
+    })
+}
+
+#[tauri::command]
+fn snapshot_set_enabled(settings: State<SettingsStore>, enabled: bool) -> Result<(), String> {
+    settings.update(|settings| settings.local_history = enabled)?;
+    Ok(())
+}
+
+#[tauri::command(async)]
+fn snapshot_read_file(
+    app: AppHandle,
+    scope: State<AccessScope>,
+    path: String,
+    id: String,
+) -> Result<MarkdownFilePayload, ScopedReadError> {
+    let canonical_path = scope.check(Path::new(&path))?;
+    build_snapshot_payload(&app, &canonical_path, &id).map_err(ScopedReadError::from)
+}
+
+#[tauri::command(async)]
+fn snapshot_read_file_diff(
+    app: AppHandle,
+    scope: State<AccessScope>,
+    path: String,
+    id: String,
+) -> Result<MarkdownFilePayload, ScopedReadError> {
+    let canonical_path = scope.check(Path::new(&path))?;
+    build_snapshot_diff_payload(&app, &canonical_path, &id).map_err(ScopedReadError::from)
 }
 
 #[tauri::command]
-fn pick_markdown_folder() -> Result<Option<MarkdownFolderPayload>, String> {
+fn pick_markdown_folder(
+    app: AppHandle,
+    scope: State<AccessScope>,
+) -> Result<Option<MarkdownFolderPayload>, String> {
     let selected_folder = FileDialog::new().pick_folder();
 
     match selected_folder {
-        Some(path) => Ok(Some(build_folder_payload(&path)?)),
+        Some(path) => {
+            scope.grant_opened(&path);
+            Ok(Some(build_folder_payload(&app, &path)?))
+        }
         None => Ok(None),
     }
 }
 
 #[tauri::command]
-fn read_markdown_folder(path: String) -> Result<MarkdownFolderPayload, String> {
-    build_folder_payload(Path::new(&path))
+fn read_markdown_folder(
+    app: AppHandle,
+    scope: State<AccessScope>,
+    path: String,
+) -> Result<MarkdownFolderPayload, ScopedReadError> {
+    let canonical_path = scope.check(Path::new(&path))?;
+    build_folder_payload(&app, &canonical_path).map_err(ScopedReadError::from)
+}
+
+/// Remembers how the folder is sorted and returns it relisted in that order.
+#[tauri::command]
+fn set_folder_sort_mode(
+    app: AppHandle,
+    scope: State<AccessScope>,
+    settings: State<SettingsStore>,
+    path: String,
+    sort_mode: FolderSortMode,
+) -> Result<MarkdownFolderPayload, ScopedReadError> {
+    let canonical_path = scope.check(Path::new(&path))?;
+    settings.update(|settings| {
+        if sort_mode == FolderSortMode::default() {
+            settings.folder_sort_modes.remove(&canonical_path);
+        } else {
+            settings
+                .folder_sort_modes
+                .insert(canonical_path.clone(), sort_mode);
+        }
+    })?;
+    build_folder_payload(&app, &canonical_path).map_err(ScopedReadError::from)
+}
+
+#[tauri::command(async)]
+fn quick_open_search(
+    scope: State<AccessScope>,
+    index: State<QuickOpenIndex>,
+    settings: State<SettingsStore>,
+    folder: String,
+    query: String,
+    refresh: bool,
+) -> Result<Vec<QuickOpenMatch>, ScopedReadError> {
+    let canonical_folder = scope.check(Path::new(&folder))?;
+    if !canonical_folder.is_dir() {
+        return Err(ScopedReadError::from(
+            "Quick open needs an opened folder.".to_string(),
+        ));
+    }
+
+    let recent = settings.snapshot().recent_files;
+    Ok(index.search(&canonical_folder, &query, &recent, refresh))
+}
+
+/// Grants the target of a link in `document`, which must be in scope and link there, and
+/// returns its canonical path.
+#[tauri::command]
+fn follow_link(
+    scope: State<AccessScope>,
+    document: String,
+    target: String,
+) -> Result<String, ScopedReadError> {
+    scope
+        .grant_followed_link(Path::new(&document), Path::new(&target))
+        .map(|path| path.to_string_lossy().to_string())
+}
+
+/// Lets the renderer load `urls` through the remote image proxy and returns the proxy URLs.
+#[tauri::command]
+fn remote_images_allow(
+    allowance: State<RemoteImageAllowance>,
+    urls: Vec<String>,
+) -> Result<Vec<String>, String> {
+    allowance.allow(&urls)
 }
 
 #[tauri::command]
-fn open_in_vscode(path: String, line: u32) -> Result<(), String> {
+fn open_in_vscode(scope: State<AccessScope>, path: String, line: u32) -> Result<(), String> {
+    scope
+        .check(Path::new(&path))
+        .map_err(|err| err.to_string())?;
     let line_number = if line == 0 { 1 } else { line };
     let target = format!("{path}:{line_number}");
 
@@ -677,29 +2581,112 @@ fn open_in_vscode(path: String, line: u32) -> Result<(), String> {
     }
 }
 
+/// Opens web links and non-markdown local files from a document in the system default handler.
+/// Programs and installers are refused, and local files that are not plain documents are only
+/// opened after the user confirms.
 #[tauri::command]
-fn theme_get_system() -> &'static str {
-    match dark_light::detect() {
-        Ok(Mode::Light) => "vscode-light",
-        _ => "vscode-dark",
+fn open_with_system_handler(scope: State<AccessScope>, target: String) -> Result<(), String> {
+    if let Ok(url) = Url::parse(&target) {
+        if SYSTEM_HANDLER_URL_SCHEMES.contains(&url.scheme()) {
+            return open_with_default_app(url.as_str());
+        }
+        if url.scheme().len() > 1 {
+            return Err(format!(
+                "Refusing to open link with scheme '{}'.",
+                url.scheme()
+            ));
+        }
+    }
+
+    let canonical_path = scope
+        .check(Path::new(&target))
+        .map_err(|err| err.to_string())?;
+    match classify_system_handler_target(&canonical_path) {
+        SystemHandlerTarget::Launcher => Err(format!(
+            "Refusing to open '{}': it is a program or installer.",
+            canonical_path.display()
+        )),
+        SystemHandlerTarget::Other if !confirm_open_with_system_handler(&canonical_path) => Ok(()),
+        _ => open_with_default_app(&canonical_path.to_string_lossy()),
     }
 }
 
 #[tauri::command]
-fn filewatch_start(
-    app: AppHandle,
-    watch_state: State<FileWatchState>,
+fn theme_get_system() -> &'static str {
+    system_theme_class()
+}
+
+/// Asks the user in a native dialog, so a script in the webview cannot trust a folder on its
+/// own.
+fn confirm_trust_folder(folder: &Path) -> bool {
+    let description = format!(
+        "Trust '{}'? Documents in this folder and below will render raw HTML, load remote images and stylesheets, and show git information.",
+        folder.display()
+    );
+    rfd::MessageDialog::new()
+        .set_level(rfd::MessageLevel::Warning)
+        .set_title("Trust this folder?")
+        .set_description(description)
+        .set_buttons(rfd::MessageButtons::YesNo)
+        .show()
+        == rfd::MessageDialogResult::Yes
+}
+
+/// Adds the folder of `path` to the trusted folders once the user confirms; returns whether
+/// it was added.
+#[tauri::command]
+fn trust_add_folder(
+    settings: State<SettingsStore>,
+    scope: State<AccessScope>,
     path: String,
+) -> Result<bool, String> {
+    let canonical_path = scope
+        .check(Path::new(&path))
+        .map_err(|err| err.to_string())?;
+    let folder = if canonical_path.is_dir() {
+        canonical_path
+    } else {
+        canonical_path
+            .parent()
+            .ok_or_else(|| "Unable to determine parent directory.".to_string())?
+            .to_path_buf()
+    };
+
+    if !confirm_trust_folder(&folder) {
+        return Ok(false);
+    }
+
+    settings.update(|settings| {
+        if !settings.trusted_folders.contains(&folder) {
+            settings.trusted_folders.push(folder);
+        }
+    })?;
+
+    Ok(true)
+}
+
+#[tauri::command]
+fn vscode_get_appearance() -> Option<VsCodeAppearancePayload> {
+    vscode_appearance::load_vscode_appearance()
+}
+
+/// Watches one markdown file and emits `event_name` with its rebuilt payload on every change.
+fn watch_markdown_file(
+    app: &AppHandle,
+    watch_inner: &Mutex<FileWatchInner>,
+    scope: &AccessScope,
+    path: &str,
+    event_name: &'static str,
 ) -> Result<(), String> {
-    let canonical_path = fs::canonicalize(Path::new(&path))
-        .map_err(|err| format!("Failed to resolve file path '{path}': {err}"))?;
+    let canonical_path = scope
+        .check(Path::new(path))
+        .map_err(|err| err.to_string())?;
 
     if !is_markdown_path(&canonical_path) {
         return Err("Can only watch markdown files.".to_string());
     }
 
-    let mut inner = watch_state
-        .inner
+    let mut inner = watch_inner
         .lock()
         .map_err(|_| "Failed to lock file watch state.".to_string())?;
 
@@ -711,6 +2698,8 @@ fn filewatch_start(
     inner.watched_path = None;
 
     let watched_path_for_events = canonical_path.clone();
+    // The last version the webview was sent, to report which lines an update touched.
+    let mut previous_content = fs::read_to_string(&canonical_path).ok();
     let app_handle = app.clone();
     let mut watcher =
         notify::recommended_watcher(move |event_result: notify::Result<notify::Event>| {
@@ -722,13 +2711,29 @@ fn filewatch_start(
                 return;
             }
 
-            let payload = match build_payload(&watched_path_for_events) {
+            let mut payload = match build_document_payload(&app_handle, &watched_path_for_events) {
                 Ok(payload) => payload,
                 Err(_) => return,
             };
 
+            // Editors often save with several events; only a real change is worth a re-render.
+            if previous_content.as_deref() == Some(payload.content.as_str()) {
+                return;
+            }
+            if let Some(previous) = previous_content.as_deref() {
+                payload.changed_lines =
+                    Some(line_diff::changed_line_ranges(previous, &payload.content));
+            }
+            record_snapshots(
+                &app_handle,
+                &watched_path_for_events,
+                previous_content.as_deref(),
+                &payload.content,
+            );
+            previous_content = Some(payload.content.clone());
+
             if let Some(window) = app_handle.get_webview_window("main") {
-                let _ = window.emit("file:changed", payload);
+                let _ = window.emit(event_name, payload);
             }
         })
         .map_err(|err| format!("Failed to initialize markdown file watcher: {err}"))?;
@@ -748,15 +2753,156 @@ fn filewatch_start(
     Ok(())
 }
 
+fn stop_markdown_file_watch(watch_inner: &Mutex<FileWatchInner>) -> Result<(), String> {
+    let mut inner = watch_inner
+        .lock()
+        .map_err(|_| "Failed to lock file watch state.".to_string())?;
+
+    inner.watcher = None;
+    inner.watched_path = None;
+
+    Ok(())
+}
+
+#[tauri::command]
+fn filewatch_start(
+    app: AppHandle,
+    watch_state: State<FileWatchState>,
+    scope: State<AccessScope>,
+    path: String,
+) -> Result<(), String> {
+    watch_markdown_file(&app, &watch_state.inner, &scope, &path, "file:changed")
+}
+
 #[tauri::command]
 fn filewatch_stop(watch_state: State<FileWatchState>) -> Result<(), String> {
+    stop_markdown_file_watch(&watch_state.inner)
+}
+
+#[tauri::command]
+fn comparewatch_start(
+    app: AppHandle,
+    watch_state: State<CompareWatchState>,
+    scope: State<AccessScope>,
+    path: String,
+) -> Result<(), String> {
+    watch_markdown_file(&app, &watch_state.inner, &scope, &path, "compare:changed")
+}
+
+#[tauri::command]
+fn comparewatch_stop(watch_state: State<CompareWatchState>) -> Result<(), String> {
+    stop_markdown_file_watch(&watch_state.inner)
+}
+
+#[tauri::command]
+fn stylewatch_start(
+    app: AppHandle,
+    watch_state: State<StylesheetWatchState>,
+    scope: State<AccessScope>,
+    path: String,
+) -> Result<(), String> {
+    let canonical_path = scope
+        .check(Path::new(&path))
+        .map_err(|err| err.to_string())?;
+    let content = fs::read_to_string(&canonical_path)
+        .map_err(|err| format!("Failed to read file '{}': {err}", canonical_path.display()))?;
+    let stylesheet_paths: Vec<PathBuf> =
+        resolve_document_stylesheets(&app, &canonical_path, &content)
+            .into_iter()
+            .map(|(_, stylesheet_path)| stylesheet_path)
+            .collect();
+
     let mut inner = watch_state
         .inner
         .lock()
-        .map_err(|_| "Failed to lock file watch state.".to_string())?;
+        .map_err(|_| "Failed to lock stylesheet watch state.".to_string())?;
+
+    if inner.watcher.is_some()
+        && inner.document_path.as_ref() == Some(&canonical_path)
+        && inner.stylesheet_paths == stylesheet_paths
+    {
+        return Ok(());
+    }
 
     inner.watcher = None;
-    inner.watched_path = None;
+    inner.document_path = None;
+    inner.stylesheet_paths.clear();
+
+    if stylesheet_paths.is_empty() {
+        return Ok(());
+    }
+
+    let document_path_for_events = canonical_path.clone();
+    let stylesheet_paths_for_events = stylesheet_paths.clone();
+    let app_handle = app.clone();
+    let mut watcher =
+        notify::recommended_watcher(move |event_result: notify::Result<notify::Event>| {
+            let Ok(event) = event_result else {
+                return;
+            };
+
+            if !is_folder_change_event(&event.kind)
+                || !event
+                    .paths
+                    .iter()
+                    .any(|changed| stylesheet_paths_for_events.contains(changed))
+            {
+                return;
+            }
+
+            let Ok(content) = fs::read_to_string(&document_path_for_events) else {
+                return;
+            };
+            let stylesheet_paths =
+                resolve_document_stylesheets(&app_handle, &document_path_for_events, &content);
+            let payload = StylesheetsChangedPayload {
+                file_path: document_path_for_events.to_string_lossy().to_string(),
+                stylesheets: user_styles::load_stylesheets(&stylesheet_paths),
+            };
+
+            if let Some(window) = app_handle.get_webview_window("main") {
+                let _ = window.emit("file:stylesheets-changed", payload);
+            }
+        })
+        .map_err(|err| format!("Failed to initialize stylesheet watcher: {err}"))?;
+
+    // Watch parent folders so stylesheets that are created or replaced on save are noticed.
+    let mut watched_dirs: Vec<&Path> = Vec::new();
+    for stylesheet_path in &stylesheet_paths {
+        let Some(parent) = stylesheet_path.parent().filter(|parent| parent.is_dir()) else {
+            continue;
+        };
+        if watched_dirs.contains(&parent) {
+            continue;
+        }
+        watcher
+            .watch(parent, RecursiveMode::NonRecursive)
+            .map_err(|err| {
+                format!(
+                    "Failed to watch stylesheet folder '{}': {err}",
+                    parent.display()
+                )
+            })?;
+        watched_dirs.push(parent);
+    }
+
+    inner.document_path = Some(canonical_path);
+    inner.stylesheet_paths = stylesheet_paths;
+    inner.watcher = Some(watcher);
+
+    Ok(())
+}
+
+#[tauri::command]
+fn stylewatch_stop(watch_state: State<StylesheetWatchState>) -> Result<(), String> {
+    let mut inner = watch_state
+        .inner
+        .lock()
+        .map_err(|_| "Failed to lock stylesheet watch state.".to_string())?;
+
+    inner.watcher = None;
+    inner.document_path = None;
+    inner.stylesheet_paths.clear();
 
     Ok(())
 }
@@ -765,10 +2911,12 @@ fn filewatch_stop(watch_state: State<FileWatchState>) -> Result<(), String> {
 fn folderwatch_start(
     app: AppHandle,
     watch_state: State<FolderWatchState>,
+    scope: State<AccessScope>,
     path: String,
 ) -> Result<(), String> {
-    let canonical_path = fs::canonicalize(Path::new(&path))
-        .map_err(|err| format!("Failed to resolve folder path '{path}': {err}"))?;
+    let canonical_path = scope
+        .check(Path::new(&path))
+        .map_err(|err| err.to_string())?;
 
     if !canonical_path.is_dir() {
         return Err("Can only watch folders.".to_string());
@@ -779,15 +2927,30 @@ fn folderwatch_start(
         .lock()
         .map_err(|_| "Failed to lock folder watch state.".to_string())?;
 
-    if inner.watcher.is_some() && inner.watched_folder.as_ref() == Some(&canonical_path) {
+    // Trusting the folder starts git status, so the index needs watching from then on.
+    let trusted = is_document_trusted(&app, &canonical_path);
+    if inner.watcher.is_some()
+        && inner.watched_folder.as_ref() == Some(&canonical_path)
+        && inner.watched_trusted == trusted
+    {
         return Ok(());
     }
 
     inner.watcher = None;
     inner.watched_folder = None;
-
+    inner.book_directories.clear();
+
+    // Staging, committing or checking out only rewrites the index, so watch it as well to
+    // keep the git status badges current. Finding it runs git, so only in trusted folders.
+    let git_index_path = git::GitRepository::discover(&canonical_path)
+        .filter(|_| trusted)
+        .and_then(|repository| repository.index_path().ok());
+    let git_index_for_events = git_index_path.clone();
     let watched_folder_for_events = canonical_path.clone();
     let watched_folder_for_payload = canonical_path.to_string_lossy().to_string();
+    let book_directories =
+        book::watch_directories(&canonical_path, book::load(&canonical_path).as_ref());
+    let mut book_directories_for_events = book_directories.clone();
     let app_handle = app.clone();
     let mut watcher =
         notify::recommended_watcher(move |event_result: notify::Result<notify::Event>| {
@@ -799,11 +2962,38 @@ fn folderwatch_start(
                 return;
             }
 
-            let files =
-                list_markdown_files_in_folder(&watched_folder_for_events).unwrap_or_default();
+            // The index's directory is watched too; skip lock files and other git internals.
+            if let Some(git_index) = git_index_for_events.as_deref() {
+                let only_git_internals = !event.paths.is_empty()
+                    && event
+                        .paths
+                        .iter()
+                        .all(|path| path.parent() == git_index.parent() && path != git_index);
+                if only_git_internals {
+                    return;
+                }
+            }
+
+            let sort_mode = folder_sort_mode(&app_handle, &watched_folder_for_events);
+            let trusted = is_document_trusted(&app_handle, &watched_folder_for_events);
+            let (files, book) = list_folder_files(&watched_folder_for_events, sort_mode, trusted)
+                .unwrap_or_default();
+
+            // Editing the ordering file can move chapters into directories not watched yet.
+            let wanted = book::watch_directories(&watched_folder_for_events, book.as_ref());
+            if wanted != book_directories_for_events {
+                book_directories_for_events = wanted.clone();
+                // Watching from inside the event handler would wait on this very thread.
+                let app_handle = app_handle.clone();
+                let folder = watched_folder_for_events.clone();
+                thread::spawn(move || update_book_watches(&app_handle, &folder, wanted));
+            }
+
             let payload = MarkdownFolderPayload {
                 folder_path: watched_folder_for_payload.clone(),
                 files,
+                book: book.map(|book| book.source),
+                sort_mode,
             };
 
             if let Some(window) = app_handle.get_webview_window("main") {
@@ -821,12 +3011,63 @@ fn folderwatch_start(
             )
         })?;
 
+    if let Some(index_directory) = git_index_path.as_deref().and_then(Path::parent) {
+        if let Err(err) = watcher.watch(index_directory, RecursiveMode::NonRecursive) {
+            log::info!(
+                "Not watching git index in '{}': {err}",
+                index_directory.display()
+            );
+        }
+    }
+
+    watch_book_directories(&mut watcher, &mut inner.book_directories, book_directories);
     inner.watched_folder = Some(canonical_path);
+    inner.watched_trusted = trusted;
     inner.watcher = Some(watcher);
 
     Ok(())
 }
 
+/// Watches the subdirectories holding a folder's ordering file and chapters, e.g. mdBook's
+/// `src/`, and stops watching the ones the book no longer uses.
+fn watch_book_directories(
+    watcher: &mut RecommendedWatcher,
+    watched: &mut BTreeSet<PathBuf>,
+    wanted: BTreeSet<PathBuf>,
+) {
+    for directory in watched.difference(&wanted) {
+        let _ = watcher.unwatch(directory);
+    }
+    for directory in wanted.difference(watched) {
+        if let Err(err) = watcher.watch(directory, RecursiveMode::NonRecursive) {
+            log::info!(
+                "Not watching book directory '{}': {err}",
+                directory.display()
+            );
+        }
+    }
+    *watched = wanted;
+}
+
+fn update_book_watches(app: &AppHandle, folder: &Path, wanted: BTreeSet<PathBuf>) {
+    let watch_state = app.state::<FolderWatchState>();
+    let Ok(mut inner) = watch_state.inner.lock() else {
+        return;
+    };
+    if inner.watched_folder.as_deref() != Some(folder) {
+        return;
+    }
+
+    let FolderWatchInner {
+        watcher,
+        book_directories,
+        ..
+    } = &mut *inner;
+    if let Some(watcher) = watcher.as_mut() {
+        watch_book_directories(watcher, book_directories, wanted);
+    }
+}
+
 #[tauri::command]
 fn folderwatch_stop(watch_state: State<FolderWatchState>) -> Result<(), String> {
     let mut inner = watch_state
@@ -836,6 +3077,7 @@ fn folderwatch_stop(watch_state: State<FolderWatchState>) -> Result<(), String>
 
     inner.watcher = None;
     inner.watched_folder = None;
+    inner.book_directories.clear();
 
     Ok(())
 }
@@ -847,6 +3089,52 @@ fn file_consume_pending_opened_target(
     pending_targets.pop()
 }
 
+#[tauri::command]
+fn app_report_viewer_state(
+    store: State<ViewerStateStore>,
+    editor_sync: State<EditorSyncState>,
+    document_waiters: State<DocumentWaiters>,
+    settings: State<SettingsStore>,
+    viewer_state: ViewerState,
+) {
+    document_waiters.update(viewer_state.file_path.as_deref());
+
+    let Ok(mut state) = store.state.lock() else {
+        return;
+    };
+    let file_changed = state.file_path != viewer_state.file_path;
+    let position_changed = file_changed || state.line != viewer_state.line;
+    *state = viewer_state.clone();
+    drop(state);
+
+    if let Some(path) = viewer_state.file_path.as_ref().filter(|_| file_changed) {
+        if let Err(err) =
+            settings.update(|settings| settings.record_recent_file(PathBuf::from(path)))
+        {
+            log::warn!("Failed to remember recent file '{path}': {err}");
+        }
+    }
+
+    let Some(path) = viewer_state.file_path.filter(|_| position_changed) else {
+        return;
+    };
+    let Ok(inner) = editor_sync.inner.lock() else {
+        return;
+    };
+    if inner
+        .suppress_until
+        .is_some_and(|suppress_until| Instant::now() < suppress_until)
+    {
+        return;
+    }
+    if let Some(hub) = inner.hub.as_ref() {
+        hub.broadcast(&CursorMessage {
+            path,
+            line: viewer_state.line,
+        });
+    }
+}
+
 #[tauri::command]
 fn app_get_startup_options(startup_options: State<StartupOptionsState>) -> StartupOptions {
     startup_options.options.clone()
@@ -859,24 +3147,86 @@ pub fn run() {
         return;
     }
 
-    let launch_target = parsed_launch_args.launch_target.clone();
+    if let Some(action) = parsed_launch_args.desktop_action {
+        std::process::exit(desktop_integration::run(action));
+    }
+
+    for message in &parsed_launch_args.unmatched_args {
+        eprintln!("Skipping {message}.");
+    }
+
+    if let Some(command) = parsed_launch_args.remote_command.as_ref() {
+        std::process::exit(run_remote_command(command));
+    }
+
+    if parsed_launch_args.wait {
+        if let Some(exit_code) = wait_in_running_instance(&parsed_launch_args) {
+            std::process::exit(exit_code);
+        }
+    }
+
+    let launch_payload = parsed_launch_args.launch_payload();
     let startup_options = parsed_launch_args.startup_options.clone();
 
     tauri::Builder::default()
         .manage(PendingOpenTargets::default())
+        .manage(AccessScope::default())
         .manage(FileWatchState::default())
+        .manage(CompareWatchState::default())
         .manage(FolderWatchState::default())
+        .manage(QuickOpenIndex::default())
+        .manage(StylesheetWatchState::default())
+        .manage(TrustState {
+            forced_safe_mode: AtomicBool::new(startup_options.safe_mode == Some(true)),
+        })
         .manage(StartupOptionsState {
             options: startup_options.clone(),
         })
-        .plugin(tauri_plugin_single_instance::init(|app, argv, _cwd| {
-            let parsed_args = parse_cli_args(argv.into_iter().skip(1));
+        .manage(ViewerStateStore::default())
+        .manage(EditorSyncState::default())
+        .manage(DocumentWaiters::default())
+        .manage(RemoteImageAllowance::default())
+        .register_asynchronous_uri_scheme_protocol(
+            asset_protocol::ASSET_SCHEME,
+            |ctx, request, responder| {
+                let app = ctx.app_handle().clone();
+                thread::spawn(move || {
+                    let scope = app.state::<AccessScope>();
+                    responder.respond(asset_protocol::handle_asset_request(&scope, &request));
+                });
+            },
+        )
+        .register_asynchronous_uri_scheme_protocol(
+            remote_images::REMOTE_IMAGE_SCHEME,
+            |ctx, request, responder| {
+                let app = ctx.app_handle().clone();
+                thread::spawn(move || {
+                    let allowance = app.state::<RemoteImageAllowance>();
+                    responder.respond(remote_images::handle_remote_image_request(
+                        &allowance, &request,
+                    ));
+                });
+            },
+        )
+        .plugin(tauri_plugin_single_instance::init(|app, argv, cwd| {
+            let mut parsed_args = parse_cli_args_in(Path::new(&cwd), argv.into_iter().skip(1));
             if parsed_args.exit_after_print {
                 return;
             }
 
-            let should_focus =
-                parsed_args.launch_target.is_some() || !parsed_args.startup_options.is_empty();
+            let launch_payload = parsed_args.launch_payload();
+            let should_focus = launch_payload.is_some() || !parsed_args.startup_options.is_empty();
+
+            // `--safe` restricts what that launch opens; a later launch opening a document
+            // without it lifts the restriction again.
+            let trust_state = app.state::<TrustState>();
+            if parsed_args.startup_options.safe_mode == Some(true) {
+                trust_state.forced_safe_mode.store(true, Ordering::Relaxed);
+            } else if launch_payload.is_some()
+                && trust_state.forced_safe_mode.swap(false, Ordering::Relaxed)
+            {
+                parsed_args.startup_options.safe_mode = Some(false);
+            }
 
             if !parsed_args.startup_options.is_empty() {
                 if let Some(window) = app.get_webview_window("main") {
@@ -884,24 +3234,49 @@ pub fn run() {
                 }
             }
 
-            if let Some(target) = parsed_args.launch_target {
-                queue_external_open(app, target.to_payload(), true);
+            if let Some(payload) = launch_payload {
+                queue_external_open(app, payload, true);
             } else if should_focus {
                 focus_main_window(app);
             }
         }))
+        .on_window_event(|window, event| {
+            if let WindowEvent::DragDrop(drag_drop_event) = event {
+                handle_drag_drop_event(window.app_handle(), drag_drop_event);
+            }
+        })
         .invoke_handler(tauri::generate_handler![
             pick_markdown_file,
             pick_markdown_folder,
             read_markdown_file,
             read_markdown_folder,
+            follow_link,
+            remote_images_allow,
+            set_folder_sort_mode,
+            quick_open_search,
+            git_file_history,
+            git_blame,
+            git_read_file_revision,
+            git_read_file_diff,
+            snapshot_list,
+            snapshot_set_enabled,
+            snapshot_read_file,
+            snapshot_read_file_diff,
             open_in_vscode,
+            open_with_system_handler,
             theme_get_system,
+            vscode_get_appearance,
+            trust_add_folder,
             filewatch_start,
             filewatch_stop,
+            comparewatch_start,
+            comparewatch_stop,
+            stylewatch_start,
+            stylewatch_stop,
             folderwatch_start,
             folderwatch_stop,
             file_consume_pending_opened_target,
+            app_report_viewer_state,
             app_get_startup_options
         ])
         .setup(move |app| {
@@ -913,9 +3288,25 @@ pub fn run() {
                 )?;
             }
 
-            if let Some(target) = launch_target.clone() {
-                let payload = target.to_payload();
-                queue_external_open(&app.handle(), payload.clone(), false);
+            app.manage(SettingsStore::load(
+                app.path()
+                    .app_config_dir()
+                    .ok()
+                    .map(|dir| dir.join(settings::SETTINGS_FILE_NAME)),
+            ));
+            app.manage(SnapshotStore::new(
+                app.path()
+                    .app_data_dir()
+                    .ok()
+                    .map(|dir| dir.join(snapshots::SNAPSHOTS_DIR_NAME)),
+            ));
+
+            spawn_system_theme_monitor(app.handle().clone());
+            start_remote_control_server(app.handle().clone());
+            start_editor_sync_server(app.handle().clone());
+
+            if let Some(payload) = launch_payload.clone() {
+                queue_external_open(app.handle(), payload.clone(), false);
 
                 if let Some(window) = app.get_webview_window("main") {
                     let _ = window.emit("file:open-on-launch", payload);
@@ -927,8 +3318,17 @@ pub fn run() {
         .build(tauri::generate_context!())
         .expect("error while building tauri application")
         .run(|app, event| {
+            // Linux and Windows deliver files and deep links as argv instead.
+            #[cfg(any(target_os = "macos", target_os = "ios"))]
             if let tauri::RunEvent::Opened { urls } = event {
                 for url in urls {
+                    if url.scheme() == DEEP_LINK_SCHEME {
+                        if let Some((target, position)) = parse_deep_link(&url) {
+                            queue_external_open(app, target.to_payload(position), true);
+                        }
+                        continue;
+                    }
+
                     let Ok(path) = url.to_file_path() else {
                         continue;
                     };
@@ -937,8 +3337,11 @@ pub fn run() {
                         continue;
                     };
 
-                    queue_external_open(app, target.to_payload(), true);
+                    queue_external_open(app, target.to_payload(None), true);
                 }
             }
+
+            #[cfg(not(any(target_os = "macos", target_os = "ios")))]
+            let _ = (app, event);
         });
 }
@@ -0,0 +1,148 @@
+use serde::Serialize;
+
+/// Above this many line pairs the changed middle is reported as one range instead of running
+/// the quadratic alignment.
+const MAX_ALIGNED_PAIRS: usize = 4_000_000;
+
+/// Lines of the new version that were inserted or changed, 1-based and inclusive like
+/// `data-source-line`. A pure deletion is reported as the line that now follows it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangedLineRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+fn push_range(ranges: &mut Vec<ChangedLineRange>, start: usize, end: usize) {
+    if let Some(last) = ranges.last_mut() {
+        if start <= last.end + 1 {
+            last.end = last.end.max(end);
+            return;
+        }
+    }
+    ranges.push(ChangedLineRange { start, end });
+}
+
+/// Indices into `new` of lines that are not part of a longest common subsequence with `old`,
+/// plus the positions in `new` where lines of `old` were dropped.
+fn unmatched_lines(old: &[&str], new: &[&str]) -> (Vec<usize>, Vec<usize>) {
+    let columns = new.len() + 1;
+    let mut lengths = vec![0u32; (old.len() + 1) * columns];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i * columns + j] = if old[i] == new[j] {
+                lengths[(i + 1) * columns + j + 1] + 1
+            } else {
+                lengths[(i + 1) * columns + j].max(lengths[i * columns + j + 1])
+            };
+        }
+    }
+
+    let (mut inserted, mut deleted) = (Vec::new(), Vec::new());
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            i += 1;
+            j += 1;
+        } else if i < old.len()
+            && (j == new.len() || lengths[(i + 1) * columns + j] >= lengths[i * columns + j + 1])
+        {
+            // Deleting first makes a replaced line report its own position only.
+            deleted.push(j);
+            i += 1;
+        } else {
+            inserted.push(j);
+            j += 1;
+        }
+    }
+    (inserted, deleted)
+}
+
+/// The line ranges of `new` that differ from `old`.
+pub fn changed_line_ranges(old: &str, new: &str) -> Vec<ChangedLineRange> {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+
+    let prefix = old_lines
+        .iter()
+        .zip(&new_lines)
+        .take_while(|(left, right)| left == right)
+        .count();
+    let suffix = old_lines[prefix..]
+        .iter()
+        .rev()
+        .zip(new_lines[prefix..].iter().rev())
+        .take_while(|(left, right)| left == right)
+        .count();
+    let old_middle = &old_lines[prefix..old_lines.len() - suffix];
+    let new_middle = &new_lines[prefix..new_lines.len() - suffix];
+    // A deletion at the very end has no following line; point at the last one instead.
+    let last_line = new_lines.len().max(1);
+    let line_at = |index: usize| (prefix + index + 1).min(last_line);
+
+    if old_middle.is_empty() && new_middle.is_empty() {
+        return Vec::new();
+    }
+    if old_middle.len().saturating_mul(new_middle.len()) > MAX_ALIGNED_PAIRS {
+        let end = line_at(new_middle.len().saturating_sub(1));
+        return vec![ChangedLineRange {
+            start: line_at(0),
+            end,
+        }];
+    }
+
+    let (inserted, deleted) = unmatched_lines(old_middle, new_middle);
+    let mut lines: Vec<usize> = inserted.into_iter().chain(deleted).map(line_at).collect();
+    lines.sort_unstable();
+    lines.dedup();
+
+    let mut ranges = Vec::new();
+    for line in lines {
+        push_range(&mut ranges, line, line);
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: usize, end: usize) -> ChangedLineRange {
+        ChangedLineRange { start, end }
+    }
+
+    #[test]
+    fn reports_nothing_for_identical_content() {
+        assert_eq!(
+            changed_line_ranges("# A\n\ntext\n", "# A\n\ntext\n"),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn reports_inserted_and_modified_lines_in_the_new_version() {
+        let old = "# Title\n\none\ntwo\nthree\n\n## End\n";
+        let new = "# Title\n\none\n2\nthree\nfour\n\n## End\nextra\n";
+
+        assert_eq!(
+            changed_line_ranges(old, new),
+            vec![range(4, 4), range(6, 6), range(9, 9)]
+        );
+    }
+
+    #[test]
+    fn merges_adjacent_lines_and_points_deletions_at_the_next_line() {
+        assert_eq!(
+            changed_line_ranges("a\nb\nc\nd\n", "a\nx\ny\nd\n"),
+            vec![range(2, 3)]
+        );
+        assert_eq!(
+            changed_line_ranges("a\nb\nc\n", "a\nc\n"),
+            vec![range(2, 2)]
+        );
+        assert_eq!(
+            changed_line_ranges("a\nb\nc\n", "a\nb\n"),
+            vec![range(2, 2)]
+        );
+    }
+}
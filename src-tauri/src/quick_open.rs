@@ -0,0 +1,379 @@
+use crate::{git::GitRepository, is_markdown_path};
+use ignore::{DirEntry, WalkBuilder};
+use serde::Serialize;
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// Stops indexing huge trees (a home folder, say) instead of stalling the palette.
+const MAX_INDEXED_FILES: usize = 50_000;
+const MAX_RESULTS: usize = 50;
+/// Folders skipped outside git repositories, where they are rarely listed in an ignore file.
+const SKIPPED_FOLDER_NAMES: [&str; 3] = ["node_modules", "target", "dist"];
+/// Score added for the most recently opened file, shrinking by `RECENT_BONUS_STEP` per rank.
+const RECENT_BONUS: i64 = 48;
+const RECENT_BONUS_STEP: i64 = 4;
+
+const MATCH_SCORE: i64 = 16;
+const CONSECUTIVE_BONUS: i64 = 12;
+const BOUNDARY_BONUS: i64 = 10;
+const FILE_NAME_BONUS: i64 = 24;
+const MAX_GAP_PENALTY: i64 = 12;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuickOpenMatch {
+    pub file_path: String,
+    /// Path below the opened folder with `/` separators.
+    pub relative_path: String,
+    /// Character (not byte) offsets into `relative_path` of the matched query characters.
+    pub positions: Vec<usize>,
+}
+
+struct IndexedFile {
+    path: PathBuf,
+    relative_path: String,
+    chars: Vec<char>,
+    lowercase: Vec<char>,
+    /// Character offset where the file name starts in `relative_path`.
+    file_name_start: usize,
+}
+
+struct FolderIndex {
+    folder: PathBuf,
+    files: Arc<Vec<IndexedFile>>,
+}
+
+/// Markdown files under the opened folder, listed once when the palette opens so each
+/// keystroke only runs the matcher.
+#[derive(Default)]
+pub struct QuickOpenIndex {
+    cache: Mutex<Option<FolderIndex>>,
+}
+
+/// Markdown files anywhere under `folder`, leaving out hidden entries and whatever
+/// `.gitignore`, `.ignore` and git's exclude files rule out, inside a repository or not.
+/// Outside a repository, dependency folders are skipped as well.
+pub fn list_markdown_files(folder: &Path) -> Vec<PathBuf> {
+    let in_repository = GitRepository::discover(folder).is_some();
+    // Symlinked folders are not followed, so links back up the tree cannot loop.
+    let walker = WalkBuilder::new(folder)
+        .hidden(true)
+        .require_git(false)
+        .follow_links(false)
+        .filter_entry(move |entry| {
+            in_repository
+                || entry.depth() == 0
+                || !entry
+                    .file_type()
+                    .is_some_and(|file_type| file_type.is_dir())
+                || !SKIPPED_FOLDER_NAMES.contains(&entry.file_name().to_string_lossy().as_ref())
+        })
+        .build();
+
+    let mut files: Vec<PathBuf> = walker
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            !entry
+                .file_type()
+                .is_some_and(|file_type| file_type.is_dir())
+        })
+        .map(DirEntry::into_path)
+        .filter(|path| is_markdown_path(path) && path.is_file())
+        .take(MAX_INDEXED_FILES)
+        .collect();
+    files.sort();
+    files
+}
+
+fn index_files(folder: &Path) -> Vec<IndexedFile> {
+    list_markdown_files(folder)
+        .into_iter()
+        .filter_map(|path| {
+            let relative_path = path
+                .strip_prefix(folder)
+                .ok()?
+                .to_string_lossy()
+                .replace('\\', "/");
+            let chars: Vec<char> = relative_path.chars().collect();
+            let lowercase = chars.iter().map(|ch| fold_case(*ch)).collect();
+            let file_name_start = chars
+                .iter()
+                .rposition(|ch| *ch == '/')
+                .map_or(0, |index| index + 1);
+            Some(IndexedFile {
+                path,
+                relative_path,
+                chars,
+                lowercase,
+                file_name_start,
+            })
+        })
+        .collect()
+}
+
+fn fold_case(ch: char) -> char {
+    ch.to_lowercase().next().unwrap_or(ch)
+}
+
+fn is_boundary(chars: &[char], index: usize) -> bool {
+    let Some(previous) = index.checked_sub(1).map(|previous| chars[previous]) else {
+        return true;
+    };
+    matches!(previous, '/' | '\\' | '-' | '_' | '.' | ' ')
+        || (previous.is_lowercase() && chars[index].is_uppercase())
+}
+
+/// Finds `query` as a subsequence of `text[start..]`, first left to right to find where the
+/// earliest match ends, then right to left from there so the matched span is as tight as
+/// possible.
+fn match_positions(query: &[char], text: &[char], start: usize) -> Option<Vec<usize>> {
+    let mut query_index = 0;
+    let mut end = None;
+    for (index, ch) in text.iter().enumerate().skip(start) {
+        if *ch == query[query_index] {
+            query_index += 1;
+            if query_index == query.len() {
+                end = Some(index);
+                break;
+            }
+        }
+    }
+
+    let end = end?;
+    let mut positions = Vec::with_capacity(query.len());
+    let mut remaining = query.iter().rev().peekable();
+    for index in (start..=end).rev() {
+        if remaining.next_if(|ch| **ch == text[index]).is_some() {
+            positions.push(index);
+        }
+    }
+    positions.reverse();
+    Some(positions)
+}
+
+fn score_positions(file: &IndexedFile, positions: &[usize]) -> i64 {
+    let mut score = 0;
+    for (rank, &position) in positions.iter().enumerate() {
+        score += MATCH_SCORE;
+        if is_boundary(&file.chars, position) {
+            score += BOUNDARY_BONUS;
+        }
+        if let Some(&previous) = rank.checked_sub(1).map(|rank| &positions[rank]) {
+            let gap = (position - previous - 1) as i64;
+            score += if gap == 0 {
+                CONSECUTIVE_BONUS
+            } else {
+                -gap.min(MAX_GAP_PENALTY)
+            };
+        }
+    }
+    if positions
+        .first()
+        .is_some_and(|&first| first >= file.file_name_start)
+    {
+        score += FILE_NAME_BONUS;
+    }
+    score
+}
+
+/// Scores `query` (already case-folded) against the file, preferring a match inside the file
+/// name over one spread across the folders.
+fn fuzzy_match(query: &[char], file: &IndexedFile) -> Option<(i64, Vec<usize>)> {
+    let in_path = match_positions(query, &file.lowercase, 0)?;
+    let in_file_name = match_positions(query, &file.lowercase, file.file_name_start);
+
+    [Some(in_path), in_file_name]
+        .into_iter()
+        .flatten()
+        .map(|positions| (score_positions(file, &positions), positions))
+        .max_by_key(|(score, _)| *score)
+}
+
+fn recent_bonus(recent: &[PathBuf], path: &Path) -> i64 {
+    recent
+        .iter()
+        .position(|recent_path| recent_path == path)
+        .map_or(0, |rank| {
+            (RECENT_BONUS - rank as i64 * RECENT_BONUS_STEP).max(0)
+        })
+}
+
+impl QuickOpenIndex {
+    fn files(&self, folder: &Path, refresh: bool) -> Arc<Vec<IndexedFile>> {
+        let mut cache = match self.cache.lock() {
+            Ok(cache) => cache,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        match cache.as_ref() {
+            Some(index) if !refresh && index.folder == folder => Arc::clone(&index.files),
+            _ => {
+                let files = Arc::new(index_files(folder));
+                *cache = Some(FolderIndex {
+                    folder: folder.to_path_buf(),
+                    files: Arc::clone(&files),
+                });
+                files
+            }
+        }
+    }
+
+    /// The best matches for `query` under `folder` (canonical). `recent` lists recently opened
+    /// files, most recent first; they rank higher, and lead the list for an empty query.
+    /// `refresh` relists the folder instead of reusing the previous listing.
+    pub fn search(
+        &self,
+        folder: &Path,
+        query: &str,
+        recent: &[PathBuf],
+        refresh: bool,
+    ) -> Vec<QuickOpenMatch> {
+        let files = self.files(folder, refresh);
+        let query: Vec<char> = query
+            .chars()
+            .filter(|ch| !ch.is_whitespace())
+            .map(fold_case)
+            .collect();
+
+        let mut matches: Vec<(i64, &IndexedFile, Vec<usize>)> = if query.is_empty() {
+            files
+                .iter()
+                .map(|file| (recent_bonus(recent, &file.path), file, Vec::new()))
+                .collect()
+        } else {
+            files
+                .iter()
+                .filter_map(|file| {
+                    let (score, positions) = fuzzy_match(&query, file)?;
+                    Some((score + recent_bonus(recent, &file.path), file, positions))
+                })
+                .collect()
+        };
+
+        matches.sort_by(|(left_score, left, _), (right_score, right, _)| {
+            right_score
+                .cmp(left_score)
+                .then_with(|| left.chars.len().cmp(&right.chars.len()))
+                .then_with(|| left.relative_path.cmp(&right.relative_path))
+        });
+        matches.truncate(MAX_RESULTS);
+
+        matches
+            .into_iter()
+            .map(|(_, file, positions)| QuickOpenMatch {
+                file_path: file.path.to_string_lossy().to_string(),
+                relative_path: file.relative_path.clone(),
+                positions,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::create_temp_directory;
+    use std::fs;
+
+    fn write_files(root: &Path, paths: &[&str]) {
+        for path in paths {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().expect("file should have a parent"))
+                .expect("should create folder");
+            fs::write(path, "# Doc\n").expect("should write file");
+        }
+    }
+
+    fn relative_paths(matches: &[QuickOpenMatch]) -> Vec<&str> {
+        matches
+            .iter()
+            .map(|found| found.relative_path.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn list_markdown_files_walks_subfolders_and_skips_hidden_ignored_and_dependencies() {
+        let root = create_temp_directory("quick-open");
+        write_files(
+            &root,
+            &[
+                "README.md",
+                "guide/setup.md",
+                "guide/deep/notes.markdown",
+                "guide/image.png",
+                ".cache/old.md",
+                "node_modules/pkg/README.md",
+                "drafts/wip.md",
+                "notes/scratch.md",
+            ],
+        );
+        // Honoured without a git repository around the folder.
+        fs::write(root.join(".gitignore"), "drafts/\n").expect("should write gitignore");
+        fs::write(root.join(".ignore"), "scratch.md\n").expect("should write ignore file");
+
+        assert_eq!(
+            list_markdown_files(&root),
+            vec![
+                root.join("README.md"),
+                root.join("guide/deep/notes.markdown"),
+                root.join("guide/setup.md"),
+            ]
+        );
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn search_ranks_tight_file_name_matches_and_reports_positions() {
+        let root = create_temp_directory("quick-open");
+        write_files(
+            &root,
+            &[
+                "guide/setup.md",
+                "reference/settings-upgrade.md",
+                "security/threat-model.md",
+            ],
+        );
+        let index = QuickOpenIndex::default();
+
+        let matches = index.search(&root, "setup", &[], false);
+        assert_eq!(
+            relative_paths(&matches),
+            vec!["guide/setup.md", "reference/settings-upgrade.md"]
+        );
+        assert_eq!(matches[0].positions, vec![6, 7, 8, 9, 10]);
+
+        let matches = index.search(&root, "T M", &[], false);
+        assert_eq!(relative_paths(&matches)[0], "security/threat-model.md");
+        assert!(index.search(&root, "zzz", &[], false).is_empty());
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn search_boosts_recent_files_and_refreshes_on_request() {
+        let root = create_temp_directory("quick-open");
+        write_files(&root, &["a.md", "b.md", "c.md"]);
+        let index = QuickOpenIndex::default();
+        let recent = [root.join("c.md"), root.join("b.md")];
+
+        assert_eq!(
+            relative_paths(&index.search(&root, "", &recent, false)),
+            vec!["c.md", "b.md", "a.md"]
+        );
+        assert_eq!(
+            relative_paths(&index.search(&root, "md", &recent, false)),
+            vec!["c.md", "b.md", "a.md"]
+        );
+
+        write_files(&root, &["d.md"]);
+        assert!(index.search(&root, "d.md", &[], false).is_empty());
+        assert_eq!(
+            relative_paths(&index.search(&root, "d.md", &[], true)),
+            vec!["d.md"]
+        );
+
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,503 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    env, fmt,
+    path::{Path, PathBuf},
+};
+
+#[cfg(unix)]
+use std::{
+    fs,
+    io::{BufRead, BufReader, ErrorKind, Write},
+    os::unix::{
+        fs::{DirBuilderExt, MetadataExt, OpenOptionsExt, PermissionsExt},
+        io::AsRawFd,
+        net::{UnixListener, UnixStream},
+    },
+    thread,
+    time::Duration,
+};
+
+#[cfg(unix)]
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Commands accepted on the control socket, one JSON object per line, e.g.
+/// `{"command":"scroll-to-line","line":80}`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum RemoteCommand {
+    ScrollToLine {
+        line: u32,
+    },
+    Reload,
+    SetTheme {
+        theme: String,
+    },
+    GetState,
+    /// Opens a document and replies only once it is no longer shown (`--wait`).
+    OpenAndWait {
+        path: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        line: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        heading: Option<String>,
+    },
+}
+
+impl RemoteCommand {
+    /// Parses `--remote <COMMAND> [ARG]` values.
+    pub fn from_cli(name: &str, argument: Option<&str>) -> Result<Self, String> {
+        match name {
+            "scroll-to-line" => argument
+                .and_then(|value| value.parse::<u32>().ok())
+                .filter(|line| *line > 0)
+                .map(|line| Self::ScrollToLine { line })
+                .ok_or_else(|| "scroll-to-line expects a positive line number.".to_string()),
+            "reload" => Ok(Self::Reload),
+            "set-theme" => argument
+                .map(|theme| Self::SetTheme {
+                    theme: theme.to_string(),
+                })
+                .ok_or_else(|| "set-theme expects dark, light, system or vscode.".to_string()),
+            "get-state" => Ok(Self::GetState),
+            _ => Err(format!(
+                "Unknown remote command '{name}'. Expected scroll-to-line, reload, set-theme or get-state."
+            )),
+        }
+    }
+
+    pub fn takes_argument(name: &str) -> bool {
+        matches!(name, "scroll-to-line" | "set-theme")
+    }
+}
+
+/// What the renderer is currently showing, as reported by the webview.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ViewerState {
+    pub file_path: Option<String>,
+    pub folder_path: Option<String>,
+    pub line: u32,
+    pub mode: String,
+    pub theme: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteResponse {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<ViewerState>,
+}
+
+impl RemoteResponse {
+    pub fn ok() -> Self {
+        Self {
+            ok: true,
+            error: None,
+            state: None,
+        }
+    }
+
+    pub fn with_state(state: ViewerState) -> Self {
+        Self {
+            state: Some(state),
+            ..Self::ok()
+        }
+    }
+
+    pub fn error(message: String) -> Self {
+        Self {
+            ok: false,
+            error: Some(message),
+            state: None,
+        }
+    }
+}
+
+/// `$XDG_RUNTIME_DIR/<name>-<user>.sock`, falling back to a private `mudkip-<user>` directory
+/// in the temp directory, which every local user can write to.
+pub fn runtime_socket_path(name: &str) -> PathBuf {
+    let user = env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .unwrap_or_else(|_| "default".to_string());
+    let runtime_dir = env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_dir())
+        .unwrap_or_else(|| env::temp_dir().join(format!("mudkip-{user}")));
+    runtime_dir.join(format!("{name}-{user}.sock"))
+}
+
+/// Fails unless `directory` is a real directory owned by the current user that nobody else
+/// can write to, so no other user can plant or replace a socket in it.
+#[cfg(unix)]
+fn verify_socket_directory(directory: &Path) -> Result<(), String> {
+    let metadata = fs::symlink_metadata(directory)
+        .map_err(|err| format!("Failed to inspect '{}': {err}", directory.display()))?;
+    // SAFETY: geteuid has no preconditions and cannot fail.
+    let user_id = unsafe { libc::geteuid() };
+    if !metadata.is_dir() || metadata.uid() != user_id || metadata.mode() & 0o022 != 0 {
+        return Err(format!(
+            "Refusing to use '{}' for sockets: it is not a private directory owned by the current user.",
+            directory.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Creates the socket's directory with mode 0700 when missing and checks that it is private.
+#[cfg(unix)]
+fn prepare_socket_directory(socket_path: &Path) -> Result<(), String> {
+    let directory = socket_path
+        .parent()
+        .ok_or_else(|| format!("Socket '{}' has no directory.", socket_path.display()))?;
+    match fs::DirBuilder::new().mode(0o700).create(directory) {
+        Ok(()) => {}
+        Err(err) if err.kind() == ErrorKind::AlreadyExists => {}
+        Err(err) => {
+            return Err(format!(
+                "Failed to create socket directory '{}': {err}",
+                directory.display()
+            ))
+        }
+    }
+    verify_socket_directory(directory)
+}
+
+pub fn remote_socket_path() -> PathBuf {
+    runtime_socket_path("mudkip")
+}
+
+/// Binds a user-only socket at `socket_path` inside a private directory, replacing a stale
+/// socket left by a crashed instance.
+#[cfg(unix)]
+pub fn bind_socket(socket_path: &Path) -> Result<UnixListener, String> {
+    prepare_socket_directory(socket_path)?;
+    if socket_path.exists() {
+        if UnixStream::connect(socket_path).is_ok() {
+            return Err(format!(
+                "Socket '{}' is already in use.",
+                socket_path.display()
+            ));
+        }
+        let _ = fs::remove_file(socket_path);
+    }
+
+    let listener = UnixListener::bind(socket_path)
+        .map_err(|err| format!("Failed to bind socket '{}': {err}", socket_path.display()))?;
+    if let Err(err) = fs::set_permissions(socket_path, fs::Permissions::from_mode(0o600)) {
+        let _ = fs::remove_file(socket_path);
+        return Err(format!(
+            "Failed to restrict socket '{}' to the current user: {err}",
+            socket_path.display()
+        ));
+    }
+    Ok(listener)
+}
+
+#[cfg(unix)]
+fn handle_connection<F>(stream: UnixStream, handler: &F) -> Result<(), String>
+where
+    F: Fn(RemoteCommand) -> RemoteResponse,
+{
+    let mut reader = BufReader::new(
+        stream
+            .try_clone()
+            .map_err(|err| format!("Failed to clone remote connection: {err}"))?,
+    );
+    let mut writer = stream;
+
+    let mut line = String::new();
+    while reader
+        .read_line(&mut line)
+        .map_err(|err| format!("Failed to read remote command: {err}"))?
+        > 0
+    {
+        let response = match serde_json::from_str::<RemoteCommand>(line.trim()) {
+            Ok(command) => handler(command),
+            Err(err) => RemoteResponse::error(format!("Invalid remote command: {err}")),
+        };
+        let serialized = serde_json::to_string(&response)
+            .map_err(|err| format!("Failed to serialize remote response: {err}"))?;
+        writeln!(writer, "{serialized}")
+            .map_err(|err| format!("Failed to write remote response: {err}"))?;
+        line.clear();
+    }
+
+    Ok(())
+}
+
+/// Listens on `socket_path` and answers each command with `handler`.
+#[cfg(unix)]
+pub fn spawn_server<F>(socket_path: PathBuf, handler: F) -> Result<(), String>
+where
+    F: Fn(RemoteCommand) -> RemoteResponse + Send + Sync + 'static,
+{
+    let listener = bind_socket(&socket_path)?;
+
+    let handler = std::sync::Arc::new(handler);
+    thread::Builder::new()
+        .name("remote-control".to_string())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                let handler = handler.clone();
+                thread::spawn(move || {
+                    if let Err(err) = handle_connection(stream, handler.as_ref()) {
+                        log::warn!("{err}");
+                    }
+                });
+            }
+        })
+        .map(|_| ())
+        .map_err(|err| format!("Failed to start remote control server: {err}"))
+}
+
+#[cfg(not(unix))]
+pub fn spawn_server<F>(_socket_path: PathBuf, _handler: F) -> Result<(), String>
+where
+    F: Fn(RemoteCommand) -> RemoteResponse + Send + Sync + 'static,
+{
+    Err("Remote control is only available on platforms with Unix domain sockets.".to_string())
+}
+
+/// Held by the running instance for as long as it runs, next to its control socket, so that
+/// clients can tell a missing instance from one whose socket they cannot reach.
+#[cfg(unix)]
+pub struct InstanceLock {
+    _file: fs::File,
+}
+
+#[cfg(unix)]
+fn instance_lock_path(socket_path: &Path) -> PathBuf {
+    socket_path.with_extension("lock")
+}
+
+#[cfg(unix)]
+fn try_lock(file: &fs::File) -> bool {
+    // SAFETY: the descriptor belongs to `file`, which outlives the call.
+    unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) == 0 }
+}
+
+/// Takes the instance lock for `socket_path`; it is released when the process exits.
+#[cfg(unix)]
+pub fn acquire_instance_lock(socket_path: &Path) -> Result<InstanceLock, String> {
+    prepare_socket_directory(socket_path)?;
+    let lock_path = instance_lock_path(socket_path);
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .mode(0o600)
+        .open(&lock_path)
+        .map_err(|err| format!("Failed to open '{}': {err}", lock_path.display()))?;
+    if !try_lock(&file) {
+        return Err(format!("Another instance holds '{}'.", lock_path.display()));
+    }
+    Ok(InstanceLock { _file: file })
+}
+
+#[cfg(not(unix))]
+pub struct InstanceLock;
+
+#[cfg(not(unix))]
+pub fn acquire_instance_lock(_socket_path: &Path) -> Result<InstanceLock, String> {
+    Err("Remote control is only available on platforms with Unix domain sockets.".to_string())
+}
+
+/// Whether an instance holds the lock for `socket_path`, whether or not it is listening.
+#[cfg(unix)]
+pub fn is_instance_running(socket_path: &Path) -> bool {
+    fs::File::open(instance_lock_path(socket_path)).is_ok_and(|file| !try_lock(&file))
+}
+
+#[cfg(not(unix))]
+pub fn is_instance_running(_socket_path: &Path) -> bool {
+    false
+}
+
+/// Why a command got no response.
+#[derive(Debug)]
+pub enum SendError {
+    /// Nothing listens on the socket, e.g. because no instance is running.
+    NotListening(String),
+    Failed(String),
+}
+
+impl fmt::Display for SendError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotListening(message) | Self::Failed(message) => formatter.write_str(message),
+        }
+    }
+}
+
+impl From<String> for SendError {
+    fn from(message: String) -> Self {
+        Self::Failed(message)
+    }
+}
+
+/// Sends one command to the running instance and waits for its response.
+#[cfg(unix)]
+pub fn send_command(
+    socket_path: &Path,
+    command: &RemoteCommand,
+) -> Result<RemoteResponse, SendError> {
+    // A socket in a directory someone else controls may belong to their server.
+    if let Some(directory) = socket_path.parent().filter(|directory| directory.exists()) {
+        verify_socket_directory(directory)?;
+    }
+    let mut stream = UnixStream::connect(socket_path).map_err(|err| {
+        let message = format!(
+            "No running Mudkip instance is listening on '{}': {err}",
+            socket_path.display()
+        );
+        match err.kind() {
+            // A crashed instance leaves its socket behind, refusing connections.
+            ErrorKind::NotFound | ErrorKind::ConnectionRefused => SendError::NotListening(message),
+            _ => SendError::Failed(message),
+        }
+    })?;
+    let waits_for_close = matches!(command, RemoteCommand::OpenAndWait { .. });
+    if !waits_for_close {
+        let _ = stream.set_read_timeout(Some(CLIENT_TIMEOUT));
+    }
+
+    let serialized = serde_json::to_string(command)
+        .map_err(|err| format!("Failed to serialize remote command: {err}"))?;
+    writeln!(stream, "{serialized}")
+        .map_err(|err| format!("Failed to send remote command: {err}"))?;
+
+    let mut line = String::new();
+    let read = BufReader::new(stream)
+        .read_line(&mut line)
+        .map_err(|err| format!("Failed to read remote response: {err}"))?;
+    // The instance exiting closes every document it showed.
+    if read == 0 && waits_for_close {
+        return Ok(RemoteResponse::ok());
+    }
+    serde_json::from_str(line.trim())
+        .map_err(|err| SendError::Failed(format!("Invalid response from running instance: {err}")))
+}
+
+#[cfg(not(unix))]
+pub fn send_command(
+    _socket_path: &Path,
+    _command: &RemoteCommand,
+) -> Result<RemoteResponse, SendError> {
+    Err(SendError::Failed(
+        "Remote control is only available on platforms with Unix domain sockets.".to_string(),
+    ))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::test_support::create_temp_directory;
+
+    /// A socket path in a fresh directory that only the current user can write to.
+    fn unique_socket_path(name: &str) -> PathBuf {
+        let directory = create_temp_directory(name);
+        fs::set_permissions(&directory, fs::Permissions::from_mode(0o700))
+            .expect("should make socket directory private");
+        directory.join(format!("{name}.sock"))
+    }
+
+    #[test]
+    fn remote_command_parses_cli_values() {
+        assert_eq!(
+            RemoteCommand::from_cli("scroll-to-line", Some("80")),
+            Ok(RemoteCommand::ScrollToLine { line: 80 })
+        );
+        assert_eq!(
+            RemoteCommand::from_cli("get-state", None),
+            Ok(RemoteCommand::GetState)
+        );
+        assert!(RemoteCommand::from_cli("scroll-to-line", Some("0")).is_err());
+        assert!(RemoteCommand::from_cli("set-theme", None).is_err());
+        assert!(RemoteCommand::from_cli("quit", None).is_err());
+    }
+
+    #[test]
+    fn server_answers_commands_over_socket() {
+        let socket_path = unique_socket_path("remote");
+
+        spawn_server(socket_path.clone(), |command| match command {
+            RemoteCommand::GetState => RemoteResponse::with_state(ViewerState {
+                file_path: Some("/docs/a.md".to_string()),
+                line: 12,
+                mode: "single-file".to_string(),
+                ..ViewerState::default()
+            }),
+            RemoteCommand::SetTheme { theme } => RemoteResponse::error(format!("bad {theme}")),
+            _ => RemoteResponse::ok(),
+        })
+        .expect("server should start");
+
+        let state = send_command(&socket_path, &RemoteCommand::GetState)
+            .expect("get-state should succeed")
+            .state
+            .expect("state should be returned");
+        assert_eq!(state.file_path.as_deref(), Some("/docs/a.md"));
+        assert_eq!(state.line, 12);
+
+        let reload = send_command(&socket_path, &RemoteCommand::Reload).expect("reload");
+        assert!(reload.ok);
+
+        let failed = send_command(
+            &socket_path,
+            &RemoteCommand::SetTheme {
+                theme: "neon".to_string(),
+            },
+        )
+        .expect("set-theme should get a response");
+        assert_eq!(failed.error.as_deref(), Some("bad neon"));
+
+        let directory = socket_path.parent().expect("socket directory");
+        let mode = fs::metadata(directory).expect("directory metadata").mode();
+        assert_eq!(mode & 0o777, 0o700);
+        assert_eq!(
+            fs::metadata(&socket_path).expect("socket metadata").mode() & 0o777,
+            0o600
+        );
+        let _ = fs::remove_dir_all(directory);
+    }
+
+    #[test]
+    fn sockets_stay_out_of_directories_others_can_write() {
+        let socket_path = unique_socket_path("shared");
+        let directory = socket_path.parent().expect("socket directory");
+        fs::create_dir_all(directory).expect("should create directory");
+        fs::set_permissions(directory, fs::Permissions::from_mode(0o777))
+            .expect("should open up directory");
+
+        assert!(bind_socket(&socket_path).is_err());
+        assert!(send_command(&socket_path, &RemoteCommand::GetState).is_err());
+        assert!(!socket_path.exists());
+
+        let _ = fs::remove_dir_all(directory);
+    }
+
+    #[test]
+    fn instance_lock_tells_a_missing_instance_from_an_unreachable_one() {
+        let socket_path = unique_socket_path("lock");
+        assert!(matches!(
+            send_command(&socket_path, &RemoteCommand::GetState),
+            Err(SendError::NotListening(_))
+        ));
+        assert!(!is_instance_running(&socket_path));
+
+        let lock = acquire_instance_lock(&socket_path).expect("should take the lock");
+        assert!(is_instance_running(&socket_path));
+        assert!(acquire_instance_lock(&socket_path).is_err());
+
+        drop(lock);
+        assert!(!is_instance_running(&socket_path));
+
+        let _ = fs::remove_dir_all(socket_path.parent().expect("socket directory"));
+    }
+}
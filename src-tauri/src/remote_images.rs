@@ -0,0 +1,189 @@
+use std::{collections::HashSet, io::Read, sync::Mutex, time::Duration};
+use tauri::http::{header, Method, Request, Response, StatusCode};
+use url::{form_urlencoded, Url};
+
+pub const REMOTE_IMAGE_SCHEME: &str = "mudkip-remote";
+
+/// Webviews on Windows and Android only route custom schemes through `http://<scheme>.localhost`.
+#[cfg(any(windows, target_os = "android"))]
+const REMOTE_IMAGE_ORIGIN: &str = "http://mudkip-remote.localhost";
+#[cfg(not(any(windows, target_os = "android")))]
+const REMOTE_IMAGE_ORIGIN: &str = "mudkip-remote://localhost";
+
+const MAX_REMOTE_IMAGE_BYTES: u64 = 20 * 1024 * 1024;
+const REMOTE_IMAGE_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Remote images the viewer chose to show: every image of a trusted document, and the ones
+/// the reader clicked to load in a restricted one. The content security policy keeps the
+/// webview itself off the network, so images only load through this proxy and only when
+/// listed here; a document cannot opt itself in.
+#[derive(Default)]
+pub struct RemoteImageAllowance {
+    urls: Mutex<HashSet<String>>,
+}
+
+fn parse_remote_url(value: &str) -> Result<Url, String> {
+    let url = Url::parse(value.trim()).map_err(|err| format!("Invalid image URL: {err}"))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!(
+            "Refusing to load image with scheme '{}'.",
+            url.scheme()
+        ));
+    }
+    Ok(url)
+}
+
+fn proxy_url(url: &Url) -> String {
+    let encoded: String = form_urlencoded::byte_serialize(url.as_str().as_bytes()).collect();
+    format!("{REMOTE_IMAGE_ORIGIN}/?url={encoded}")
+}
+
+impl RemoteImageAllowance {
+    /// Allows `urls` and returns the proxy URL for each, in order.
+    pub fn allow(&self, urls: &[String]) -> Result<Vec<String>, String> {
+        let parsed = urls
+            .iter()
+            .map(|url| parse_remote_url(url))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut allowed = self
+            .urls
+            .lock()
+            .map_err(|_| "Failed to lock remote image allowance.".to_string())?;
+        allowed.extend(parsed.iter().map(|url| url.to_string()));
+        Ok(parsed.iter().map(proxy_url).collect())
+    }
+
+    fn is_allowed(&self, url: &Url) -> bool {
+        self.urls
+            .lock()
+            .map(|allowed| allowed.contains(url.as_str()))
+            .unwrap_or(false)
+    }
+}
+
+fn requested_url(request: &Request<Vec<u8>>) -> Option<Url> {
+    let query = request.uri().query()?;
+    form_urlencoded::parse(query.as_bytes())
+        .find(|(key, _)| key == "url")
+        .and_then(|(_, value)| parse_remote_url(&value).ok())
+}
+
+fn empty_response(status: StatusCode) -> Response<Vec<u8>> {
+    Response::builder()
+        .status(status)
+        .body(Vec::new())
+        .unwrap_or_default()
+}
+
+fn fetch_image(url: &Url) -> Result<(String, Vec<u8>), String> {
+    let response = ureq::AgentBuilder::new()
+        .timeout(REMOTE_IMAGE_TIMEOUT)
+        .build()
+        .get(url.as_str())
+        .call()
+        .map_err(|err| format!("Failed to load image '{url}': {err}"))?;
+
+    let content_type = response.content_type().to_ascii_lowercase();
+    if !content_type.starts_with("image/") {
+        return Err(format!(
+            "'{url}' is not an image (content type '{content_type}')."
+        ));
+    }
+
+    let mut body = Vec::new();
+    response
+        .into_reader()
+        .take(MAX_REMOTE_IMAGE_BYTES + 1)
+        .read_to_end(&mut body)
+        .map_err(|err| format!("Failed to read image '{url}': {err}"))?;
+    if body.len() as u64 > MAX_REMOTE_IMAGE_BYTES {
+        return Err(format!("Image '{url}' is too large."));
+    }
+    Ok((content_type, body))
+}
+
+/// Serves `mudkip-remote://localhost/?url=<image URL>` for allowed images.
+pub fn handle_remote_image_request(
+    allowance: &RemoteImageAllowance,
+    request: &Request<Vec<u8>>,
+) -> Response<Vec<u8>> {
+    if request.method() != Method::GET {
+        return empty_response(StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    let Some(url) = requested_url(request) else {
+        return empty_response(StatusCode::BAD_REQUEST);
+    };
+    if !allowance.is_allowed(&url) {
+        log::warn!("Refusing to load remote image '{url}': not allowed for this document.");
+        return empty_response(StatusCode::FORBIDDEN);
+    }
+
+    match fetch_image(&url) {
+        Ok((content_type, body)) => Response::builder()
+            .header(header::CONTENT_TYPE, content_type)
+            .header(header::CACHE_CONTROL, "max-age=3600")
+            .body(body)
+            .unwrap_or_else(|_| empty_response(StatusCode::INTERNAL_SERVER_ERROR)),
+        Err(err) => {
+            log::warn!("{err}");
+            empty_response(StatusCode::BAD_GATEWAY)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proxy_request(uri: &str) -> Request<Vec<u8>> {
+        Request::builder()
+            .uri(uri)
+            .body(Vec::new())
+            .expect("should build request")
+    }
+
+    #[test]
+    fn allow_returns_proxy_urls_for_web_images_only() {
+        let allowance = RemoteImageAllowance::default();
+        let proxied = allowance
+            .allow(&["https://example.com/a b.png?x=1&y=2".to_string()])
+            .expect("https images should be allowed");
+        assert_eq!(
+            proxied,
+            vec![format!(
+                "{REMOTE_IMAGE_ORIGIN}/?url=https%3A%2F%2Fexample.com%2Fa%2520b.png%3Fx%3D1%26y%3D2"
+            )]
+        );
+
+        let request = proxy_request(&proxied[0]);
+        let url = requested_url(&request).expect("proxy URL should carry the image URL");
+        assert_eq!(url.as_str(), "https://example.com/a%20b.png?x=1&y=2");
+        assert!(allowance.is_allowed(&url));
+
+        assert!(allowance
+            .allow(&["file:///etc/passwd".to_string()])
+            .is_err());
+        assert!(allowance
+            .allow(&["javascript:alert(1)".to_string()])
+            .is_err());
+    }
+
+    #[test]
+    fn handle_remote_image_request_rejects_images_nobody_allowed() {
+        let allowance = RemoteImageAllowance::default();
+        let response = handle_remote_image_request(
+            &allowance,
+            &proxy_request(&format!(
+                "{REMOTE_IMAGE_ORIGIN}/?url=https%3A%2F%2Ftracker.example%2Fpixel.gif"
+            )),
+        );
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let missing = handle_remote_image_request(
+            &allowance,
+            &proxy_request(&format!("{REMOTE_IMAGE_ORIGIN}/")),
+        );
+        assert_eq!(missing.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,148 @@
+use crate::folder_listing::FolderSortMode;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+pub const SETTINGS_FILE_NAME: &str = "settings.json";
+const MAX_RECENT_FILES: usize = 50;
+
+/// Preferences Mudkip persists between launches in the app config directory.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AppSettings {
+    pub trusted_folders: Vec<PathBuf>,
+    /// Keep local snapshots of watched documents; off unless the user turns it on.
+    pub local_history: bool,
+    /// Folder list sort order per opened folder; folders sorted by name are left out.
+    pub folder_sort_modes: BTreeMap<PathBuf, FolderSortMode>,
+    /// Documents the viewer showed, most recent first, for quick open to rank higher.
+    pub recent_files: Vec<PathBuf>,
+}
+
+impl AppSettings {
+    /// Moves `path` to the front of the recent files, keeping the list bounded.
+    pub fn record_recent_file(&mut self, path: PathBuf) {
+        self.recent_files.retain(|recent| *recent != path);
+        self.recent_files.insert(0, path);
+        self.recent_files.truncate(MAX_RECENT_FILES);
+    }
+}
+
+pub struct SettingsStore {
+    path: Option<PathBuf>,
+    settings: Mutex<AppSettings>,
+}
+
+fn read_settings_file(path: &Path) -> AppSettings {
+    let Ok(source) = fs::read_to_string(path) else {
+        return AppSettings::default();
+    };
+
+    serde_json::from_str(&source).unwrap_or_else(|err| {
+        log::warn!("Ignoring invalid settings file '{}': {err}", path.display());
+        AppSettings::default()
+    })
+}
+
+impl SettingsStore {
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let settings = path.as_deref().map(read_settings_file).unwrap_or_default();
+
+        Self {
+            path,
+            settings: Mutex::new(settings),
+        }
+    }
+
+    pub fn snapshot(&self) -> AppSettings {
+        self.settings
+            .lock()
+            .map(|settings| settings.clone())
+            .unwrap_or_default()
+    }
+
+    /// Applies `change` and writes the result to disk.
+    pub fn update<F>(&self, change: F) -> Result<AppSettings, String>
+    where
+        F: FnOnce(&mut AppSettings),
+    {
+        let mut settings = self
+            .settings
+            .lock()
+            .map_err(|_| "Failed to lock settings.".to_string())?;
+        change(&mut settings);
+
+        if let Some(path) = self.path.as_ref() {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(|err| {
+                    format!(
+                        "Failed to create settings folder '{}': {err}",
+                        parent.display()
+                    )
+                })?;
+            }
+
+            let serialized = serde_json::to_string_pretty(&*settings)
+                .map_err(|err| format!("Failed to serialize settings: {err}"))?;
+            fs::write(path, serialized)
+                .map_err(|err| format!("Failed to write settings '{}': {err}", path.display()))?;
+        }
+
+        Ok(settings.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::create_temp_directory;
+
+    #[test]
+    fn settings_store_persists_updates() {
+        let dir = create_temp_directory("settings");
+        let path = dir.join(SETTINGS_FILE_NAME);
+
+        let store = SettingsStore::load(Some(path.clone()));
+        assert_eq!(store.snapshot(), AppSettings::default());
+
+        store
+            .update(|settings| settings.trusted_folders.push(PathBuf::from("/docs")))
+            .expect("settings should save");
+
+        let reloaded = SettingsStore::load(Some(path));
+        assert_eq!(
+            reloaded.snapshot().trusted_folders,
+            vec![PathBuf::from("/docs")]
+        );
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn recent_files_keep_the_latest_first_without_duplicates() {
+        let mut settings = AppSettings::default();
+        for index in 0..MAX_RECENT_FILES + 5 {
+            settings.record_recent_file(PathBuf::from(format!("/docs/{index}.md")));
+        }
+        settings.record_recent_file(PathBuf::from("/docs/10.md"));
+
+        assert_eq!(settings.recent_files.len(), MAX_RECENT_FILES);
+        assert_eq!(settings.recent_files[0], PathBuf::from("/docs/10.md"));
+        assert_eq!(
+            settings.recent_files[1],
+            PathBuf::from(format!("/docs/{}.md", MAX_RECENT_FILES + 4))
+        );
+        assert_eq!(
+            settings
+                .recent_files
+                .iter()
+                .filter(|path| path.as_path() == Path::new("/docs/10.md"))
+                .count(),
+            1
+        );
+    }
+}
@@ -0,0 +1,238 @@
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::Serialize;
+use std::{
+    cmp::Reverse,
+    fs,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+pub const SNAPSHOTS_DIR_NAME: &str = "snapshots";
+const SNAPSHOT_EXTENSION: &str = ".md.gz";
+/// Records which document a snapshot folder belongs to, for people browsing the data dir.
+const SOURCE_FILE_NAME: &str = "source.txt";
+/// Retention per document; the newest snapshot is always kept.
+const MAX_SNAPSHOTS_PER_FILE: usize = 100;
+const MAX_SNAPSHOT_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotEntry {
+    /// Milliseconds since the Unix epoch as a string; doubles as the snapshot's file name.
+    pub id: String,
+    pub timestamp: u64,
+    /// Compressed size on disk in bytes.
+    pub size: u64,
+}
+
+/// Local history of watched documents: gzip-compressed copies in one folder per document
+/// under the app data dir.
+pub struct SnapshotStore {
+    root: Option<PathBuf>,
+}
+
+/// FNV-1a, so the folder name stays the same across builds and platforms.
+fn path_key(path: &Path) -> String {
+    let hash = path
+        .to_string_lossy()
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+        });
+    format!("{hash:016x}")
+}
+
+/// Snapshot ids come from the webview, so only plain timestamps are accepted as file names.
+fn is_snapshot_id(value: &str) -> bool {
+    (1..=20).contains(&value.len()) && value.chars().all(|ch| ch.is_ascii_digit())
+}
+
+fn millis_since_epoch(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+impl SnapshotStore {
+    pub fn new(root: Option<PathBuf>) -> Self {
+        Self { root }
+    }
+
+    fn directory(&self, path: &Path) -> Option<PathBuf> {
+        self.root.as_ref().map(|root| root.join(path_key(path)))
+    }
+
+    /// Snapshots of `path`, newest first.
+    pub fn list(&self, path: &Path) -> Vec<SnapshotEntry> {
+        let Some(entries) = self
+            .directory(path)
+            .and_then(|directory| fs::read_dir(directory).ok())
+        else {
+            return Vec::new();
+        };
+
+        let mut snapshots: Vec<SnapshotEntry> = entries
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let file_name = entry.file_name().to_string_lossy().to_string();
+                let id = file_name.strip_suffix(SNAPSHOT_EXTENSION)?;
+                let timestamp = id.parse().ok().filter(|_| is_snapshot_id(id))?;
+                Some(SnapshotEntry {
+                    id: id.to_string(),
+                    timestamp,
+                    size: entry.metadata().map(|metadata| metadata.len()).unwrap_or(0),
+                })
+            })
+            .collect();
+        snapshots.sort_by_key(|snapshot| Reverse(snapshot.timestamp));
+        snapshots
+    }
+
+    pub fn read(&self, path: &Path, id: &str) -> Result<String, String> {
+        if !is_snapshot_id(id) {
+            return Err(format!("'{id}' is not a snapshot id."));
+        }
+        let directory = self
+            .directory(path)
+            .ok_or_else(|| "Local history is unavailable.".to_string())?;
+        let snapshot_path = directory.join(format!("{id}{SNAPSHOT_EXTENSION}"));
+        let compressed = fs::read(&snapshot_path)
+            .map_err(|err| format!("Failed to read snapshot '{id}': {err}"))?;
+
+        let mut content = String::new();
+        GzDecoder::new(compressed.as_slice())
+            .read_to_string(&mut content)
+            .map_err(|err| format!("Failed to decompress snapshot '{id}': {err}"))?;
+        Ok(content)
+    }
+
+    /// Saves `content` unless it matches the newest snapshot, then applies retention. Returns
+    /// whether a snapshot was written.
+    pub fn record(&self, path: &Path, content: &str) -> Result<bool, String> {
+        self.record_at(path, content, SystemTime::now())
+    }
+
+    fn record_at(&self, path: &Path, content: &str, now: SystemTime) -> Result<bool, String> {
+        let directory = self
+            .directory(path)
+            .ok_or_else(|| "Local history is unavailable.".to_string())?;
+        let newest = self.list(path).into_iter().next();
+        if let Some(newest) = newest.as_ref() {
+            if self.read(path, &newest.id).as_deref() == Ok(content) {
+                return Ok(false);
+            }
+        }
+
+        fs::create_dir_all(&directory).map_err(|err| {
+            format!(
+                "Failed to create snapshot folder '{}': {err}",
+                directory.display()
+            )
+        })?;
+        let source_path = directory.join(SOURCE_FILE_NAME);
+        if !source_path.exists() {
+            let _ = fs::write(&source_path, path.to_string_lossy().as_bytes());
+        }
+
+        // Keep ids unique and ordered even when two versions land within a millisecond.
+        let timestamp = newest
+            .map(|newest| newest.timestamp + 1)
+            .unwrap_or_default()
+            .max(millis_since_epoch(now));
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(content.as_bytes())
+            .and_then(|_| encoder.finish())
+            .and_then(|compressed| {
+                fs::write(
+                    directory.join(format!("{timestamp}{SNAPSHOT_EXTENSION}")),
+                    compressed,
+                )
+            })
+            .map_err(|err| format!("Failed to write snapshot: {err}"))?;
+
+        self.prune(path, now);
+        Ok(true)
+    }
+
+    fn prune(&self, path: &Path, now: SystemTime) {
+        let Some(directory) = self.directory(path) else {
+            return;
+        };
+        let oldest_kept =
+            millis_since_epoch(now.checked_sub(MAX_SNAPSHOT_AGE).unwrap_or(UNIX_EPOCH));
+
+        for (index, snapshot) in self.list(path).into_iter().enumerate() {
+            if index == 0 {
+                continue;
+            }
+            if index >= MAX_SNAPSHOTS_PER_FILE || snapshot.timestamp < oldest_kept {
+                let _ =
+                    fs::remove_file(directory.join(format!("{}{SNAPSHOT_EXTENSION}", snapshot.id)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::create_temp_directory;
+
+    #[test]
+    fn record_skips_unchanged_content_and_lists_newest_first() {
+        let root = create_temp_directory("snapshots");
+        let store = SnapshotStore::new(Some(root.clone()));
+        let document = Path::new("/notes/meeting.md");
+        let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        assert_eq!(store.record_at(document, "# One\n", start), Ok(true));
+        assert_eq!(store.record_at(document, "# One\n", start), Ok(false));
+        assert_eq!(store.record_at(document, "# Two\n", start), Ok(true));
+
+        let snapshots = store.list(document);
+        assert_eq!(snapshots.len(), 2);
+        assert!(snapshots[0].timestamp > snapshots[1].timestamp);
+        assert_eq!(
+            store.read(document, &snapshots[0].id),
+            Ok("# Two\n".to_string())
+        );
+        assert_eq!(
+            store.read(document, &snapshots[1].id),
+            Ok("# One\n".to_string())
+        );
+        assert!(store.read(document, "../settings").is_err());
+        assert!(store.list(Path::new("/notes/other.md")).is_empty());
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn record_prunes_old_and_excess_snapshots() {
+        let root = create_temp_directory("snapshots");
+        let store = SnapshotStore::new(Some(root.clone()));
+        let document = Path::new("/reports/daily.md");
+        let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        for version in 0..MAX_SNAPSHOTS_PER_FILE + 5 {
+            store
+                .record_at(document, &format!("version {version}"), start)
+                .expect("snapshot should be written");
+        }
+        assert_eq!(store.list(document).len(), MAX_SNAPSHOTS_PER_FILE);
+
+        let later = start + MAX_SNAPSHOT_AGE + Duration::from_secs(60);
+        store
+            .record_at(document, "fresh", later)
+            .expect("snapshot should be written");
+        let snapshots = store.list(document);
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(
+            store.read(document, &snapshots[0].id),
+            Ok("fresh".to_string())
+        );
+
+        let _ = fs::remove_dir_all(root);
+    }
+}
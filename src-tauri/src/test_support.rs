@@ -0,0 +1,37 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// `mudkip-<stem>-<unique suffix>[.<extension>]` in the system temp directory.
+fn unique_temp_path(name: &str) -> PathBuf {
+    let unique_suffix = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system clock should be after unix epoch")
+        .as_nanos();
+    let name = Path::new(name);
+    let stem = name.file_stem().unwrap_or_default().to_string_lossy();
+    let mut file_name = format!("mudkip-{stem}-{unique_suffix}");
+    if let Some(extension) = name.extension() {
+        file_name.push('.');
+        file_name.push_str(&extension.to_string_lossy());
+    }
+    env::temp_dir().join(file_name)
+}
+
+/// Creates an empty, uniquely named directory under the system temp directory and returns
+/// its canonical path.
+pub fn create_temp_directory(name: &str) -> PathBuf {
+    let path = unique_temp_path(name);
+    fs::create_dir_all(&path).expect("should create temp directory");
+    fs::canonicalize(path).expect("temp directory should canonicalize")
+}
+
+/// Writes `content` to a uniquely named file under the system temp directory, keeping the
+/// extension of `name`, and returns its path.
+pub fn create_temp_file(name: &str, content: &str) -> PathBuf {
+    let path = unique_temp_path(name);
+    fs::write(&path, content).expect("should create temp file");
+    path
+}
@@ -0,0 +1,163 @@
+use crate::front_matter::front_matter_values;
+use serde::Serialize;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+pub const USER_STYLESHEET_FILE_NAME: &str = "style.css";
+const FOLDER_STYLESHEET_DIR: &str = ".mudkip";
+const FRONT_MATTER_CSS_KEY: &str = "css";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StylesheetSource {
+    Global,
+    Folder,
+    Document,
+}
+
+impl StylesheetSource {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Global => "global",
+            Self::Folder => "folder",
+            Self::Document => "document",
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserStylesheetPayload {
+    source: String,
+    path: String,
+    css: String,
+}
+
+fn is_css_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("css"))
+}
+
+/// Lists `.mudkip/style.css` files from the filesystem root down to `start_dir`, so styles
+/// closer to the document are applied last and win.
+fn find_folder_stylesheets(start_dir: &Path) -> Vec<PathBuf> {
+    let mut stylesheets: Vec<PathBuf> = start_dir
+        .ancestors()
+        .map(|dir| {
+            dir.join(FOLDER_STYLESHEET_DIR)
+                .join(USER_STYLESHEET_FILE_NAME)
+        })
+        .filter(|path| path.is_file())
+        .collect();
+    stylesheets.reverse();
+    stylesheets
+}
+
+/// Resolves every stylesheet that applies to a document, in cascade order: the global user
+/// stylesheet, folder stylesheets, then stylesheets listed in the document's `css:` front matter.
+/// The global path is included even when missing so callers can watch for its creation.
+pub fn resolve_stylesheet_paths(
+    global_stylesheet: Option<&Path>,
+    document_path: &Path,
+    content: &str,
+) -> Vec<(StylesheetSource, PathBuf)> {
+    let mut resolved: Vec<(StylesheetSource, PathBuf)> = Vec::new();
+
+    if let Some(global_stylesheet) = global_stylesheet {
+        resolved.push((StylesheetSource::Global, global_stylesheet.to_path_buf()));
+    }
+
+    let Some(document_dir) = document_path.parent() else {
+        return resolved;
+    };
+
+    for stylesheet in find_folder_stylesheets(document_dir) {
+        resolved.push((StylesheetSource::Folder, stylesheet));
+    }
+
+    for entry in front_matter_values(content, FRONT_MATTER_CSS_KEY) {
+        let stylesheet = document_dir.join(&entry);
+        if !is_css_path(&stylesheet) {
+            log::warn!(
+                "Ignoring front-matter css entry '{}': not a .css file.",
+                entry
+            );
+            continue;
+        }
+        resolved.push((StylesheetSource::Document, stylesheet));
+    }
+
+    let mut seen = Vec::new();
+    resolved.retain(|(_, path)| {
+        let key = fs::canonicalize(path).unwrap_or_else(|_| path.clone());
+        if seen.contains(&key) {
+            return false;
+        }
+        seen.push(key);
+        true
+    });
+
+    resolved
+}
+
+pub fn load_stylesheets(paths: &[(StylesheetSource, PathBuf)]) -> Vec<UserStylesheetPayload> {
+    paths
+        .iter()
+        .filter_map(|(source, path)| match fs::read_to_string(path) {
+            Ok(css) => Some(UserStylesheetPayload {
+                source: source.as_str().to_string(),
+                path: path.to_string_lossy().to_string(),
+                css,
+            }),
+            Err(err) => {
+                if err.kind() != io::ErrorKind::NotFound {
+                    log::warn!("Failed to read stylesheet '{}': {err}", path.display());
+                }
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::create_temp_directory;
+
+    #[test]
+    fn resolve_stylesheet_paths_orders_global_folder_then_document() {
+        let root = create_temp_directory("user-styles");
+        let docs_dir = root.join("docs");
+        fs::create_dir_all(root.join(FOLDER_STYLESHEET_DIR)).expect("should create root styles");
+        fs::create_dir_all(docs_dir.join(FOLDER_STYLESHEET_DIR))
+            .expect("should create docs styles");
+        fs::write(root.join(".mudkip/style.css"), "body{}").expect("should write root css");
+        fs::write(docs_dir.join(".mudkip/style.css"), "p{}").expect("should write docs css");
+
+        let document = docs_dir.join("guide.md");
+        let global = root.join("global.css");
+        let resolved = resolve_stylesheet_paths(
+            Some(&global),
+            &document,
+            "---\ncss: [wide.css, notes.txt]\n---\n# Guide\n",
+        );
+
+        assert_eq!(
+            resolved,
+            vec![
+                (StylesheetSource::Global, global),
+                (StylesheetSource::Folder, root.join(".mudkip/style.css")),
+                (StylesheetSource::Folder, docs_dir.join(".mudkip/style.css")),
+                (StylesheetSource::Document, docs_dir.join("wide.css")),
+            ]
+        );
+
+        let loaded = load_stylesheets(&resolved);
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[1].css, "p{}");
+
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,824 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+const MAX_THEME_INCLUDE_DEPTH: usize = 8;
+
+/// Maps highlight.js token classes to the TextMate scopes VS Code themes color.
+/// Candidates are tried in order; the first scope the theme styles wins.
+const HLJS_SCOPE_MAP: &[(&str, &[&str])] = &[
+    (".hljs-comment, .hljs-quote", &["comment"]),
+    (
+        ".hljs-doctag",
+        &["storage.type.class.jsdoc", "comment.block.documentation"],
+    ),
+    (".hljs-keyword", &["keyword.control", "keyword", "storage"]),
+    (".hljs-operator", &["keyword.operator"]),
+    (".hljs-literal", &["constant.language"]),
+    (".hljs-number", &["constant.numeric"]),
+    (".hljs-symbol", &["constant.other.symbol", "constant"]),
+    (".hljs-string", &["string"]),
+    (".hljs-regexp", &["string.regexp"]),
+    (
+        ".hljs-template-tag, .hljs-subst",
+        &[
+            "punctuation.definition.template-expression",
+            "meta.embedded",
+        ],
+    ),
+    (
+        ".hljs-variable, .hljs-template-variable",
+        &["variable.other", "variable"],
+    ),
+    (".hljs-params", &["variable.parameter", "variable"]),
+    (
+        ".hljs-title, .hljs-title.function_",
+        &["entity.name.function", "support.function"],
+    ),
+    (
+        ".hljs-title.class_, .hljs-class",
+        &[
+            "entity.name.type.class",
+            "entity.name.type",
+            "entity.name.class",
+        ],
+    ),
+    (".hljs-type", &["entity.name.type", "support.type"]),
+    (
+        ".hljs-built_in, .hljs-builtin-name",
+        &["support.function", "support.class", "support.type"],
+    ),
+    (
+        ".hljs-attr, .hljs-attribute, .hljs-property",
+        &["support.type.property-name", "entity.other.attribute-name"],
+    ),
+    (".hljs-name, .hljs-selector-tag", &["entity.name.tag"]),
+    (".hljs-tag", &["punctuation.definition.tag"]),
+    (".hljs-selector-id", &["entity.other.attribute-name.id"]),
+    (
+        ".hljs-selector-class",
+        &["entity.other.attribute-name.class"],
+    ),
+    (
+        ".hljs-selector-attr, .hljs-selector-pseudo",
+        &[
+            "entity.other.attribute-name.pseudo-class",
+            "entity.other.attribute-name",
+        ],
+    ),
+    (
+        ".hljs-meta, .hljs-meta-keyword",
+        &["meta.preprocessor", "keyword.control.directive"],
+    ),
+    (".hljs-section", &["markup.heading", "entity.name.section"]),
+    (
+        ".hljs-bullet",
+        &["punctuation.definition.list.begin.markdown", "markup.list"],
+    ),
+    (".hljs-emphasis", &["markup.italic"]),
+    (".hljs-strong", &["markup.bold"]),
+    (".hljs-link", &["markup.underline.link"]),
+    (".hljs-code", &["markup.inline.raw", "markup.raw"]),
+    (".hljs-addition", &["markup.inserted"]),
+    (".hljs-deletion", &["markup.deleted"]),
+];
+
+#[derive(Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VsCodeAppearancePayload {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub theme_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub theme_kind: Option<String>,
+    pub theme_css: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub font_family: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub font_size: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_height: Option<f64>,
+    pub stylesheets: Vec<String>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct VsCodeSettings {
+    color_theme: Option<String>,
+    font_family: Option<String>,
+    font_size: Option<f64>,
+    line_height: Option<f64>,
+    styles: Vec<String>,
+}
+
+struct ThemeContribution {
+    names: Vec<String>,
+    ui_theme: Option<String>,
+    path: PathBuf,
+}
+
+#[derive(Default)]
+struct LoadedTheme {
+    theme_type: Option<String>,
+    colors: Map<String, Value>,
+    token_rules: Vec<TokenRule>,
+}
+
+#[derive(Debug)]
+struct TokenRule {
+    scopes: Vec<String>,
+    foreground: Option<String>,
+    font_style: Option<String>,
+}
+
+pub fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+}
+
+fn vscode_user_dirs() -> Vec<PathBuf> {
+    let product_dirs = ["Code", "Code - OSS", "VSCodium"];
+    let mut config_roots = Vec::new();
+
+    #[cfg(target_os = "macos")]
+    if let Some(home) = home_dir() {
+        config_roots.push(home.join("Library").join("Application Support"));
+    }
+
+    #[cfg(target_os = "windows")]
+    if let Some(app_data) = env::var_os("APPDATA") {
+        config_roots.push(PathBuf::from(app_data));
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    {
+        if let Some(config_home) = env::var_os("XDG_CONFIG_HOME").filter(|value| !value.is_empty())
+        {
+            config_roots.push(PathBuf::from(config_home));
+        } else if let Some(home) = home_dir() {
+            config_roots.push(home.join(".config"));
+        }
+    }
+
+    config_roots
+        .iter()
+        .flat_map(|root| {
+            product_dirs
+                .iter()
+                .map(move |product| root.join(product).join("User"))
+        })
+        .collect()
+}
+
+fn vscode_extension_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+
+    if let Some(home) = home_dir() {
+        dirs.push(home.join(".vscode").join("extensions"));
+        dirs.push(home.join(".vscode-oss").join("extensions"));
+    }
+
+    // Built-in themes (Dark+, Light+, Modern, ...) ship inside the VS Code install.
+    #[cfg(target_os = "macos")]
+    dirs.push(PathBuf::from(
+        "/Applications/Visual Studio Code.app/Contents/Resources/app/extensions",
+    ));
+
+    #[cfg(target_os = "windows")]
+    if let Some(local_app_data) = env::var_os("LOCALAPPDATA") {
+        dirs.push(
+            PathBuf::from(local_app_data)
+                .join("Programs")
+                .join("Microsoft VS Code")
+                .join("resources")
+                .join("app")
+                .join("extensions"),
+        );
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    {
+        dirs.push(PathBuf::from("/usr/share/code/resources/app/extensions"));
+        dirs.push(PathBuf::from(
+            "/opt/visual-studio-code/resources/app/extensions",
+        ));
+        dirs.push(PathBuf::from("/usr/lib/code/extensions"));
+        dirs.push(PathBuf::from(
+            "/snap/code/current/usr/share/code/resources/app/extensions",
+        ));
+    }
+
+    dirs
+}
+
+/// Strips `//` and `/* */` comments so VS Code's JSONC files parse as JSON.
+fn strip_jsonc_comments(source: &str) -> String {
+    let chars: Vec<char> = source.chars().collect();
+    let mut output = String::with_capacity(source.len());
+    let mut index = 0usize;
+    let mut in_string = false;
+
+    while index < chars.len() {
+        let current = chars[index];
+        let next = chars.get(index + 1).copied();
+
+        if in_string {
+            output.push(current);
+            if current == '\\' {
+                if let Some(escaped) = next {
+                    output.push(escaped);
+                    index += 1;
+                }
+            } else if current == '"' {
+                in_string = false;
+            }
+            index += 1;
+            continue;
+        }
+
+        match (current, next) {
+            ('/', Some('/')) => {
+                while index < chars.len() && chars[index] != '\n' {
+                    index += 1;
+                }
+            }
+            ('/', Some('*')) => {
+                index += 2;
+                while index < chars.len()
+                    && !(chars[index] == '*' && chars.get(index + 1) == Some(&'/'))
+                {
+                    index += 1;
+                }
+                index += 2;
+            }
+            _ => {
+                in_string = current == '"';
+                output.push(current);
+                index += 1;
+            }
+        }
+    }
+
+    output
+}
+
+/// Drops commas that directly precede a closing brace or bracket.
+fn strip_trailing_commas(source: &str) -> String {
+    let chars: Vec<char> = source.chars().collect();
+    let mut output = String::with_capacity(source.len());
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (index, &current) in chars.iter().enumerate() {
+        if in_string {
+            in_string = escaped || current != '"';
+            escaped = !escaped && current == '\\';
+            output.push(current);
+            continue;
+        }
+
+        if current == ',' {
+            let next_significant = chars[index + 1..].iter().find(|ch| !ch.is_whitespace());
+            if matches!(next_significant, Some('}') | Some(']')) {
+                continue;
+            }
+        }
+
+        in_string = current == '"';
+        output.push(current);
+    }
+
+    output
+}
+
+fn strip_jsonc(source: &str) -> String {
+    strip_trailing_commas(&strip_jsonc_comments(source))
+}
+
+pub fn parse_jsonc(source: &str) -> Result<Value, String> {
+    let source = source.trim_start_matches('\u{feff}');
+    serde_json::from_str(&strip_jsonc(source)).map_err(|err| format!("Invalid JSON: {err}"))
+}
+
+fn parse_settings(source: &str) -> Result<VsCodeSettings, String> {
+    let value = parse_jsonc(source)?;
+    let Some(object) = value.as_object() else {
+        return Err("VS Code settings are not a JSON object.".to_string());
+    };
+
+    let string_setting = |key: &str| {
+        object
+            .get(key)
+            .and_then(Value::as_str)
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+    };
+    let number_setting = |key: &str| {
+        object
+            .get(key)
+            .and_then(Value::as_f64)
+            .filter(|value| value.is_finite() && *value > 0.0)
+    };
+
+    let styles = object
+        .get("markdown.styles")
+        .and_then(Value::as_array)
+        .map(|entries| {
+            entries
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(VsCodeSettings {
+        color_theme: string_setting("workbench.colorTheme"),
+        font_family: string_setting("markdown.preview.fontFamily"),
+        font_size: number_setting("markdown.preview.fontSize"),
+        line_height: number_setting("markdown.preview.lineHeight"),
+        styles,
+    })
+}
+
+fn read_settings() -> Option<VsCodeSettings> {
+    for user_dir in vscode_user_dirs() {
+        let settings_path = user_dir.join("settings.json");
+        let Ok(source) = fs::read_to_string(&settings_path) else {
+            continue;
+        };
+
+        match parse_settings(&source) {
+            Ok(settings) => return Some(settings),
+            Err(err) => {
+                log::warn!(
+                    "Ignoring VS Code settings '{}': {err}",
+                    settings_path.display()
+                );
+            }
+        }
+    }
+
+    None
+}
+
+fn theme_contributions(extension_dir: &Path) -> Vec<ThemeContribution> {
+    let Ok(source) = fs::read_to_string(extension_dir.join("package.json")) else {
+        return Vec::new();
+    };
+    let Ok(manifest) = parse_jsonc(&source) else {
+        return Vec::new();
+    };
+
+    let Some(themes) = manifest
+        .pointer("/contributes/themes")
+        .and_then(Value::as_array)
+    else {
+        return Vec::new();
+    };
+
+    themes
+        .iter()
+        .filter_map(|theme| {
+            let path = theme.get("path").and_then(Value::as_str)?;
+            // Built-in themes are selected by id; extensions without an id by label.
+            let names: Vec<String> = ["id", "label"]
+                .iter()
+                .filter_map(|key| theme.get(*key).and_then(Value::as_str))
+                .map(str::to_string)
+                .collect();
+            if names.is_empty() {
+                return None;
+            }
+
+            Some(ThemeContribution {
+                names,
+                ui_theme: theme
+                    .get("uiTheme")
+                    .and_then(Value::as_str)
+                    .map(str::to_string),
+                path: extension_dir.join(path),
+            })
+        })
+        .collect()
+}
+
+fn find_theme_contribution(
+    theme_name: &str,
+    extension_roots: &[PathBuf],
+) -> Option<ThemeContribution> {
+    for root in extension_roots {
+        let Ok(entries) = fs::read_dir(root) else {
+            continue;
+        };
+
+        let mut extension_dirs: Vec<PathBuf> = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir())
+            .collect();
+        extension_dirs.sort();
+
+        for extension_dir in extension_dirs {
+            if let Some(contribution) = theme_contributions(&extension_dir)
+                .into_iter()
+                .find(|contribution| contribution.names.iter().any(|name| name == theme_name))
+            {
+                return Some(contribution);
+            }
+        }
+    }
+
+    None
+}
+
+fn string_or_list(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(scopes) => scopes
+            .split(',')
+            .map(str::trim)
+            .filter(|scope| !scope.is_empty())
+            .map(str::to_string)
+            .collect(),
+        Value::Array(entries) => entries
+            .iter()
+            .filter_map(Value::as_str)
+            .map(str::trim)
+            .filter(|scope| !scope.is_empty())
+            .map(str::to_string)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn parse_token_rule(value: &Value) -> Option<TokenRule> {
+    let settings = value.get("settings")?;
+    let scopes = value.get("scope").map(string_or_list).unwrap_or_default();
+
+    Some(TokenRule {
+        scopes,
+        foreground: settings
+            .get("foreground")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        font_style: settings
+            .get("fontStyle")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+    })
+}
+
+fn load_theme_file(path: &Path, depth: usize, theme: &mut LoadedTheme) -> Result<(), String> {
+    if depth > MAX_THEME_INCLUDE_DEPTH {
+        return Err(format!(
+            "Theme include chain is too deep at '{}'.",
+            path.display()
+        ));
+    }
+
+    let source = fs::read_to_string(path)
+        .map_err(|err| format!("Failed to read theme '{}': {err}", path.display()))?;
+    let value = parse_jsonc(&source)
+        .map_err(|err| format!("Failed to parse theme '{}': {err}", path.display()))?;
+
+    if let Some(include) = value.get("include").and_then(Value::as_str) {
+        let parent = path.parent().unwrap_or_else(|| Path::new("."));
+        load_theme_file(&parent.join(include), depth + 1, theme)?;
+    }
+
+    if let Some(theme_type) = value.get("type").and_then(Value::as_str) {
+        theme.theme_type = Some(theme_type.to_string());
+    }
+
+    if let Some(colors) = value.get("colors").and_then(Value::as_object) {
+        for (key, color) in colors {
+            theme.colors.insert(key.clone(), color.clone());
+        }
+    }
+
+    match value.get("tokenColors") {
+        Some(Value::Array(rules)) => {
+            theme
+                .token_rules
+                .extend(rules.iter().filter_map(parse_token_rule));
+        }
+        Some(Value::String(tm_theme)) => {
+            log::warn!(
+                "Skipping TextMate token colors '{}' referenced by '{}'.",
+                tm_theme,
+                path.display()
+            );
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+fn theme_kind(ui_theme: Option<&str>, theme_type: Option<&str>) -> &'static str {
+    match ui_theme.or(theme_type) {
+        Some("vs") | Some("hc-light") | Some("light") => "vscode-light",
+        _ => "vscode-dark",
+    }
+}
+
+fn is_safe_css_color(value: &str) -> bool {
+    let Some(hex) = value.strip_prefix('#') else {
+        return false;
+    };
+    matches!(hex.len(), 3 | 4 | 6 | 8) && hex.chars().all(|ch| ch.is_ascii_hexdigit())
+}
+
+fn scope_matches(rule_scope: &str, token_scope: &str) -> bool {
+    token_scope == rule_scope
+        || token_scope
+            .strip_prefix(rule_scope)
+            .is_some_and(|rest| rest.starts_with('.'))
+}
+
+/// Resolves foreground and font style independently, like VS Code does: the most specific
+/// matching selector wins, and later rules win ties.
+fn resolve_token_style(rules: &[TokenRule], token_scope: &str) -> (Option<String>, Option<String>) {
+    let mut foreground: Option<(usize, String)> = None;
+    let mut font_style: Option<(usize, String)> = None;
+
+    for rule in rules {
+        for scope in &rule.scopes {
+            // Descendant selectors need scope context that highlight.js does not provide.
+            if scope.contains(' ') || !scope_matches(scope, token_scope) {
+                continue;
+            }
+
+            let specificity = scope.split('.').count();
+            if let Some(color) = rule
+                .foreground
+                .as_ref()
+                .filter(|color| is_safe_css_color(color))
+            {
+                if foreground
+                    .as_ref()
+                    .map_or(true, |(best, _)| specificity >= *best)
+                {
+                    foreground = Some((specificity, color.clone()));
+                }
+            }
+            if let Some(style) = rule.font_style.as_ref() {
+                if font_style
+                    .as_ref()
+                    .map_or(true, |(best, _)| specificity >= *best)
+                {
+                    font_style = Some((specificity, style.clone()));
+                }
+            }
+        }
+    }
+
+    (
+        foreground.map(|(_, color)| color),
+        font_style.map(|(_, style)| style),
+    )
+}
+
+fn font_style_declarations(font_style: &str) -> String {
+    let words: Vec<&str> = font_style.split_whitespace().collect();
+    let mut declarations = vec![
+        format!(
+            "font-style: {};",
+            if words.contains(&"italic") {
+                "italic"
+            } else {
+                "normal"
+            }
+        ),
+        format!(
+            "font-weight: {};",
+            if words.contains(&"bold") {
+                "bold"
+            } else {
+                "normal"
+            }
+        ),
+    ];
+
+    let decorations: Vec<&str> = words
+        .iter()
+        .filter_map(|word| match *word {
+            "underline" => Some("underline"),
+            "strikethrough" => Some("line-through"),
+            _ => None,
+        })
+        .collect();
+    declarations.push(format!(
+        "text-decoration: {};",
+        if decorations.is_empty() {
+            "none".to_string()
+        } else {
+            decorations.join(" ")
+        }
+    ));
+
+    declarations.join(" ")
+}
+
+fn theme_to_css(theme: &LoadedTheme) -> String {
+    let mut css = String::from("body.vscode-user {\n");
+
+    for (key, color) in &theme.colors {
+        let Some(color) = color.as_str().filter(|color| is_safe_css_color(color)) else {
+            continue;
+        };
+        if !key
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '.')
+        {
+            continue;
+        }
+        css.push_str(&format!("  --vscode-{}: {color};\n", key.replace('.', "-")));
+    }
+    css.push_str("}\n");
+
+    // Rules without a scope carry the editor's default foreground.
+    if let Some(default_foreground) = theme
+        .token_rules
+        .iter()
+        .filter(|rule| rule.scopes.is_empty())
+        .filter_map(|rule| rule.foreground.as_ref())
+        .rfind(|color| is_safe_css_color(color))
+    {
+        css.push_str(&format!(
+            "body.vscode-user .hljs {{ color: {default_foreground}; }}\n"
+        ));
+    }
+
+    for (selectors, candidate_scopes) in HLJS_SCOPE_MAP {
+        let Some((foreground, font_style)) = candidate_scopes
+            .iter()
+            .map(|scope| resolve_token_style(&theme.token_rules, scope))
+            .find(|(foreground, font_style)| foreground.is_some() || font_style.is_some())
+        else {
+            continue;
+        };
+
+        let mut declarations = Vec::new();
+        if let Some(color) = foreground {
+            declarations.push(format!("color: {color};"));
+        }
+        if let Some(style) = font_style {
+            declarations.push(font_style_declarations(&style));
+        }
+
+        let scoped_selectors = selectors
+            .split(',')
+            .map(|selector| format!("body.vscode-user {}", selector.trim()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        css.push_str(&format!(
+            "{scoped_selectors} {{ {} }}\n",
+            declarations.join(" ")
+        ));
+    }
+
+    css
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix("~/").zip(home_dir()) {
+        Some((rest, home)) => home.join(rest),
+        None => PathBuf::from(path),
+    }
+}
+
+fn load_markdown_styles(styles: &[String], payload: &mut VsCodeAppearancePayload) {
+    for style in styles {
+        if style.starts_with("https://") || style.starts_with("http://") {
+            log::info!(
+                "Skipping remote markdown.styles entry '{style}': the preview does not load stylesheets from the web."
+            );
+            continue;
+        }
+
+        let style_path = expand_home(style);
+        if !style_path.is_absolute() {
+            log::warn!(
+                "Skipping workspace-relative markdown.styles entry '{}'.",
+                style
+            );
+            continue;
+        }
+
+        match fs::read_to_string(&style_path) {
+            Ok(css) => payload.stylesheets.push(css),
+            Err(err) => log::warn!(
+                "Failed to read markdown.styles entry '{}': {err}",
+                style_path.display()
+            ),
+        }
+    }
+}
+
+/// Reads the user's VS Code settings and active color theme and translates them for the preview.
+/// Returns `None` when no VS Code settings can be found.
+pub fn load_vscode_appearance() -> Option<VsCodeAppearancePayload> {
+    let settings = read_settings()?;
+    let mut payload = VsCodeAppearancePayload {
+        font_family: settings.font_family.clone(),
+        font_size: settings.font_size,
+        line_height: settings.line_height,
+        ..Default::default()
+    };
+
+    load_markdown_styles(&settings.styles, &mut payload);
+
+    let Some(theme_name) = settings.color_theme else {
+        return Some(payload);
+    };
+
+    let Some(contribution) = find_theme_contribution(&theme_name, &vscode_extension_dirs()) else {
+        log::warn!("Unable to locate VS Code color theme '{}'.", theme_name);
+        return Some(payload);
+    };
+
+    let mut theme = LoadedTheme::default();
+    if let Err(err) = load_theme_file(&contribution.path, 0, &mut theme) {
+        log::warn!("{err}");
+        return Some(payload);
+    }
+
+    payload.theme_kind = Some(
+        theme_kind(
+            contribution.ui_theme.as_deref(),
+            theme.theme_type.as_deref(),
+        )
+        .to_string(),
+    );
+    payload.theme_css = theme_to_css(&theme);
+    payload.theme_name = Some(theme_name);
+
+    Some(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_settings_accepts_comments_and_trailing_commas() {
+        let settings = parse_settings(
+            r#"{
+                // Editor look
+                "workbench.colorTheme": "Monokai",
+                /* preview */ "markdown.preview.fontSize": 16,
+                "markdown.preview.fontFamily": "Georgia, serif",
+                "markdown.styles": ["~/preview.css", "https://example.com/a.css",],
+                "files.exclude": { "**/.git": true, },
+            }"#,
+        )
+        .expect("settings should parse");
+
+        assert_eq!(settings.color_theme.as_deref(), Some("Monokai"));
+        assert_eq!(settings.font_size, Some(16.0));
+        assert_eq!(settings.font_family.as_deref(), Some("Georgia, serif"));
+        assert_eq!(settings.line_height, None);
+        assert_eq!(settings.styles.len(), 2);
+    }
+
+    #[test]
+    fn strip_jsonc_keeps_comment_markers_inside_strings() {
+        let stripped = strip_jsonc(r#"{"url": "https://example.com/*x*/", "q": "a\"//b"}"#);
+        let value: Value = serde_json::from_str(&stripped).expect("should parse");
+        assert_eq!(value["url"], "https://example.com/*x*/");
+        assert_eq!(value["q"], "a\"//b");
+    }
+
+    #[test]
+    fn theme_to_css_prefers_most_specific_scope() {
+        let theme = LoadedTheme {
+            theme_type: Some("dark".to_string()),
+            colors: serde_json::from_str(r##"{"editor.background": "#101010", "bad": "red;}"}"##)
+                .expect("colors should parse"),
+            token_rules: vec![
+                TokenRule {
+                    scopes: vec!["keyword".to_string()],
+                    foreground: Some("#111111".to_string()),
+                    font_style: None,
+                },
+                TokenRule {
+                    scopes: vec!["keyword.control".to_string()],
+                    foreground: Some("#222222".to_string()),
+                    font_style: Some("italic".to_string()),
+                },
+            ],
+        };
+
+        let css = theme_to_css(&theme);
+        assert!(css.contains("--vscode-editor-background: #101010;"));
+        assert!(!css.contains("red;}"));
+        assert!(
+            css.contains("body.vscode-user .hljs-keyword { color: #222222; font-style: italic;")
+        );
+    }
+}
@@ -42,6 +42,48 @@ window.markdownViewerDesktop = {
   readMarkdownFolder(folderPath) {
     return invoke("read_markdown_folder", { path: folderPath });
   },
+  setFolderSortMode(folderPath, sortMode) {
+    return invoke("set_folder_sort_mode", { path: folderPath, sortMode });
+  },
+  quickOpenSearch(folderPath, query, refresh) {
+    return invoke("quick_open_search", { folder: folderPath, query, refresh });
+  },
+  getGitFileHistory(filePath) {
+    return invoke("git_file_history", { path: filePath });
+  },
+  getGitBlame(filePath) {
+    return invoke("git_blame", { path: filePath });
+  },
+  readGitFileRevision(filePath, commit) {
+    return invoke("git_read_file_revision", { path: filePath, commit });
+  },
+  readGitFileDiff(filePath, revision) {
+    return invoke("git_read_file_diff", { path: filePath, revision });
+  },
+  getLocalHistory(filePath) {
+    return invoke("snapshot_list", { path: filePath });
+  },
+  setLocalHistoryEnabled(enabled) {
+    return invoke("snapshot_set_enabled", { enabled });
+  },
+  readSnapshot(filePath, id) {
+    return invoke("snapshot_read_file", { path: filePath, id });
+  },
+  readSnapshotDiff(filePath, id) {
+    return invoke("snapshot_read_file_diff", { path: filePath, id });
+  },
+  trustFolder(path) {
+    return invoke("trust_add_folder", { path });
+  },
+  followLink(documentPath, targetPath) {
+    return invoke("follow_link", { document: documentPath, target: targetPath });
+  },
+  allowRemoteImages(urls) {
+    return invoke("remote_images_allow", { urls });
+  },
+  openWithSystemHandler(target) {
+    return invoke("open_with_system_handler", { target });
+  },
   openInVSCodeAtLine(filePath, line) {
     return invoke("open_in_vscode", { path: filePath, line });
   },
@@ -51,6 +93,18 @@ window.markdownViewerDesktop = {
   stopAutoRefreshWatch() {
     return invoke("filewatch_stop");
   },
+  startCompareWatch(filePath) {
+    return invoke("comparewatch_start", { path: filePath });
+  },
+  stopCompareWatch() {
+    return invoke("comparewatch_stop");
+  },
+  startStylesheetWatch(filePath) {
+    return invoke("stylewatch_start", { path: filePath });
+  },
+  stopStylesheetWatch() {
+    return invoke("stylewatch_stop");
+  },
   startFolderWatch(folderPath) {
     return invoke("folderwatch_start", { path: folderPath });
   },
@@ -60,9 +114,15 @@ window.markdownViewerDesktop = {
   getSystemTheme() {
     return invoke("theme_get_system");
   },
+  getVSCodeAppearance() {
+    return invoke("vscode_get_appearance");
+  },
   getStartupOptions() {
     return invoke("app_get_startup_options");
   },
+  reportViewerState(viewerState) {
+    return invoke("app_report_viewer_state", { viewerState });
+  },
   consumePendingExternalOpenTarget() {
     return invoke("file_consume_pending_opened_target");
   },
@@ -72,13 +132,37 @@ window.markdownViewerDesktop = {
   onExternalFileOpen(callback) {
     return addEventListener("file:opened-external", callback);
   },
+  onDropHover(callback) {
+    return addEventListener("drop:hover", callback);
+  },
+  onDropHoverEnd(callback) {
+    return addEventListener("drop:hover-end", callback);
+  },
+  onDropRejected(callback) {
+    return addEventListener("drop:rejected", callback);
+  },
   onFileChanged(callback) {
     return addEventListener("file:changed", callback);
   },
+  onCompareFileChanged(callback) {
+    return addEventListener("compare:changed", callback);
+  },
+  onStylesheetsChanged(callback) {
+    return addEventListener("file:stylesheets-changed", callback);
+  },
   onFolderChanged(callback) {
     return addEventListener("folder:changed", callback);
   },
   onStartupOptions(callback) {
     return addEventListener("app:startup-options", callback);
   },
+  onSystemThemeChanged(callback) {
+    return addEventListener("app:system-theme-changed", callback);
+  },
+  onRemoteScrollToLine(callback) {
+    return addEventListener("remote:scroll-to-line", callback);
+  },
+  onRemoteReload(callback) {
+    return addEventListener("remote:reload", callback);
+  },
 };
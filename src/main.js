@@ -37,17 +37,23 @@ hljs.registerLanguage("typescript", typescript);
 hljs.registerLanguage("ts", typescript);
 
 const previewEl = document.getElementById("preview");
+const comparePreviewEl = document.getElementById("compare-preview");
+const contentAreaEl = document.querySelector(".content-area");
 const appShellEl = document.querySelector(".app-shell");
 const openFileControlsEl = document.querySelector(".open-file-controls");
 const openFileButton = document.getElementById("open-file-button");
 const openFileOptionsButton = document.getElementById("open-file-options-button");
 const openFileOptionsMenu = document.getElementById("open-file-options-menu");
 const openFolderButton = document.getElementById("open-folder-button");
+const compareWithButton = document.getElementById("compare-with-button");
 const toggleTOCButton = document.getElementById("toggle-toc-button");
 const toggleFolderPanelButton = document.getElementById("toggle-folder-panel-button");
+const previousChapterButton = document.getElementById("previous-chapter-button");
+const nextChapterButton = document.getElementById("next-chapter-button");
 const toggleThemeButton = document.getElementById("toggle-theme-button");
 const openVSCodeButton = document.getElementById("open-vscode-button");
 const toggleAutoRefreshButton = document.getElementById("toggle-autorefresh-button");
+const toggleTimelineButton = document.getElementById("toggle-timeline-button");
 const fileInput = document.getElementById("file-input");
 const fileNameEl = document.getElementById("file-name");
 const tocDrawerEl = document.getElementById("toc-drawer");
@@ -56,12 +62,77 @@ const tocEmptyEl = document.getElementById("toc-empty");
 const folderFilesDrawerEl = document.getElementById("folder-files-drawer");
 const folderFilesListEl = document.getElementById("folder-files-list");
 const folderFilesEmptyEl = document.getElementById("folder-files-empty");
+const folderFilesTitleEl = document.getElementById("folder-files-title");
+const folderFilesNoticeEl = document.getElementById("folder-files-notice");
+const folderChangedFilterEl = document.getElementById("folder-changed-filter");
+const folderChangedOnlyInput = document.getElementById("folder-changed-only-input");
+const folderSortEl = document.getElementById("folder-sort");
+const folderSortSelect = document.getElementById("folder-sort-select");
+const safeModeBannerEl = document.getElementById("safe-mode-banner");
+const loadRemoteImagesButton = document.getElementById("load-remote-images-button");
+const trustFolderButton = document.getElementById("trust-folder-button");
+const dropOverlayEl = document.getElementById("drop-overlay");
+const gitStripEl = document.getElementById("git-strip");
+const gitLastCommitEl = document.getElementById("git-last-commit");
+const gitWorkingCopyButton = document.getElementById("git-working-copy-button");
+const toggleGitHistoryButton = document.getElementById("toggle-git-history-button");
+const toggleGitBlameButton = document.getElementById("toggle-git-blame-button");
+const blameCardEl = document.getElementById("blame-card");
+const quickOpenEl = document.getElementById("quick-open");
+const quickOpenInput = document.getElementById("quick-open-input");
+const quickOpenListEl = document.getElementById("quick-open-list");
+const quickOpenEmptyEl = document.getElementById("quick-open-empty");
+const gitHistoryListEl = document.getElementById("git-history-list");
+const timelineStripEl = document.getElementById("timeline-strip");
+const timelineStatusEl = document.getElementById("timeline-status");
+const localHistoryInput = document.getElementById("local-history-input");
+const timelineCurrentButton = document.getElementById("timeline-current-button");
+const timelineListEl = document.getElementById("timeline-list");
+const gitCompareForm = document.getElementById("git-compare-form");
+const gitCompareRevisionInput = document.getElementById("git-compare-revision");
+const compareBarEl = document.getElementById("compare-bar");
+const compareFileNamesEl = document.getElementById("compare-file-names");
+const exitCompareButton = document.getElementById("exit-compare-button");
 
 let currentFilePath = null;
 let currentFolderPath = null;
 let currentOpenMode = "single-file";
 let folderFiles = [];
+// The folder's ordering file (e.g. SUMMARY.md) while its files are listed as book chapters.
+let folderBook = null;
 let autoRefreshEnabled = true;
+let vscodeAppearance = null;
+let followSystemTheme = true;
+let safeModeForced = false;
+let followChangesEnabled = false;
+let currentDocumentTrusted = true;
+// Commit hash when the preview shows a historical version of `currentFilePath`.
+let currentRevision = null;
+// Revision the working copy is compared with, as typed (`HEAD`, `main`, ...).
+let currentDiffRevision = null;
+let diffSummary = null;
+let gitHistory = null;
+// Id of the local snapshot shown, or compared against when `currentSnapshotDiff` is set.
+let currentSnapshot = null;
+let currentSnapshotDiff = false;
+let localHistory = null;
+let blameEnabled = false;
+let blamePayload = null;
+let blameEntries = [];
+let blameLayoutFrame = null;
+// The second document shown next to the current one in compare mode.
+let comparePayload = null;
+let compareAlignFrame = null;
+let ignoreScrollEventFrom = null;
+const navigationHistory = { entries: [], index: -1 };
+let quickOpenResults = [];
+let quickOpenSelectedIndex = 0;
+// Responses to superseded keystrokes are dropped.
+let quickOpenRequestId = 0;
+const VIEWER_STATE_REPORT_DELAY_MS = 150;
+let viewerStateReportTimer = null;
+const DROP_MESSAGE_DURATION_MS = 4000;
+let dropMessageTimer = null;
 
 function markdownSourceLinePlugin(md) {
   md.core.ruler.after("block", "attach_source_lines", (state) => {
@@ -92,6 +163,49 @@ const markdown = new MarkdownIt({
   .use(markdownItFootnote)
   .use(markdownSourceLinePlugin);
 
+// Untrusted documents keep remote images off the network until the reader opts in. The desktop
+// app's content security policy blocks the web entirely, so there every remote image is held
+// back here and loaded through the backend proxy once allowed.
+const renderPolicy = { blockRemoteImages: false };
+
+function isRemoteUrl(value) {
+  return /^\s*(?:https?:)?\/\//i.test(value ?? "");
+}
+
+function isRemoteImageProxyUrl(value) {
+  return /^\s*(?:mudkip-remote:|https?:\/\/mudkip-remote\.localhost)/i.test(value ?? "");
+}
+
+DOMPurify.addHook("afterSanitizeAttributes", (node) => {
+  if (node.tagName !== "IMG") {
+    return;
+  }
+
+  // Only the viewer hands out proxy URLs; a document may not name one itself.
+  if (isRemoteImageProxyUrl(node.getAttribute("src"))) {
+    node.removeAttribute("src");
+  }
+  if (isRemoteImageProxyUrl(node.getAttribute("srcset"))) {
+    node.removeAttribute("srcset");
+  }
+
+  if (!renderPolicy.blockRemoteImages && !desktopAPI) {
+    return;
+  }
+
+  const src = node.getAttribute("src");
+  if (isRemoteUrl(src)) {
+    node.setAttribute("data-blocked-src", src);
+    node.removeAttribute("src");
+  }
+
+  const srcset = node.getAttribute("srcset");
+  if (srcset && /(?:https?:)?\/\//i.test(srcset)) {
+    node.setAttribute("data-blocked-srcset", srcset);
+    node.removeAttribute("srcset");
+  }
+});
+
 const defaultMarkdown = `# Mudkip
 
 Read-only preview tuned to match VS Code Markdown Preview.
@@ -115,31 +229,116 @@ function toggleOpenFileOptionsMenu() {
   openFileOptionsButton.setAttribute("aria-expanded", String(nextOpenState));
 }
 
-function updateThemeToggleButton() {
-  const isDarkTheme = document.body.classList.contains("vscode-dark");
+function hasVSCodeTheme() {
+  return Boolean(vscodeAppearance?.themeCss && isBaseThemeClass(vscodeAppearance.themeKind));
+}
 
-  if (isDarkTheme) {
-    toggleThemeButton.innerHTML = "&#9728;";
-    toggleThemeButton.title = "Switch to Light theme";
-    toggleThemeButton.setAttribute("aria-label", "Switch to Light theme");
-    return;
+function getCurrentThemeClass() {
+  if (document.body.classList.contains("vscode-user")) {
+    return "vscode-user";
+  }
+  return document.body.classList.contains("vscode-dark") ? "vscode-dark" : "vscode-light";
+}
+
+function getNextThemeClass() {
+  const currentTheme = getCurrentThemeClass();
+
+  if (currentTheme === "vscode-dark") {
+    return "vscode-light";
+  }
+
+  if (currentTheme === "vscode-light" && hasVSCodeTheme()) {
+    return "vscode-user";
   }
 
-  toggleThemeButton.innerHTML = "&#9790;";
-  toggleThemeButton.title = "Switch to Dark theme";
-  toggleThemeButton.setAttribute("aria-label", "Switch to Dark theme");
+  return "vscode-dark";
+}
+
+function updateThemeToggleButton() {
+  const nextTheme = getNextThemeClass();
+  const labels = {
+    "vscode-dark": { icon: "&#9790;", label: "Switch to Dark theme" },
+    "vscode-light": { icon: "&#9728;", label: "Switch to Light theme" },
+    "vscode-user": { icon: "&#9998;", label: `Switch to VS Code theme (${vscodeAppearance?.themeName ?? "custom"})` },
+  };
+  const { icon, label } = labels[nextTheme];
+
+  toggleThemeButton.innerHTML = icon;
+  toggleThemeButton.title = label;
+  toggleThemeButton.setAttribute("aria-label", label);
 }
 
 function setTheme(themeClass) {
-  document.body.classList.remove("vscode-dark", "vscode-light");
-  document.body.classList.add(themeClass);
+  document.body.classList.remove("vscode-dark", "vscode-light", "vscode-user");
+
+  if (themeClass === "vscode-user") {
+    if (hasVSCodeTheme()) {
+      document.body.classList.add("vscode-user", vscodeAppearance.themeKind);
+    } else {
+      document.body.classList.add("vscode-dark");
+    }
+  } else {
+    document.body.classList.add(themeClass);
+  }
+
   updateThemeToggleButton();
+  scheduleViewerStateReport();
 }
 
-function isThemeClass(value) {
+function isBaseThemeClass(value) {
   return value === "vscode-dark" || value === "vscode-light";
 }
 
+function isThemeClass(value) {
+  return isBaseThemeClass(value) || value === "vscode-user";
+}
+
+function setStyleElementContent(id, css) {
+  let styleEl = document.getElementById(id);
+  if (!styleEl) {
+    styleEl = document.createElement("style");
+    styleEl.id = id;
+    document.head.appendChild(styleEl);
+  }
+  styleEl.textContent = css ?? "";
+}
+
+function applyVSCodeAppearance(appearance) {
+  vscodeAppearance = appearance && typeof appearance === "object" ? appearance : null;
+  if (!vscodeAppearance) {
+    return;
+  }
+
+  if (typeof vscodeAppearance.fontFamily === "string") {
+    document.body.style.setProperty("--markdown-font-family", vscodeAppearance.fontFamily);
+  }
+  if (typeof vscodeAppearance.fontSize === "number") {
+    document.body.style.setProperty("--markdown-font-size", `${vscodeAppearance.fontSize}px`);
+  }
+  if (typeof vscodeAppearance.lineHeight === "number") {
+    document.body.style.setProperty("--markdown-line-height", String(vscodeAppearance.lineHeight));
+  }
+
+  setStyleElementContent("vscode-user-theme", vscodeAppearance.themeCss);
+
+  const stylesheets = Array.isArray(vscodeAppearance.stylesheets) ? vscodeAppearance.stylesheets : [];
+  setStyleElementContent("vscode-markdown-styles", stylesheets.join("\n"));
+
+  updateThemeToggleButton();
+}
+
+async function loadVSCodeAppearance() {
+  if (!desktopAPI || typeof desktopAPI.getVSCodeAppearance !== "function") {
+    return;
+  }
+
+  try {
+    applyVSCodeAppearance(await desktopAPI.getVSCodeAppearance());
+  } catch (error) {
+    console.error("Failed to load VS Code appearance:", error);
+  }
+}
+
 function setTOCOpen(isOpen) {
   appShellEl.classList.toggle("toc-open", isOpen);
   toggleTOCButton.setAttribute("aria-expanded", String(isOpen));
@@ -170,7 +369,7 @@ async function applyThemeFromSystemPreference() {
 
   try {
     const systemTheme = await desktopAPI.getSystemTheme();
-    if (isThemeClass(systemTheme)) {
+    if (isBaseThemeClass(systemTheme)) {
       setTheme(systemTheme);
       return;
     }
@@ -188,14 +387,34 @@ function applyStartupOptions(options, config = {}) {
 
   const syncWatcher = config.syncWatcher !== false;
 
-  if (isThemeClass(options.theme)) {
+  if (options.theme === "system") {
+    followSystemTheme = true;
+    applyThemeFromSystemPreference().catch((error) => {
+      console.error("Failed to apply system theme:", error);
+    });
+  } else if (isThemeClass(options.theme)) {
+    followSystemTheme = false;
     setTheme(options.theme);
   }
 
+  if (options.safeMode === true && !safeModeForced) {
+    safeModeForced = true;
+    reloadCurrentDocument().catch((error) => {
+      console.error("Failed to reload document in safe mode:", error);
+    });
+  } else if (options.safeMode === false) {
+    // A later launch opened a document without --safe; the document it opens follows.
+    safeModeForced = false;
+  }
+
   if (typeof options.tocOpen === "boolean") {
     setTOCOpen(options.tocOpen);
   }
 
+  if (typeof options.followChanges === "boolean") {
+    followChangesEnabled = options.followChanges;
+  }
+
   if (typeof options.autoRefresh === "boolean") {
     autoRefreshEnabled = options.autoRefresh;
     updateAutoRefreshButton();
@@ -221,7 +440,9 @@ function slugifyHeading(text) {
 }
 
 function rebuildTableOfContents() {
-  const headings = Array.from(previewEl.querySelectorAll("h1, h2, h3, h4, h5, h6"));
+  const headings = Array.from(previewEl.querySelectorAll("h1, h2, h3, h4, h5, h6")).filter(
+    (heading) => !heading.closest(".diff-removed"),
+  );
   const seenIds = new Map();
   tocListEl.innerHTML = "";
 
@@ -270,108 +491,1468 @@ function setBaseHref(baseHref) {
   baseEl.setAttribute("href", baseHref ?? "./");
 }
 
-function renderMarkdown(source, options = {}) {
-  setBaseHref(options.baseHref);
-  const rendered = markdown.render(source);
-  previewEl.innerHTML = DOMPurify.sanitize(rendered);
-  rebuildTableOfContents();
+function blankOutFrontMatter(source) {
+  const match = /^\uFEFF?---\r?\n[\s\S]*?\r?\n(?:---|\.\.\.)[ \t]*(?:\r?\n|$)/.exec(source);
+  if (!match) {
+    return source;
+  }
+
+  // Keep the line count so data-source-line still points at the right source line.
+  const lineCount = match[0].split("\n").length - 1;
+  return "\n".repeat(lineCount) + source.slice(match[0].length);
 }
 
-function getPreviewScrollRatio() {
-  const maxScroll = previewEl.scrollHeight - previewEl.clientHeight;
-  if (maxScroll <= 0) {
-    return 0;
+function applyUserStylesheets(stylesheets) {
+  let styleEl = document.getElementById("user-stylesheets");
+  if (!styleEl) {
+    styleEl = document.createElement("style");
+    styleEl.id = "user-stylesheets";
   }
-  return previewEl.scrollTop / maxScroll;
+
+  const entries = Array.isArray(stylesheets) ? stylesheets : [];
+  styleEl.textContent = entries
+    .filter((entry) => typeof entry?.css === "string")
+    .map((entry) => `/* ${entry.source}: ${String(entry.path).replaceAll("*/", "* /")} */\n${entry.css}`)
+    .join("\n");
+
+  // User styles must come after the theme and markdown.styles sheets to win the cascade.
+  document.head.appendChild(styleEl);
 }
 
-function restorePreviewScrollRatio(ratio) {
-  const maxScroll = previewEl.scrollHeight - previewEl.clientHeight;
-  if (maxScroll <= 0) {
-    previewEl.scrollTop = 0;
+function absoluteRemoteUrl(value) {
+  const trimmed = value.trim();
+  return trimmed.startsWith("//") ? `https:${trimmed}` : trimmed;
+}
+
+function parseSrcset(srcset) {
+  return srcset
+    .split(/,\s+/)
+    .map((candidate) => candidate.trim().split(/\s+/))
+    .filter(([url]) => url)
+    .map(([url, ...descriptors]) => ({ url, descriptor: descriptors.join(" ") }));
+}
+
+async function proxyRemoteImage(image, src, srcset) {
+  const candidates = srcset ? parseSrcset(srcset) : [];
+  const remoteUrls = [src, ...candidates.map((candidate) => candidate.url)]
+    .filter((url) => isRemoteUrl(url))
+    .map(absoluteRemoteUrl);
+  const proxied = new Map();
+  if (remoteUrls.length > 0) {
+    const proxyUrls = await desktopAPI.allowRemoteImages(remoteUrls);
+    remoteUrls.forEach((url, index) => proxied.set(url, proxyUrls[index]));
+  }
+  const resolve = (url) => (isRemoteUrl(url) ? proxied.get(absoluteRemoteUrl(url)) : url);
+
+  if (candidates.length > 0) {
+    image.setAttribute(
+      "srcset",
+      candidates.map(({ url, descriptor }) => [resolve(url), descriptor].filter(Boolean).join(" ")).join(", "),
+    );
+  }
+  if (src) {
+    image.setAttribute("src", resolve(src));
+  }
+}
+
+function loadBlockedImage(image) {
+  const src = image.dataset.blockedSrc;
+  const srcset = image.dataset.blockedSrcset;
+  delete image.dataset.blockedSrc;
+  delete image.dataset.blockedSrcset;
+
+  image.hidden = false;
+  if (image.previousElementSibling?.classList.contains("blocked-image-placeholder")) {
+    image.previousElementSibling.remove();
+  }
+
+  if (typeof desktopAPI?.allowRemoteImages === "function") {
+    proxyRemoteImage(image, src, srcset).catch((error) => {
+      console.error("Unable to load remote image:", error);
+    });
     return;
   }
-  previewEl.scrollTop = Math.max(0, Math.min(maxScroll, maxScroll * ratio));
+
+  if (srcset) {
+    image.setAttribute("srcset", srcset);
+  }
+  if (src) {
+    image.setAttribute("src", src);
+  }
 }
 
-async function syncAutoRefreshWatcher() {
-  if (!desktopAPI) {
+function insertBlockedImagePlaceholders(container = previewEl, blocked = renderPolicy.blockRemoteImages) {
+  for (const image of container.querySelectorAll("img[data-blocked-src], img[data-blocked-srcset]")) {
+    if (!blocked) {
+      loadBlockedImage(image);
+      continue;
+    }
+
+    let host = "a remote server";
+    try {
+      host = new URL(image.dataset.blockedSrc, "https://invalid/").host || host;
+    } catch {
+      // Keep the generic label.
+    }
+
+    const placeholder = document.createElement("button");
+    placeholder.type = "button";
+    placeholder.className = "blocked-image-placeholder";
+    placeholder.textContent = `Load image from ${host}${image.alt ? ` (${image.alt})` : ""}`;
+    placeholder.addEventListener("click", () => {
+      loadBlockedImage(image);
+    });
+
+    image.hidden = true;
+    image.before(placeholder);
+  }
+}
+
+function updateSafeModeBanner() {
+  const isRestricted = !currentDocumentTrusted;
+  safeModeBannerEl.hidden = !isRestricted;
+  loadRemoteImagesButton.hidden = !previewEl.querySelector("img[data-blocked-src], img[data-blocked-srcset]");
+  trustFolderButton.hidden = safeModeForced || !desktopAPI || !currentFilePath;
+}
+
+function formatCommitDate(isoDate) {
+  const date = new Date(isoDate);
+  if (Number.isNaN(date.getTime())) {
+    return isoDate ?? "";
+  }
+  return date.toLocaleDateString(undefined, { year: "numeric", month: "short", day: "numeric" });
+}
+
+function describeCommit(commit) {
+  return `${commit.shortHash} · ${commit.authorName} · ${formatCommitDate(commit.date)}`;
+}
+
+function setGitHistoryOpen(isOpen) {
+  const shouldOpen = isOpen && !gitStripEl.hidden;
+  gitHistoryListEl.hidden = !shouldOpen;
+  toggleGitHistoryButton.setAttribute("aria-expanded", String(shouldOpen));
+}
+
+function renderGitStrip() {
+  const commits = Array.isArray(gitHistory?.commits) ? gitHistory.commits : [];
+  gitStripEl.hidden = commits.length === 0;
+  gitHistoryListEl.innerHTML = "";
+  if (commits.length === 0) {
+    setGitHistoryOpen(false);
     return;
   }
 
-  if (!autoRefreshEnabled || !currentFilePath || typeof desktopAPI.startAutoRefreshWatch !== "function") {
-    if (typeof desktopAPI.stopAutoRefreshWatch === "function") {
-      await desktopAPI.stopAutoRefreshWatch();
+  const shownCommit = commits.find((commit) => commit.hash === currentRevision) ?? commits[0];
+  const label = currentRevision ? "Viewing" : "Last commit";
+  gitLastCommitEl.textContent = currentDiffRevision
+    ? `Comparing with ${currentDiffRevision}: ${diffSummary ?? "no changes"}`
+    : `${label}: ${shownCommit.subject} (${describeCommit(shownCommit)})`;
+  gitLastCommitEl.title = shownCommit.body ? `${shownCommit.subject}\n\n${shownCommit.body}` : shownCommit.subject;
+  gitWorkingCopyButton.hidden = !currentRevision && !currentDiffRevision;
+  gitWorkingCopyButton.textContent = currentDiffRevision ? "Exit compare" : "Back to working copy";
+
+  for (const commit of commits) {
+    const item = document.createElement("li");
+    const button = document.createElement("button");
+    button.type = "button";
+    button.className = "git-history-entry";
+    button.dataset.commit = commit.hash;
+    button.title = commit.body ? `${commit.subject}\n\n${commit.body}` : commit.subject;
+    if (commit.hash === currentRevision) {
+      button.setAttribute("aria-current", "true");
     }
+
+    const subject = document.createElement("span");
+    subject.className = "git-history-subject";
+    subject.textContent = commit.subject;
+    const meta = document.createElement("span");
+    meta.className = "git-history-meta";
+    meta.textContent = describeCommit(commit);
+
+    button.append(subject, meta);
+    item.append(button);
+    gitHistoryListEl.append(item);
+  }
+}
+
+function clearGitHistory() {
+  currentRevision = null;
+  currentDiffRevision = null;
+  gitHistory = null;
+  renderGitStrip();
+}
+
+async function refreshGitHistory() {
+  if (!desktopAPI || typeof desktopAPI.getGitFileHistory !== "function" || !currentFilePath) {
+    clearGitHistory();
     return;
   }
 
-  await desktopAPI.startAutoRefreshWatch(currentFilePath);
+  const filePath = currentFilePath;
+  const history = await desktopAPI.getGitFileHistory(filePath);
+  if (filePath !== currentFilePath) {
+    return;
+  }
+  gitHistory = history ?? null;
+  renderGitStrip();
 }
 
-async function syncFolderWatcher() {
-  if (!desktopAPI) {
+async function openGitRevision(commit) {
+  if (!desktopAPI || !currentFilePath || typeof desktopAPI.readGitFileRevision !== "function") {
     return;
   }
 
-  if (currentOpenMode !== "folder" || !currentFolderPath || typeof desktopAPI.startFolderWatch !== "function") {
-    if (typeof desktopAPI.stopFolderWatch === "function") {
-      await desktopAPI.stopFolderWatch();
-    }
+  const payload = await desktopAPI.readGitFileRevision(currentFilePath, commit);
+  renderDesktopPayload(payload, { syncWatcher: false });
+}
+
+async function openGitDiff(revision) {
+  if (!desktopAPI || !currentFilePath || typeof desktopAPI.readGitFileDiff !== "function") {
     return;
   }
 
-  await desktopAPI.startFolderWatch(currentFolderPath);
+  const payload = await desktopAPI.readGitFileDiff(currentFilePath, revision || "HEAD");
+  renderDesktopPayload(payload, { syncWatcher: false });
 }
 
-function syncFolderWatcherWithLogging() {
-  syncFolderWatcher().catch((error) => {
-    console.error("Failed to sync folder watcher:", error);
-  });
+const RELATIVE_TIME_UNITS = [
+  ["year", 365 * 24 * 60 * 60],
+  ["month", 30 * 24 * 60 * 60],
+  ["week", 7 * 24 * 60 * 60],
+  ["day", 24 * 60 * 60],
+  ["hour", 60 * 60],
+  ["minute", 60],
+];
+const relativeTimeFormat = new Intl.RelativeTimeFormat(undefined, { numeric: "auto" });
+
+function formatAge(isoDate) {
+  const seconds = (new Date(isoDate).getTime() - Date.now()) / 1000;
+  if (Number.isNaN(seconds)) {
+    return "";
+  }
+
+  for (const [unit, unitSeconds] of RELATIVE_TIME_UNITS) {
+    if (Math.abs(seconds) >= unitSeconds) {
+      return relativeTimeFormat.format(Math.round(seconds / unitSeconds), unit);
+    }
+  }
+  return relativeTimeFormat.format(0, "minute");
 }
 
-function updateFolderFilesList(files, selectedPath = null) {
-  folderFiles = Array.isArray(files) ? files : [];
-  folderFilesListEl.innerHTML = "";
+// Blame follows the working copy, so it is left out of revision, diff and snapshot views.
+function canShowBlame() {
+  return (
+    blameEnabled && Boolean(currentFilePath) && !currentRevision && !currentDiffRevision && !currentSnapshot && !comparePayload
+  );
+}
 
-  if (folderFiles.length === 0) {
-    folderFilesEmptyEl.hidden = false;
-    return { hasFiles: false, hasSelection: false };
+// The commit that last changed most of the block's source lines.
+function pickBlameCommit(start, end) {
+  const lineCounts = new Map();
+  for (const hunk of blamePayload?.hunks ?? []) {
+    const overlap = Math.min(end, hunk.endLine) - Math.max(start, hunk.startLine) + 1;
+    if (overlap > 0) {
+      lineCounts.set(hunk.hash, (lineCounts.get(hunk.hash) ?? 0) + overlap);
+    }
   }
 
-  folderFilesEmptyEl.hidden = true;
+  let bestHash = null;
+  for (const [hash, count] of lineCounts) {
+    if (bestHash === null || count > lineCounts.get(bestHash)) {
+      bestHash = hash;
+    }
+  }
+  return bestHash;
+}
 
-  let hasSelection = false;
+function hideBlameCard() {
+  blameCardEl.hidden = true;
+}
 
-  for (const file of folderFiles) {
-    if (!file?.filePath) {
+function clearBlameGutter() {
+  previewEl.querySelector(":scope > .blame-gutter")?.remove();
+  previewEl.classList.remove("has-blame-gutter");
+  blameEntries = [];
+  hideBlameCard();
+}
+
+// Entries sit next to their block and end where the next one starts.
+function layoutBlameGutter() {
+  blameLayoutFrame = null;
+  const tops = blameEntries.map(({ block }) => block.offsetTop);
+  blameEntries.forEach(({ entry }, index) => {
+    entry.style.top = `${tops[index]}px`;
+    entry.style.height = index + 1 < tops.length ? `${tops[index + 1] - tops[index]}px` : "";
+  });
+}
+
+function scheduleBlameLayout() {
+  if (blameEntries.length === 0 || blameLayoutFrame !== null) {
+    return;
+  }
+  blameLayoutFrame = requestAnimationFrame(layoutBlameGutter);
+}
+
+// Maps blame hunks onto the top-level blocks through their source-line ranges; consecutive
+// blocks from the same commit share one gutter entry.
+function renderBlameGutter() {
+  clearBlameGutter();
+  if (canShowBlame() && !currentDocumentTrusted) {
+    renderBlameTrustNotice();
+    return;
+  }
+  if (!canShowBlame() || !blamePayload) {
+    return;
+  }
+
+  const commits = new Map(blamePayload.commits.map((commit) => [commit.hash, commit]));
+  const gutter = document.createElement("div");
+  gutter.className = "blame-gutter";
+
+  let previousHash = null;
+  for (const block of previewEl.querySelectorAll(":scope > [data-source-line]")) {
+    const { start, end } = getSourceLineRange(block);
+    const hash = pickBlameCommit(start, end);
+    if (!hash || hash === previousHash) {
       continue;
     }
+    previousHash = hash;
+
+    const commit = commits.get(hash);
+    const entry = document.createElement("div");
+    entry.className = "blame-entry";
+    entry.dataset.blameHash = hash;
+    entry.classList.toggle("is-uncommitted", !commit);
+    entry.textContent = commit ? `${commit.authorName} · ${formatAge(commit.date)}` : "Not committed yet";
+    gutter.append(entry);
+    blameEntries.push({ entry, block });
+  }
 
+  previewEl.classList.add("has-blame-gutter");
+  previewEl.append(gutter);
+  layoutBlameGutter();
+}
+
+// Blame runs git in the document's repository, whose config may name filter commands, so
+// untrusted documents only get a hint to trust the folder.
+function renderBlameTrustNotice() {
+  const gutter = document.createElement("div");
+  gutter.className = "blame-gutter";
+  const notice = document.createElement("div");
+  notice.className = "blame-notice";
+  notice.textContent = "Trust this folder to enable blame";
+  gutter.append(notice);
+  previewEl.classList.add("has-blame-gutter");
+  previewEl.append(gutter);
+}
+
+async function refreshBlame() {
+  if (!canShowBlame() || !desktopAPI || typeof desktopAPI.getGitBlame !== "function") {
+    clearBlameGutter();
+    return;
+  }
+  if (!currentDocumentTrusted) {
+    blamePayload = null;
+    renderBlameGutter();
+    return;
+  }
+
+  const filePath = currentFilePath;
+  const payload = await desktopAPI.getGitBlame(filePath);
+  if (filePath !== currentFilePath) {
+    return;
+  }
+  blamePayload = payload ?? null;
+  renderBlameGutter();
+}
+
+function refreshBlameWithLogging() {
+  refreshBlame().catch((error) => {
+    console.error("Failed to load git blame:", error);
+  });
+}
+
+function setBlameEnabled(enabled) {
+  blameEnabled = enabled;
+  toggleGitBlameButton.setAttribute("aria-pressed", String(enabled));
+  if (enabled) {
+    refreshBlameWithLogging();
+  } else {
+    blamePayload = null;
+    clearBlameGutter();
+  }
+}
+
+function showBlameCard(entry) {
+  const commit = blamePayload?.commits.find((candidate) => candidate.hash === entry.dataset.blameHash);
+  const subject = document.createElement("strong");
+  subject.textContent = commit ? commit.subject : "Not committed yet";
+  const meta = document.createElement("div");
+  meta.className = "blame-card-meta";
+  meta.textContent = commit
+    ? `${commit.shortHash} · ${commit.authorName} <${commit.authorEmail}> · ${new Date(commit.date).toLocaleString()}`
+    : "Local changes in the working copy";
+  blameCardEl.replaceChildren(subject, meta);
+  if (commit?.body) {
+    const body = document.createElement("div");
+    body.className = "blame-card-body";
+    body.textContent = commit.body;
+    blameCardEl.append(body);
+  }
+
+  const entryRect = entry.getBoundingClientRect();
+  blameCardEl.hidden = false;
+  const top = Math.min(entryRect.top, window.innerHeight - blameCardEl.offsetHeight - 8);
+  blameCardEl.style.left = `${entryRect.right + 8}px`;
+  blameCardEl.style.top = `${Math.max(8, top)}px`;
+}
+
+function formatSnapshotTime(timestamp) {
+  return new Date(Number(timestamp)).toLocaleString(undefined, { dateStyle: "medium", timeStyle: "medium" });
+}
+
+function formatByteSize(bytes) {
+  return bytes < 1024 ? `${bytes} B` : `${(bytes / 1024).toFixed(1)} KB`;
+}
+
+function describeLocalHistory(snapshots) {
+  if (localHistory?.enabled !== true) {
+    return snapshots.length > 0 ? `Local history is off (${snapshots.length} saved)` : "Local history is off";
+  }
+  if (!autoRefreshEnabled) {
+    return "Snapshots are only saved while auto-refresh is on";
+  }
+  if (snapshots.length === 0) {
+    return "No snapshots yet: one is saved each time auto-refresh sees a new version";
+  }
+  return `${snapshots.length} local snapshot${snapshots.length === 1 ? "" : "s"}`;
+}
+
+function renderTimelineStrip() {
+  const snapshots = Array.isArray(localHistory?.snapshots) ? localHistory.snapshots : [];
+  localHistoryInput.checked = localHistory?.enabled === true;
+  timelineCurrentButton.hidden = !currentSnapshot;
+  if (currentSnapshot && currentSnapshotDiff) {
+    timelineStatusEl.textContent = `Comparing with snapshot from ${formatSnapshotTime(currentSnapshot)}: ${diffSummary ?? "no changes"}`;
+  } else if (currentSnapshot) {
+    timelineStatusEl.textContent = `Viewing snapshot from ${formatSnapshotTime(currentSnapshot)}`;
+  } else {
+    timelineStatusEl.textContent = describeLocalHistory(snapshots);
+  }
+
+  timelineListEl.innerHTML = "";
+  for (const snapshot of snapshots) {
     const item = document.createElement("li");
-    item.className = "toc-item";
+    item.className = "timeline-entry";
 
     const button = document.createElement("button");
     button.type = "button";
-    button.className = "folder-file-link";
-    button.dataset.filePath = file.filePath;
-    button.textContent = file.fileName || file.filePath;
-
-    if (selectedPath && file.filePath === selectedPath) {
+    button.className = "git-history-entry";
+    button.dataset.snapshotId = snapshot.id;
+    if (snapshot.id === currentSnapshot && !currentSnapshotDiff) {
       button.setAttribute("aria-current", "true");
-      hasSelection = true;
     }
 
-    item.append(button);
-    folderFilesListEl.append(item);
+    const time = document.createElement("span");
+    time.className = "git-history-subject";
+    time.textContent = formatSnapshotTime(snapshot.timestamp);
+    const meta = document.createElement("span");
+    meta.className = "git-history-meta";
+    meta.textContent = formatByteSize(snapshot.size);
+    button.append(time, meta);
+
+    const diffButton = document.createElement("button");
+    diffButton.type = "button";
+    diffButton.className = "timeline-diff-button";
+    diffButton.dataset.diffSnapshotId = snapshot.id;
+    diffButton.textContent = "Diff";
+    diffButton.title = "Compare the current version with this snapshot";
+    if (snapshot.id === currentSnapshot && currentSnapshotDiff) {
+      diffButton.setAttribute("aria-pressed", "true");
+    }
+
+    item.append(button, diffButton);
+    timelineListEl.append(item);
+  }
+}
+
+function isTimelineOpen() {
+  return !timelineStripEl.hidden;
+}
+
+function setTimelineOpen(isOpen) {
+  const shouldOpen = isOpen && Boolean(currentFilePath);
+  timelineStripEl.hidden = !shouldOpen;
+  toggleTimelineButton.setAttribute("aria-expanded", String(shouldOpen));
+  if (shouldOpen) {
+    refreshLocalHistoryWithLogging();
+  }
+}
+
+async function refreshLocalHistory() {
+  if (!desktopAPI || typeof desktopAPI.getLocalHistory !== "function" || !currentFilePath) {
+    localHistory = null;
+    renderTimelineStrip();
+    return;
+  }
+
+  const filePath = currentFilePath;
+  const history = await desktopAPI.getLocalHistory(filePath);
+  if (filePath !== currentFilePath) {
+    return;
+  }
+  localHistory = history ?? null;
+  renderTimelineStrip();
+}
+
+function refreshLocalHistoryWithLogging() {
+  refreshLocalHistory().catch((error) => {
+    console.error("Failed to load local history:", error);
+  });
+}
+
+function clearLocalHistory() {
+  currentSnapshot = null;
+  currentSnapshotDiff = false;
+  localHistory = null;
+  toggleTimelineButton.disabled = true;
+  setTimelineOpen(false);
+}
+
+async function openSnapshot(id) {
+  if (!desktopAPI || !currentFilePath || typeof desktopAPI.readSnapshot !== "function") {
+    return;
   }
 
-  if (folderFilesListEl.children.length === 0) {
-    folderFilesEmptyEl.hidden = false;
-    return { hasFiles: false, hasSelection: false };
+  const payload = await desktopAPI.readSnapshot(currentFilePath, id);
+  renderDesktopPayload(payload, { syncWatcher: false });
+}
+
+async function openSnapshotDiff(id) {
+  if (!desktopAPI || !currentFilePath || typeof desktopAPI.readSnapshotDiff !== "function") {
+    return;
+  }
+
+  const payload = await desktopAPI.readSnapshotDiff(currentFilePath, id);
+  renderDesktopPayload(payload, { syncWatcher: false });
+}
+
+function renderMarkdownHtml(source) {
+  return DOMPurify.sanitize(markdown.render(blankOutFrontMatter(source)));
+}
+
+// Blocks compare equal when their markup matches, ignoring where they sit in the source.
+function getBlockSignature(element) {
+  const clone = element.cloneNode(true);
+  for (const node of [clone, ...clone.querySelectorAll("[data-source-line]")]) {
+    node.removeAttribute?.("data-source-line");
+    node.removeAttribute?.("data-source-line-end");
+  }
+  return clone.outerHTML ?? clone.textContent;
+}
+
+// Weighted longest common subsequence: pairs items where `score(i, j)` is positive so the total
+// score is maximal. Returns aligned rows of indices, with `null` where one side has no partner.
+function alignSequences(leftLength, rightLength, score) {
+  const best = Array.from({ length: leftLength + 1 }, () => new Uint32Array(rightLength + 1));
+
+  for (let i = leftLength - 1; i >= 0; i -= 1) {
+    for (let j = rightLength - 1; j >= 0; j -= 1) {
+      const pairScore = score(i, j);
+      const skip = Math.max(best[i + 1][j], best[i][j + 1]);
+      best[i][j] = pairScore > 0 ? Math.max(skip, pairScore + best[i + 1][j + 1]) : skip;
+    }
+  }
+
+  const rows = [];
+  let i = 0;
+  let j = 0;
+  while (i < leftLength || j < rightLength) {
+    const pairScore = i < leftLength && j < rightLength ? score(i, j) : 0;
+    if (pairScore > 0 && best[i][j] === pairScore + best[i + 1][j + 1]) {
+      rows.push([i, j]);
+      i += 1;
+      j += 1;
+    } else if (j >= rightLength || (i < leftLength && best[i + 1][j] >= best[i][j + 1])) {
+      rows.push([i, null]);
+      i += 1;
+    } else {
+      rows.push([null, j]);
+      j += 1;
+    }
+  }
+  return rows;
+}
+
+function diffBlockSequences(baseBlocks, currentBlocks) {
+  const baseKeys = baseBlocks.map(getBlockSignature);
+  const currentKeys = currentBlocks.map(getBlockSignature);
+  const rows = alignSequences(baseKeys.length, currentKeys.length, (i, j) =>
+    baseKeys[i] === currentKeys[j] ? 1 : 0,
+  );
+
+  return rows.map(([i, j]) => {
+    if (i !== null && j !== null) {
+      return { type: "same", current: currentBlocks[j] };
+    }
+    return i !== null ? { type: "removed", base: baseBlocks[i] } : { type: "added", current: currentBlocks[j] };
+  });
+}
+
+function wrapDiffBlock(element, classNames) {
+  const wrapper = document.createElement("div");
+  wrapper.className = `diff-block ${classNames}`;
+  wrapper.append(element);
+  return wrapper;
+}
+
+// Removed blocks are not part of the current source, so they must not take part in
+// source-line scrolling or heading anchors.
+function detachRemovedBlock(element) {
+  for (const node of [element, ...element.querySelectorAll("[data-source-line], [id]")]) {
+    node.removeAttribute?.("data-source-line");
+    node.removeAttribute?.("data-source-line-end");
+    node.removeAttribute?.("id");
+  }
+  return element;
+}
+
+// Replaces the rendered working copy in the preview with a block-level rich diff against
+// `baseHtml`. A run of removed blocks followed by added ones pairs up as modifications.
+function applyRenderedDiff(baseHtml) {
+  const baseContainer = document.createElement("div");
+  baseContainer.innerHTML = baseHtml;
+  const operations = diffBlockSequences(Array.from(baseContainer.children), Array.from(previewEl.children));
+  const counts = { added: 0, removed: 0, modified: 0 };
+  const fragment = document.createDocumentFragment();
+
+  let index = 0;
+  while (index < operations.length) {
+    if (operations[index].type === "same") {
+      fragment.append(operations[index].current);
+      index += 1;
+      continue;
+    }
+
+    const removed = [];
+    const added = [];
+    while (index < operations.length && operations[index].type !== "same") {
+      if (operations[index].type === "removed") {
+        removed.push(operations[index].base);
+      } else {
+        added.push(operations[index].current);
+      }
+      index += 1;
+    }
+
+    const pairs = Math.min(removed.length, added.length);
+    for (let pair = 0; pair < pairs; pair += 1) {
+      fragment.append(
+        wrapDiffBlock(detachRemovedBlock(removed[pair]), "diff-removed"),
+        wrapDiffBlock(added[pair], "diff-modified"),
+      );
+    }
+    for (const block of removed.slice(pairs)) {
+      fragment.append(wrapDiffBlock(detachRemovedBlock(block), "diff-removed"));
+    }
+    for (const block of added.slice(pairs)) {
+      fragment.append(wrapDiffBlock(block, "diff-added"));
+    }
+
+    counts.modified += pairs;
+    counts.removed += removed.length - pairs;
+    counts.added += added.length - pairs;
+  }
+
+  previewEl.replaceChildren(fragment);
+  return counts;
+}
+
+function isHeadingElement(element) {
+  return /^H[1-6]$/.test(element.tagName);
+}
+
+// Puts the blocks back where they were before `alignComparePanes` grouped them.
+function unwrapCompareSections(container) {
+  for (const section of container.querySelectorAll(":scope > .compare-section")) {
+    section.replaceWith(...section.children);
+  }
+  for (const block of container.querySelectorAll(".compare-differs")) {
+    block.classList.remove("compare-differs");
+  }
+}
+
+// A section starts at every top-level heading; content before the first heading is level 0.
+function splitIntoSections(container) {
+  unwrapCompareSections(container);
+  const sections = [];
+  let current = null;
+  for (const element of Array.from(container.children)) {
+    if (!current || isHeadingElement(element)) {
+      current = {
+        level: isHeadingElement(element) ? Number(element.tagName[1]) : 0,
+        title: isHeadingElement(element) ? element.textContent.trim() : "",
+        blocks: [],
+      };
+      sections.push(current);
+    }
+    current.blocks.push(element);
+  }
+  return sections;
+}
+
+// Sections pair up only at the same heading level, preferring identical titles, so drafts line up
+// by title and translations by structure.
+function scoreSectionPair(left, right) {
+  if (left.level !== right.level) {
+    return 0;
+  }
+  return left.title === right.title ? 2 : 1;
+}
+
+function markDifferingBlocks(leftBlocks, rightBlocks) {
+  const leftKeys = leftBlocks.map(getBlockSignature);
+  const rightKeys = rightBlocks.map(getBlockSignature);
+  const rows = alignSequences(leftKeys.length, rightKeys.length, (i, j) => (leftKeys[i] === rightKeys[j] ? 1 : 0));
+  for (const [i, j] of rows) {
+    if (i === null) {
+      rightBlocks[j].classList.add("compare-differs");
+    } else if (j === null) {
+      leftBlocks[i].classList.add("compare-differs");
+    }
+  }
+}
+
+function createCompareSection(section) {
+  const element = document.createElement("div");
+  element.className = "compare-section";
+  if (section) {
+    element.append(...section.blocks);
+  }
+  return element;
+}
+
+// Aligned sections get the same height on both sides, so equal scroll offsets show matching
+// content.
+function equalizeCompareSections() {
+  compareAlignFrame = null;
+  const leftSections = previewEl.querySelectorAll(":scope > .compare-section");
+  const rightSections = comparePreviewEl.querySelectorAll(":scope > .compare-section");
+  if (!comparePayload || leftSections.length !== rightSections.length) {
+    return;
+  }
+
+  for (const section of [...leftSections, ...rightSections]) {
+    section.style.minHeight = "";
+  }
+  const heights = Array.from(leftSections, (left, index) =>
+    Math.max(left.offsetHeight, rightSections[index].offsetHeight),
+  );
+  heights.forEach((height, index) => {
+    leftSections[index].style.minHeight = `${height}px`;
+    rightSections[index].style.minHeight = `${height}px`;
+  });
+}
+
+function scheduleCompareEqualize() {
+  if (!comparePayload || compareAlignFrame !== null) {
+    return;
+  }
+  compareAlignFrame = requestAnimationFrame(equalizeCompareSections);
+}
+
+function alignComparePanes() {
+  const leftSections = splitIntoSections(previewEl);
+  const rightSections = splitIntoSections(comparePreviewEl);
+  const rows = alignSequences(leftSections.length, rightSections.length, (i, j) =>
+    scoreSectionPair(leftSections[i], rightSections[j]),
+  );
+
+  const leftFragment = document.createDocumentFragment();
+  const rightFragment = document.createDocumentFragment();
+  for (const [i, j] of rows) {
+    const left = i === null ? null : leftSections[i];
+    const right = j === null ? null : rightSections[j];
+    const leftElement = createCompareSection(left);
+    const rightElement = createCompareSection(right);
+
+    if (left && right) {
+      markDifferingBlocks(left.blocks, right.blocks);
+    } else {
+      (left ? leftElement : rightElement).classList.add("compare-unmatched");
+    }
+
+    leftFragment.append(leftElement);
+    rightFragment.append(rightElement);
+  }
+
+  previewEl.replaceChildren(leftFragment);
+  comparePreviewEl.replaceChildren(rightFragment);
+  equalizeCompareSections();
+}
+
+// The compare pane shares the document's <base>, so its relative URLs are resolved up front.
+function resolveRelativeUrls(container, baseHref) {
+  if (!baseHref) {
+    return;
+  }
+
+  for (const [selector, attribute] of [
+    ["img[src]", "src"],
+    ["video[src]", "src"],
+    ["audio[src]", "src"],
+    ["source[src]", "src"],
+    ["a[href]:not([href^='#'])", "href"],
+  ]) {
+    for (const element of container.querySelectorAll(selector)) {
+      try {
+        element.setAttribute(attribute, new URL(element.getAttribute(attribute), baseHref).href);
+      } catch {
+        // Leave unparsable URLs alone.
+      }
+    }
+  }
+}
+
+function renderComparePane() {
+  if (!comparePayload) {
+    return;
+  }
+
+  const trusted = comparePayload.trusted === true;
+  markdown.set({ html: trusted });
+  renderPolicy.blockRemoteImages = !trusted;
+  comparePreviewEl.innerHTML = renderMarkdownHtml(comparePayload.content);
+  markdown.set({ html: currentDocumentTrusted });
+  renderPolicy.blockRemoteImages = !currentDocumentTrusted;
+
+  resolveRelativeUrls(comparePreviewEl, comparePayload.baseHref);
+  insertBlockedImagePlaceholders(comparePreviewEl, !trusted);
+  alignComparePanes();
+}
+
+async function syncCompareWatcher() {
+  if (!desktopAPI) {
+    return;
+  }
+
+  if (!comparePayload || !autoRefreshEnabled || typeof desktopAPI.startCompareWatch !== "function") {
+    if (typeof desktopAPI.stopCompareWatch === "function") {
+      await desktopAPI.stopCompareWatch();
+    }
+    return;
+  }
+
+  await desktopAPI.startCompareWatch(comparePayload.filePath);
+}
+
+function syncCompareWatcherWithLogging() {
+  syncCompareWatcher().catch((error) => {
+    console.error("Failed to sync compare watcher:", error);
+  });
+}
+
+function leaveCompareMode() {
+  if (!comparePayload) {
+    return;
+  }
+
+  comparePayload = null;
+  contentAreaEl.classList.remove("is-comparing");
+  comparePreviewEl.hidden = true;
+  comparePreviewEl.replaceChildren();
+  compareBarEl.hidden = true;
+  unwrapCompareSections(previewEl);
+  syncCompareWatcherWithLogging();
+  renderBlameGutter();
+}
+
+async function enterCompareMode(payload) {
+  if (!desktopAPI || !currentFilePath || !payload?.filePath) {
+    return;
+  }
+
+  comparePayload = payload;
+  contentAreaEl.classList.add("is-comparing");
+  comparePreviewEl.hidden = false;
+  compareBarEl.hidden = false;
+  syncCompareWatcherWithLogging();
+
+  // The left side always shows the working copy while comparing.
+  const leftPayload = await desktopAPI.readMarkdownFile(currentFilePath);
+  renderDesktopPayload(leftPayload);
+  compareFileNamesEl.textContent = `${leftPayload.fileName} ↔ ${payload.fileName}`;
+  compareFileNamesEl.title = `${leftPayload.filePath}\n${payload.filePath}`;
+  comparePreviewEl.scrollTop = previewEl.scrollTop;
+}
+
+async function compareWithFileDialog() {
+  if (!desktopAPI || !currentFilePath) {
+    return;
+  }
+
+  const result = await desktopAPI.openMarkdownDialog();
+  if (result?.canceled || !result?.payload) {
+    return;
+  }
+  await enterCompareMode(result.payload);
+}
+
+async function openComparisonByPath(filePath) {
+  if (!desktopAPI || !filePath) {
+    return;
+  }
+  await enterCompareMode(await desktopAPI.readMarkdownFile(filePath));
+}
+
+function syncCompareScroll(source, target) {
+  if (ignoreScrollEventFrom === source) {
+    ignoreScrollEventFrom = null;
+    return;
+  }
+  if (!comparePayload || target.scrollTop === source.scrollTop) {
+    return;
+  }
+
+  ignoreScrollEventFrom = target;
+  target.scrollTop = source.scrollTop;
+}
+
+function getSourceLineRange(node) {
+  const start = Number.parseInt(node.getAttribute("data-source-line") ?? "", 10);
+  const end = Number.parseInt(node.getAttribute("data-source-line-end") ?? `${start}`, 10);
+  return { start, end: Number.isFinite(end) ? end : start };
+}
+
+// Marks the innermost blocks whose source lines overlap a changed range; nested list items and
+// table rows carry their own lines, so the highlight stays as tight as the markdown allows.
+function highlightChangedLines(ranges) {
+  if (!Array.isArray(ranges) || ranges.length === 0) {
+    return;
+  }
+
+  const overlapsChange = (node) => {
+    const { start, end } = getSourceLineRange(node);
+    return Number.isFinite(start) && ranges.some((range) => range.start <= end && start <= range.end);
+  };
+  const changedBlocks = Array.from(previewEl.querySelectorAll("[data-source-line]")).filter(
+    (node) =>
+      overlapsChange(node) && !Array.from(node.querySelectorAll("[data-source-line]")).some(overlapsChange),
+  );
+
+  for (const block of changedBlocks) {
+    block.classList.remove("change-flash");
+    // Restart the animation when the same block changes again before it faded out.
+    void block.offsetWidth;
+    block.classList.add("change-flash");
+    block.addEventListener("animationend", () => block.classList.remove("change-flash"), { once: true });
+  }
+
+  const firstBlock = changedBlocks[0];
+  if (!followChangesEnabled || !firstBlock) {
+    return;
+  }
+
+  const previewRect = previewEl.getBoundingClientRect();
+  const blockRect = firstBlock.getBoundingClientRect();
+  if (blockRect.bottom < previewRect.top || blockRect.top > previewRect.bottom) {
+    firstBlock.scrollIntoView({ block: "center" });
+  }
+}
+
+function describeDiffCounts(counts) {
+  const parts = Object.entries(counts)
+    .filter(([, count]) => count > 0)
+    .map(([kind, count]) => `${count} ${kind}`);
+  return parts.length > 0 ? parts.join(", ") : null;
+}
+
+function renderMarkdown(source, options = {}) {
+  const trusted = options.trusted !== false;
+  currentDocumentTrusted = trusted;
+  markdown.set({ html: trusted });
+  renderPolicy.blockRemoteImages = !trusted;
+
+  setBaseHref(options.baseHref);
+  applyUserStylesheets(options.stylesheets);
+  previewEl.innerHTML = renderMarkdownHtml(source);
+  if (typeof options.diffBase?.content === "string") {
+    diffSummary = describeDiffCounts(applyRenderedDiff(renderMarkdownHtml(options.diffBase.content)));
+  }
+  insertBlockedImagePlaceholders();
+  rebuildTableOfContents();
+  updateSafeModeBanner();
+}
+
+function getPreviewScrollRatio() {
+  const maxScroll = previewEl.scrollHeight - previewEl.clientHeight;
+  if (maxScroll <= 0) {
+    return 0;
+  }
+  return previewEl.scrollTop / maxScroll;
+}
+
+function restorePreviewScrollRatio(ratio) {
+  const maxScroll = previewEl.scrollHeight - previewEl.clientHeight;
+  if (maxScroll <= 0) {
+    previewEl.scrollTop = 0;
+    return;
+  }
+  previewEl.scrollTop = Math.max(0, Math.min(maxScroll, maxScroll * ratio));
+}
+
+const MARKDOWN_LINK_PATTERN = /\.(md|markdown|mdown|mkd|txt)$/i;
+const SYSTEM_HANDLER_PROTOCOLS = new Set(["http:", "https:", "mailto:"]);
+
+function recordHistoryEntry(filePath) {
+  if (!filePath || navigationHistory.entries[navigationHistory.index]?.filePath === filePath) {
+    return;
+  }
+
+  navigationHistory.entries.splice(navigationHistory.index + 1);
+  navigationHistory.entries.push({ filePath, scrollRatio: 0 });
+  navigationHistory.index = navigationHistory.entries.length - 1;
+}
+
+function saveHistoryScrollPosition() {
+  const entry = navigationHistory.entries[navigationHistory.index];
+  if (entry && entry.filePath === currentFilePath) {
+    entry.scrollRatio = getPreviewScrollRatio();
+  }
+}
+
+function getNavigationMode() {
+  return currentOpenMode === "folder" || currentOpenMode === "collection" ? currentOpenMode : "single-file";
+}
+
+async function traverseHistory(delta) {
+  const targetIndex = navigationHistory.index + delta;
+  const entry = navigationHistory.entries[targetIndex];
+  if (!entry) {
+    return;
+  }
+
+  saveHistoryScrollPosition();
+  navigationHistory.index = targetIndex;
+  await openDesktopFileByPath(entry.filePath, { mode: getNavigationMode(), recordHistory: false });
+  requestAnimationFrame(() => {
+    restorePreviewScrollRatio(entry.scrollRatio);
+  });
+}
+
+function traverseHistoryWithLogging(delta) {
+  traverseHistory(delta).catch((error) => {
+    console.error("Failed to navigate history:", error);
+  });
+}
+
+function scrollToFragment(fragment) {
+  if (!fragment) {
+    return false;
+  }
+
+  let id = fragment;
+  try {
+    id = decodeURIComponent(fragment);
+  } catch {
+    // Keep the raw fragment when it is not valid percent-encoding.
+  }
+
+  const target = document.getElementById(id) ?? document.getElementById(slugifyHeading(id));
+  if (!target || !previewEl.contains(target)) {
+    return false;
+  }
+
+  target.scrollIntoView({ block: "start" });
+  return true;
+}
+
+function isAssetUrl(url) {
+  return url.protocol === "mudkip-asset:" || url.host === "mudkip-asset.localhost";
+}
+
+function assetUrlToPath(url) {
+  const path = decodeURIComponent(url.pathname);
+  // `/C:/docs/a.md` -> `C:/docs/a.md`
+  return /^\/[A-Za-z]:\//.test(path) ? path.slice(1) : path;
+}
+
+async function followDocumentLink(rawHref, documentPath = currentFilePath) {
+  if (rawHref.startsWith("#")) {
+    scrollToFragment(rawHref.slice(1));
+    return;
+  }
+
+  let url;
+  try {
+    url = new URL(rawHref, document.baseURI);
+  } catch {
+    return;
+  }
+
+  if (SYSTEM_HANDLER_PROTOCOLS.has(url.protocol)) {
+    await desktopAPI.openWithSystemHandler(url.href);
+    return;
+  }
+
+  if (!isAssetUrl(url)) {
+    return;
+  }
+
+  const isFolderLink = url.pathname.endsWith("/");
+  const fragment = url.hash.slice(1);
+  let targetPath = assetUrlToPath(url);
+  // Linked files only become readable once the user follows the link, and only when the
+  // document really links there.
+  if (documentPath && typeof desktopAPI.followLink === "function") {
+    targetPath = await desktopAPI.followLink(documentPath, targetPath);
+  }
+
+  if (isFolderLink) {
+    await openDesktopFolderByPath(targetPath);
+    return;
+  }
+
+  if (!MARKDOWN_LINK_PATTERN.test(targetPath)) {
+    await desktopAPI.openWithSystemHandler(targetPath);
+    return;
+  }
+
+  if (targetPath !== currentFilePath) {
+    await openDesktopFileByPath(targetPath, { mode: getNavigationMode() });
+  }
+
+  if (!scrollToFragment(fragment)) {
+    previewEl.scrollTop = 0;
+  }
+}
+
+async function syncAutoRefreshWatcher() {
+  if (!desktopAPI) {
+    return;
+  }
+
+  if (!autoRefreshEnabled || !currentFilePath || typeof desktopAPI.startAutoRefreshWatch !== "function") {
+    if (typeof desktopAPI.stopAutoRefreshWatch === "function") {
+      await desktopAPI.stopAutoRefreshWatch();
+    }
+    return;
+  }
+
+  await desktopAPI.startAutoRefreshWatch(currentFilePath);
+}
+
+async function syncStylesheetWatcher() {
+  if (!desktopAPI) {
+    return;
+  }
+
+  if (!currentFilePath || typeof desktopAPI.startStylesheetWatch !== "function") {
+    if (typeof desktopAPI.stopStylesheetWatch === "function") {
+      await desktopAPI.stopStylesheetWatch();
+    }
+    return;
+  }
+
+  await desktopAPI.startStylesheetWatch(currentFilePath);
+}
+
+async function syncFolderWatcher() {
+  if (!desktopAPI) {
+    return;
+  }
+
+  if (currentOpenMode !== "folder" || !currentFolderPath || typeof desktopAPI.startFolderWatch !== "function") {
+    if (typeof desktopAPI.stopFolderWatch === "function") {
+      await desktopAPI.stopFolderWatch();
+    }
+    return;
+  }
+
+  await desktopAPI.startFolderWatch(currentFolderPath);
+}
+
+function syncFolderWatcherWithLogging() {
+  syncFolderWatcher().catch((error) => {
+    console.error("Failed to sync folder watcher:", error);
+  });
+}
+
+const GIT_STATUS_BADGES = {
+  modified: { letter: "M", label: "Modified" },
+  added: { letter: "A", label: "Added" },
+  untracked: { letter: "U", label: "Untracked" },
+  ignored: { letter: "I", label: "Ignored" },
+  conflicted: { letter: "C", label: "Conflicted" },
+};
+
+function isChangedGitStatus(status) {
+  return Boolean(status) && status !== "ignored";
+}
+
+function createGitStatusBadge(status) {
+  const badge = GIT_STATUS_BADGES[status];
+  if (!badge) {
+    return null;
+  }
+
+  const element = document.createElement("span");
+  element.className = `git-status-badge git-status-${status}`;
+  element.textContent = badge.letter;
+  element.title = badge.label;
+  return element;
+}
+
+function updateFolderFilesList(files, selectedPath = null) {
+  folderFiles = Array.isArray(files) ? files : [];
+  folderFilesListEl.innerHTML = "";
+
+  // The filter only makes sense when the folder is inside a git repository with changes.
+  const hasGitStatus = folderFiles.some((file) => file?.gitStatus);
+  folderChangedFilterEl.hidden = !hasGitStatus;
+  const changedOnly = hasGitStatus && folderChangedOnlyInput.checked;
+  folderFilesEmptyEl.textContent = changedOnly ? "No changed markdown files." : "No markdown files found.";
+
+  if (folderFiles.length === 0) {
+    folderFilesEmptyEl.hidden = false;
+    return { hasFiles: false, hasSelection: false };
+  }
+
+  let hasFiles = false;
+  let hasSelection = false;
+
+  for (const file of folderFiles) {
+    if (!file?.filePath) {
+      continue;
+    }
+
+    hasFiles = true;
+    const isSelected = Boolean(selectedPath) && file.filePath === selectedPath;
+    hasSelection ||= isSelected;
+    if (changedOnly && !isChangedGitStatus(file.gitStatus)) {
+      continue;
+    }
+
+    const item = document.createElement("li");
+    item.className = "toc-item";
+
+    const button = document.createElement("button");
+    button.type = "button";
+    button.className = "folder-file-link";
+    button.dataset.filePath = file.filePath;
+    button.classList.toggle("is-git-ignored", file.gitStatus === "ignored");
+
+    if (file.depth > 0) {
+      button.style.setProperty("--chapter-depth", String(file.depth));
+    }
+
+    const label = document.createElement("span");
+    label.className = "folder-file-label";
+
+    const name = document.createElement("span");
+    name.className = "folder-file-name";
+    name.textContent = file.title || file.fileName || file.filePath;
+    label.append(name);
+
+    const details = describeFolderFile(file);
+    if (details) {
+      const meta = document.createElement("span");
+      meta.className = "folder-file-meta";
+      meta.textContent = details;
+      label.append(meta);
+    }
+
+    if (file.title) {
+      button.title = file.fileName;
+    }
+    button.append(label);
+
+    const badge = createGitStatusBadge(file.gitStatus);
+    if (badge) {
+      button.append(badge);
+    }
+
+    if (isSelected) {
+      button.setAttribute("aria-current", "true");
+    }
+
+    item.append(button);
+    folderFilesListEl.append(item);
+  }
+
+  folderFilesEmptyEl.hidden = folderFilesListEl.children.length > 0;
+  updateChapterNavigation(selectedPath);
+  return { hasFiles, hasSelection };
+}
+
+// File name (when a title is shown instead), size and age under each folder entry.
+function describeFolderFile(file) {
+  const parts = [];
+  if (file.title && file.fileName) {
+    parts.push(file.fileName);
+  }
+  if (typeof file.size === "number") {
+    parts.push(formatByteSize(file.size));
+  }
+  if (typeof file.modified === "number") {
+    parts.push(formatAge(file.modified));
+  }
+  return parts.join(" · ");
+}
+
+function getChapterLabel(file) {
+  return file.title || file.fileName || file.filePath;
+}
+
+function getAdjacentChapter(offset, selectedPath = currentFilePath) {
+  if (!folderBook || !selectedPath) {
+    return null;
+  }
+
+  const index = folderFiles.findIndex((file) => file?.filePath === selectedPath);
+  return index === -1 ? null : folderFiles[index + offset] ?? null;
+}
+
+function updateChapterNavigation(selectedPath = currentFilePath) {
+  const isBook = currentOpenMode === "folder" && Boolean(folderBook);
+  previousChapterButton.hidden = !isBook;
+  nextChapterButton.hidden = !isBook;
+  toggleFolderPanelButton.classList.toggle("toolbar-right-button", !isBook);
+
+  for (const [button, offset, label] of [
+    [previousChapterButton, -1, "Previous chapter"],
+    [nextChapterButton, 1, "Next chapter"],
+  ]) {
+    const chapter = isBook ? getAdjacentChapter(offset, selectedPath) : null;
+    button.disabled = !chapter;
+    button.title = chapter ? `${label}: ${getChapterLabel(chapter)}` : label;
+  }
+}
+
+function appendHighlightedText(parent, chars, start, end, positions) {
+  let run = "";
+  let runIsMatch = false;
+  const flush = () => {
+    if (!run) {
+      return;
+    }
+    if (runIsMatch) {
+      const mark = document.createElement("mark");
+      mark.textContent = run;
+      parent.append(mark);
+    } else {
+      parent.append(run);
+    }
+    run = "";
+  };
+
+  for (let index = start; index < end; index += 1) {
+    const isMatch = positions.has(index);
+    if (isMatch !== runIsMatch) {
+      flush();
+      runIsMatch = isMatch;
+    }
+    run += chars[index];
+  }
+  flush();
+}
+
+function renderQuickOpenResults() {
+  quickOpenListEl.innerHTML = "";
+  quickOpenEmptyEl.hidden = quickOpenResults.length > 0;
+
+  quickOpenResults.forEach((result, index) => {
+    // Positions count characters, so split by code point rather than UTF-16 unit.
+    const chars = Array.from(result.relativePath);
+    const fileNameStart = chars.lastIndexOf("/") + 1;
+    const positions = new Set(result.positions);
+
+    const item = document.createElement("li");
+    item.className = "quick-open-item";
+    item.dataset.index = String(index);
+    item.setAttribute("role", "option");
+    item.setAttribute("aria-selected", String(index === quickOpenSelectedIndex));
+
+    const name = document.createElement("span");
+    name.className = "quick-open-name";
+    appendHighlightedText(name, chars, fileNameStart, chars.length, positions);
+    item.append(name);
+
+    if (fileNameStart > 0) {
+      const folder = document.createElement("span");
+      folder.className = "quick-open-folder";
+      appendHighlightedText(folder, chars, 0, fileNameStart - 1, positions);
+      item.append(folder);
+    }
+
+    quickOpenListEl.append(item);
+  });
+
+  quickOpenListEl.children[quickOpenSelectedIndex]?.scrollIntoView({ block: "nearest" });
+}
+
+async function runQuickOpenSearch(refresh = false) {
+  const requestId = ++quickOpenRequestId;
+  const results = await desktopAPI.quickOpenSearch(currentFolderPath, quickOpenInput.value, refresh);
+  if (requestId !== quickOpenRequestId || quickOpenEl.hidden) {
+    return;
+  }
+
+  quickOpenResults = Array.isArray(results) ? results : [];
+  quickOpenSelectedIndex = 0;
+  renderQuickOpenResults();
+}
+
+function runQuickOpenSearchWithLogging(refresh = false) {
+  runQuickOpenSearch(refresh).catch((error) => {
+    console.error("Failed to search folder files:", error);
+  });
+}
+
+function openQuickOpen() {
+  if (!desktopAPI || typeof desktopAPI.quickOpenSearch !== "function" || currentOpenMode !== "folder" || !currentFolderPath) {
+    return;
+  }
+
+  quickOpenEl.hidden = false;
+  quickOpenInput.value = "";
+  quickOpenInput.focus();
+  // Relist the folder each time the palette opens; keystrokes reuse that listing.
+  runQuickOpenSearchWithLogging(true);
+}
+
+function closeQuickOpen() {
+  if (quickOpenEl.hidden) {
+    return;
+  }
+
+  quickOpenEl.hidden = true;
+  quickOpenRequestId += 1;
+  quickOpenResults = [];
+  quickOpenListEl.innerHTML = "";
+}
+
+function openQuickOpenResult(index) {
+  const result = quickOpenResults[index];
+  closeQuickOpen();
+  if (!result) {
+    return;
+  }
+
+  openDesktopFileByPath(result.filePath, { mode: getNavigationMode() }).catch((error) => {
+    console.error("Failed to open file from quick open:", error);
+  });
+}
+
+function openAdjacentChapter(offset) {
+  const chapter = getAdjacentChapter(offset);
+  if (!chapter) {
+    return;
   }
 
-  return { hasFiles: true, hasSelection };
+  openDesktopFileByPath(chapter.filePath, { mode: getNavigationMode() }).catch((error) => {
+    console.error("Failed to open chapter:", error);
+  });
 }
 
 function renderFolderEmptyState() {
@@ -379,6 +1960,10 @@ function renderFolderEmptyState() {
   fileNameEl.textContent = "No Markdown files in folder";
   currentFilePath = null;
   openVSCodeButton.disabled = true;
+  compareWithButton.disabled = true;
+  clearGitHistory();
+  clearLocalHistory();
+  leaveCompareMode();
 
   syncAutoRefreshWatcher().catch((error) => {
     console.error("Failed to stop auto-refresh watcher:", error);
@@ -390,18 +1975,61 @@ function renderFolderSelectionPrompt() {
   fileNameEl.textContent = "No file selected";
   currentFilePath = null;
   openVSCodeButton.disabled = true;
+  compareWithButton.disabled = true;
+  clearGitHistory();
+  clearLocalHistory();
+  leaveCompareMode();
 
   syncAutoRefreshWatcher().catch((error) => {
     console.error("Failed to stop auto-refresh watcher:", error);
   });
 }
 
+function setFolderFilesNotice(messages) {
+  const lines = Array.isArray(messages) ? messages.filter((message) => typeof message === "string") : [];
+  folderFilesNoticeEl.textContent = lines.length > 0 ? `Skipped:\n${lines.join("\n")}` : "";
+  folderFilesNoticeEl.hidden = lines.length === 0;
+}
+
+function showDropOverlay(text, rejected) {
+  clearTimeout(dropMessageTimer);
+  dropMessageTimer = null;
+  dropOverlayEl.textContent = text;
+  dropOverlayEl.classList.toggle("is-rejected", rejected);
+  dropOverlayEl.hidden = false;
+}
+
+function hideDropOverlay() {
+  if (dropMessageTimer) {
+    return;
+  }
+  dropOverlayEl.hidden = true;
+}
+
+// Lingers after the drop so the user can see why something did not open.
+function showDropMessage(heading, messages) {
+  const lines = Array.isArray(messages) ? messages.filter((message) => typeof message === "string") : [];
+  if (lines.length === 0) {
+    return;
+  }
+
+  showDropOverlay(`${heading}\n${lines.join("\n")}`, true);
+  dropMessageTimer = setTimeout(() => {
+    dropMessageTimer = null;
+    hideDropOverlay();
+  }, DROP_MESSAGE_DURATION_MS);
+}
+
 function enterSingleFileMode() {
   currentOpenMode = "single-file";
   currentFolderPath = null;
+  folderBook = null;
+  folderSortEl.hidden = true;
+  setFolderFilesNotice([]);
   updateFolderFilesList([], null);
   setFolderPanelVisible(false);
   syncFolderWatcherWithLogging();
+  scheduleViewerStateReport();
 }
 
 function applyFolderPayload(payload, options = {}) {
@@ -411,7 +2039,17 @@ function applyFolderPayload(payload, options = {}) {
 
   currentOpenMode = "folder";
   currentFolderPath = payload.folderPath ?? null;
+  folderBook = payload.book ?? null;
+  folderFilesTitleEl.textContent = folderBook ? "Chapters" : "Folder Files";
+  folderFilesTitleEl.title = folderBook ? `Ordered by ${folderBook}` : "";
+  // Book chapters keep the ordering file's order.
+  folderSortEl.hidden = Boolean(folderBook);
+  folderSortSelect.value = payload.sortMode ?? "name";
+  if (options.preserveSelection === false) {
+    setFolderFilesNotice([]);
+  }
   setFolderPanelVisible(true);
+  scheduleViewerStateReport();
 
   const preferredSelection =
     options.selectedFilePath ??
@@ -452,6 +2090,10 @@ async function openFileFromBrowser(file) {
   fileNameEl.textContent = file.name;
   currentFilePath = null;
   openVSCodeButton.disabled = true;
+  compareWithButton.disabled = true;
+  clearGitHistory();
+  clearLocalHistory();
+  leaveCompareMode();
 
   syncAutoRefreshWatcher().catch((error) => {
     console.error("Failed to stop auto-refresh watcher:", error);
@@ -465,7 +2107,12 @@ function renderDesktopPayload(payload, options = {}) {
 
   const previousScrollRatio = options.preserveScroll ? getPreviewScrollRatio() : null;
 
-  renderMarkdown(payload.content, { baseHref: payload.baseHref });
+  renderMarkdown(payload.content, {
+    baseHref: payload.baseHref,
+    stylesheets: payload.stylesheets,
+    trusted: payload.trusted === true,
+    diffBase: payload.diffBase,
+  });
 
   if (previousScrollRatio !== null) {
     requestAnimationFrame(() => {
@@ -473,9 +2120,69 @@ function renderDesktopPayload(payload, options = {}) {
     });
   }
 
-  fileNameEl.textContent = payload.fileName ?? "Unknown";
+  const previousFilePath = currentFilePath;
+  if (
+    comparePayload &&
+    (payload.filePath !== previousFilePath || payload.revision || payload.diffBase || payload.snapshot)
+  ) {
+    leaveCompareMode();
+  }
+  renderComparePane();
+  currentSnapshot = payload.snapshot ?? null;
+  currentSnapshotDiff = Boolean(currentSnapshot && payload.diffBase);
+  currentRevision = payload.revision ?? null;
+  currentDiffRevision = currentSnapshot ? null : (payload.diffBase?.revision ?? null);
+  const fileName = payload.fileName ?? "Unknown";
+  if (currentSnapshot) {
+    fileNameEl.textContent = `${fileName} ${currentSnapshotDiff ? "vs" : "@"} ${formatSnapshotTime(currentSnapshot)}`;
+  } else if (currentRevision) {
+    fileNameEl.textContent = `${fileName} @ ${currentRevision.slice(0, 7)}`;
+  } else if (currentDiffRevision) {
+    const baseNote = payload.diffBase.missing ? ", not in revision" : "";
+    fileNameEl.textContent = `${fileName} vs ${currentDiffRevision} (${payload.diffBase.commit.slice(0, 7)}${baseNote})`;
+  } else {
+    fileNameEl.textContent = fileName;
+  }
   currentFilePath = payload.filePath ?? null;
   openVSCodeButton.disabled = !currentFilePath;
+  compareWithButton.disabled = !currentFilePath;
+  toggleTimelineButton.disabled = !currentFilePath;
+  updateSafeModeBanner();
+  scheduleViewerStateReport();
+
+  if (previousFilePath !== currentFilePath) {
+    localHistory = null;
+  }
+  if (isTimelineOpen()) {
+    refreshLocalHistoryWithLogging();
+  } else {
+    renderTimelineStrip();
+  }
+
+  if ((currentRevision || currentDiffRevision || currentSnapshot) && previousFilePath === currentFilePath) {
+    renderGitStrip();
+  } else {
+    if (previousFilePath !== currentFilePath) {
+      gitHistory = null;
+      setGitHistoryOpen(false);
+    }
+    refreshGitHistory().catch((error) => {
+      console.error("Failed to load git history:", error);
+    });
+  }
+
+  if (previousFilePath !== currentFilePath) {
+    blamePayload = null;
+  }
+  // Show the last known blame right away; the refresh picks up lines that just changed.
+  renderBlameGutter();
+  if (blameEnabled) {
+    refreshBlameWithLogging();
+  }
+
+  syncStylesheetWatcher().catch((error) => {
+    console.error("Failed to sync stylesheet watcher:", error);
+  });
 
   if (options.syncWatcher !== false) {
     syncAutoRefreshWatcher().catch((error) => {
@@ -484,6 +2191,43 @@ function renderDesktopPayload(payload, options = {}) {
   }
 }
 
+async function reloadCurrentDocument() {
+  if (!desktopAPI || !currentFilePath) {
+    return;
+  }
+
+  let payload;
+  if (currentSnapshot) {
+    payload = currentSnapshotDiff
+      ? await desktopAPI.readSnapshotDiff(currentFilePath, currentSnapshot)
+      : await desktopAPI.readSnapshot(currentFilePath, currentSnapshot);
+  } else if (currentRevision) {
+    payload = await desktopAPI.readGitFileRevision(currentFilePath, currentRevision);
+  } else if (currentDiffRevision) {
+    payload = await desktopAPI.readGitFileDiff(currentFilePath, currentDiffRevision);
+  } else {
+    payload = await desktopAPI.readMarkdownFile(currentFilePath);
+  }
+  renderDesktopPayload(payload, { preserveScroll: true, syncWatcher: false });
+}
+
+async function trustCurrentFolder() {
+  if (!desktopAPI || typeof desktopAPI.trustFolder !== "function" || !currentFilePath) {
+    return;
+  }
+
+  const trustedPath = currentOpenMode === "folder" && currentFolderPath ? currentFolderPath : currentFilePath;
+  // The backend asks the user to confirm before trusting anything.
+  if (!(await desktopAPI.trustFolder(trustedPath))) {
+    return;
+  }
+  await reloadCurrentDocument();
+  // Git status badges are only listed for trusted folders.
+  if (currentOpenMode === "folder" && currentFolderPath && typeof desktopAPI.readMarkdownFolder === "function") {
+    applyFolderPayload(await desktopAPI.readMarkdownFolder(currentFolderPath), { preserveSelection: true });
+  }
+}
+
 async function openDesktopFileDialog() {
   if (!desktopAPI) {
     return;
@@ -495,7 +2239,9 @@ async function openDesktopFileDialog() {
   }
 
   enterSingleFileMode();
+  saveHistoryScrollPosition();
   renderDesktopPayload(result.payload);
+  recordHistoryEntry(result.payload.filePath);
 }
 
 async function openDesktopFolderDialog() {
@@ -516,16 +2262,24 @@ async function openDesktopFileByPath(filePath, options = {}) {
     return;
   }
 
-  if (options.mode !== "folder") {
+  const keepsFileList = options.mode === "folder" || options.mode === "collection";
+  if (!keepsFileList) {
     enterSingleFileMode();
   }
 
   const payload = await desktopAPI.readMarkdownFile(filePath);
+  if (options.recordHistory !== false) {
+    saveHistoryScrollPosition();
+  }
   renderDesktopPayload(payload);
 
-  if (options.mode === "folder") {
+  if (keepsFileList) {
     updateFolderFilesList(folderFiles, payload.filePath ?? null);
   }
+
+  if (options.recordHistory !== false) {
+    recordHistoryEntry(payload.filePath);
+  }
 }
 
 async function openDesktopFolderByPath(folderPath) {
@@ -552,25 +2306,122 @@ function normalizeOpenTarget(target) {
 
   const targetType = target.targetType;
   const targetPath = target.path;
+  const unmatched = Array.isArray(target.unmatched) ? target.unmatched : [];
   if ((targetType === "file" || targetType === "folder") && typeof targetPath === "string") {
-    return { targetType, path: targetPath };
+    const diffRevision = typeof target.diffRevision === "string" ? target.diffRevision : null;
+    const comparePath = typeof target.comparePath === "string" ? target.comparePath : null;
+    return { targetType, path: targetPath, position: target.position ?? null, unmatched, diffRevision, comparePath };
+  }
+
+  if (targetType === "collection" && Array.isArray(target.paths)) {
+    const paths = target.paths.filter((path) => typeof path === "string");
+    return { targetType, path: paths[0] ?? null, paths, unmatched };
   }
 
   return null;
 }
 
+function scrollToSourceLine(line) {
+  let bestNode = null;
+  let bestLine = 0;
+
+  for (const node of previewEl.querySelectorAll("[data-source-line]")) {
+    const startLine = Number.parseInt(node.getAttribute("data-source-line") ?? "", 10);
+    // Nested blocks come after their parent, so `>=` prefers the innermost match.
+    if (Number.isFinite(startLine) && startLine <= line && startLine >= bestLine) {
+      bestNode = node;
+      bestLine = startLine;
+    }
+  }
+
+  if (!bestNode) {
+    return false;
+  }
+
+  bestNode.scrollIntoView({ block: "start" });
+  return true;
+}
+
+function revealOpenPosition(position) {
+  if (!position || typeof position !== "object") {
+    return;
+  }
+
+  if (Number.isFinite(position.line)) {
+    scrollToSourceLine(position.line);
+  } else if (typeof position.heading === "string") {
+    scrollToFragment(position.heading);
+  }
+}
+
+function getPathSegments(filePath) {
+  return filePath.split(/[\\/]/).filter(Boolean);
+}
+
+// Files with the same name (docs/a/README.md, docs/b/README.md) get their parent folder added.
+function getCollectionFiles(paths) {
+  const nameCounts = new Map();
+  for (const filePath of paths) {
+    const fileName = getPathSegments(filePath).at(-1) ?? filePath;
+    nameCounts.set(fileName, (nameCounts.get(fileName) ?? 0) + 1);
+  }
+
+  return paths.map((filePath) => {
+    const segments = getPathSegments(filePath);
+    const fileName = segments.at(-1) ?? filePath;
+    return {
+      filePath,
+      fileName: nameCounts.get(fileName) > 1 ? segments.slice(-2).join("/") : fileName,
+    };
+  });
+}
+
+async function openDesktopCollection(paths, unmatched) {
+  if (!desktopAPI || paths.length === 0) {
+    return;
+  }
+
+  currentOpenMode = "collection";
+  currentFolderPath = null;
+  folderBook = null;
+  folderSortEl.hidden = true;
+  folderFilesTitleEl.textContent = "Opened Files";
+  folderFilesTitleEl.title = "";
+  setFolderFilesNotice(unmatched);
+  setFolderPanelVisible(true);
+  updateFolderFilesList(getCollectionFiles(paths), null);
+  setFolderPanelOpen(true);
+  syncFolderWatcherWithLogging();
+
+  await openDesktopFileByPath(paths[0], { mode: "collection" });
+}
+
 async function openDesktopTarget(target) {
   const normalized = normalizeOpenTarget(target);
   if (!normalized || !normalized.path) {
     return;
   }
 
+  if (normalized.targetType === "collection") {
+    await openDesktopCollection(normalized.paths, normalized.unmatched);
+    return;
+  }
+
   if (normalized.targetType === "folder") {
     await openDesktopFolderByPath(normalized.path);
+    setFolderFilesNotice(normalized.unmatched);
     return;
   }
 
   await openDesktopFileByPath(normalized.path, { mode: "single-file" });
+  if (normalized.diffRevision) {
+    await openGitDiff(normalized.diffRevision);
+  }
+  if (normalized.comparePath) {
+    await openComparisonByPath(normalized.comparePath);
+  }
+  revealOpenPosition(normalized.position);
+  showDropMessage("Skipped:", normalized.unmatched);
 }
 
 async function bindExternalOpenEvents() {
@@ -585,6 +2436,28 @@ async function bindExternalOpenEvents() {
     }
   }
 
+  if (typeof desktopAPI.onDropHover === "function") {
+    desktopAPI.onDropHover((payload) => {
+      if (payload?.accepted) {
+        showDropOverlay("Drop to open", false);
+      } else {
+        showDropOverlay("Only markdown files and folders can be opened", true);
+      }
+    });
+  }
+
+  if (typeof desktopAPI.onDropHoverEnd === "function") {
+    desktopAPI.onDropHoverEnd(() => {
+      hideDropOverlay();
+    });
+  }
+
+  if (typeof desktopAPI.onDropRejected === "function") {
+    desktopAPI.onDropRejected((messages) => {
+      showDropMessage("Cannot open:", messages);
+    });
+  }
+
   if (typeof desktopAPI.onExternalFileOpen === "function") {
     desktopAPI.onExternalFileOpen((target) => {
       openDesktopTarget(target).catch((error) => {
@@ -598,7 +2471,43 @@ async function bindExternalOpenEvents() {
       if (!payload || !payload.filePath || payload.filePath !== currentFilePath) {
         return;
       }
+      if (currentRevision || (currentSnapshot && !currentSnapshotDiff)) {
+        // The document on screen stays put, but the change may have added a snapshot.
+        if (isTimelineOpen()) {
+          refreshLocalHistoryWithLogging();
+        }
+        return;
+      }
+      if (currentDiffRevision || currentSnapshotDiff) {
+        reloadCurrentDocument().catch((error) => {
+          console.error("Failed to refresh comparison:", error);
+        });
+        return;
+      }
       renderDesktopPayload(payload, { preserveScroll: true, syncWatcher: false });
+      // After the scroll position has been restored.
+      requestAnimationFrame(() => {
+        highlightChangedLines(payload.changedLines);
+      });
+    });
+  }
+
+  if (typeof desktopAPI.onCompareFileChanged === "function") {
+    desktopAPI.onCompareFileChanged((payload) => {
+      if (!payload || !comparePayload || payload.filePath !== comparePayload.filePath) {
+        return;
+      }
+      comparePayload = payload;
+      renderComparePane();
+    });
+  }
+
+  if (typeof desktopAPI.onStylesheetsChanged === "function") {
+    desktopAPI.onStylesheetsChanged((payload) => {
+      if (!payload || !payload.filePath || payload.filePath !== currentFilePath) {
+        return;
+      }
+      applyUserStylesheets(payload.stylesheets);
     });
   }
 
@@ -613,6 +2522,33 @@ async function bindExternalOpenEvents() {
   }
 }
 
+function reportViewerState() {
+  viewerStateReportTimer = null;
+  if (!desktopAPI || typeof desktopAPI.reportViewerState !== "function") {
+    return;
+  }
+
+  desktopAPI
+    .reportViewerState({
+      filePath: currentFilePath,
+      folderPath: currentFolderPath,
+      line: currentFilePath ? getCurrentSourceLine() : 0,
+      mode: currentOpenMode,
+      theme: getCurrentThemeClass(),
+    })
+    .catch((error) => {
+      console.error("Failed to report viewer state:", error);
+    });
+}
+
+// Remote control (`--remote get-state`) reads the last reported state.
+function scheduleViewerStateReport() {
+  if (viewerStateReportTimer !== null) {
+    return;
+  }
+  viewerStateReportTimer = setTimeout(reportViewerState, VIEWER_STATE_REPORT_DELAY_MS);
+}
+
 function getScrollContainer() {
   if (previewEl.scrollHeight > previewEl.clientHeight + 1) {
     return previewEl;
@@ -702,6 +2638,17 @@ openFileOptionsButton.addEventListener("click", (event) => {
   toggleOpenFileOptionsMenu();
 });
 
+compareWithButton.addEventListener("click", () => {
+  closeOpenFileOptionsMenu();
+  compareWithFileDialog().catch((error) => {
+    console.error("Failed to open comparison:", error);
+  });
+});
+
+exitCompareButton.addEventListener("click", () => {
+  leaveCompareMode();
+});
+
 openFolderButton.addEventListener("click", () => {
   closeOpenFileOptionsMenu();
   if (!desktopAPI) {
@@ -723,6 +2670,33 @@ toggleFolderPanelButton.addEventListener("click", () => {
   setFolderPanelOpen(!isOpen);
 });
 
+folderChangedOnlyInput.addEventListener("change", () => {
+  updateFolderFilesList(folderFiles, currentFilePath);
+});
+
+folderSortSelect.addEventListener("change", () => {
+  if (!desktopAPI || typeof desktopAPI.setFolderSortMode !== "function" || !currentFolderPath) {
+    return;
+  }
+
+  desktopAPI
+    .setFolderSortMode(currentFolderPath, folderSortSelect.value)
+    .then((payload) => {
+      applyFolderPayload(payload, { preserveSelection: true });
+    })
+    .catch((error) => {
+      console.error("Failed to change folder sort order:", error);
+    });
+});
+
+previousChapterButton.addEventListener("click", () => {
+  openAdjacentChapter(-1);
+});
+
+nextChapterButton.addEventListener("click", () => {
+  openAdjacentChapter(1);
+});
+
 folderFilesListEl.addEventListener("click", (event) => {
   const button = event.target.closest("button[data-file-path]");
   if (!button) {
@@ -734,7 +2708,7 @@ folderFilesListEl.addEventListener("click", (event) => {
     return;
   }
 
-  openDesktopFileByPath(selectedFilePath, { mode: "folder" }).catch((error) => {
+  openDesktopFileByPath(selectedFilePath, { mode: getNavigationMode() }).catch((error) => {
     console.error("Failed to open markdown file from folder:", error);
   });
 });
@@ -754,9 +2728,153 @@ document.addEventListener("click", (event) => {
   closeOpenFileOptionsMenu();
 });
 
+quickOpenInput.addEventListener("input", () => {
+  runQuickOpenSearchWithLogging();
+});
+
+quickOpenInput.addEventListener("keydown", (event) => {
+  if (event.key === "ArrowDown" || event.key === "ArrowUp") {
+    event.preventDefault();
+    if (quickOpenResults.length > 0) {
+      const delta = event.key === "ArrowDown" ? 1 : -1;
+      quickOpenSelectedIndex = (quickOpenSelectedIndex + delta + quickOpenResults.length) % quickOpenResults.length;
+      renderQuickOpenResults();
+    }
+  } else if (event.key === "Enter") {
+    event.preventDefault();
+    openQuickOpenResult(quickOpenSelectedIndex);
+  }
+});
+
+quickOpenListEl.addEventListener("click", (event) => {
+  const item = event.target.closest(".quick-open-item");
+  if (item) {
+    openQuickOpenResult(Number(item.dataset.index));
+  }
+});
+
+document.addEventListener("mousedown", (event) => {
+  if (!quickOpenEl.hidden && !quickOpenEl.contains(event.target)) {
+    closeQuickOpen();
+  }
+});
+
 document.addEventListener("keydown", (event) => {
   if (event.key === "Escape") {
     closeOpenFileOptionsMenu();
+    closeQuickOpen();
+  }
+
+  if ((event.ctrlKey || event.metaKey) && !event.altKey && event.key.toLowerCase() === "p") {
+    event.preventDefault();
+    openQuickOpen();
+    return;
+  }
+
+  const isMac = navigator.platform.toUpperCase().includes("MAC");
+  const isBack =
+    event.key === "BrowserBack" ||
+    (event.altKey && event.key === "ArrowLeft") ||
+    (isMac && event.metaKey && event.key === "[");
+  const isForward =
+    event.key === "BrowserForward" ||
+    (event.altKey && event.key === "ArrowRight") ||
+    (isMac && event.metaKey && event.key === "]");
+
+  if (isBack || isForward) {
+    event.preventDefault();
+    traverseHistoryWithLogging(isBack ? -1 : 1);
+  }
+});
+
+// Mouse buttons 3 and 4 are the side "back" and "forward" buttons.
+document.addEventListener("mouseup", (event) => {
+  if (event.button === 3 || event.button === 4) {
+    event.preventDefault();
+    traverseHistoryWithLogging(event.button === 3 ? -1 : 1);
+  }
+});
+
+previewEl.addEventListener("scroll", scheduleViewerStateReport, { passive: true });
+window.addEventListener("scroll", scheduleViewerStateReport, { passive: true });
+
+previewEl.addEventListener("click", (event) => {
+  const link = event.target.closest("a[href]");
+  if (!link || !previewEl.contains(link)) {
+    return;
+  }
+
+  const rawHref = link.getAttribute("href") ?? "";
+  if (!desktopAPI) {
+    if (rawHref.startsWith("#")) {
+      event.preventDefault();
+      scrollToFragment(rawHref.slice(1));
+    }
+    return;
+  }
+
+  // Never let a document link navigate the webview away from the app shell.
+  event.preventDefault();
+  followDocumentLink(rawHref).catch((error) => {
+    console.error("Failed to follow link:", error);
+  });
+});
+
+comparePreviewEl.addEventListener("click", (event) => {
+  const link = event.target.closest("a[href]");
+  if (!link || !desktopAPI) {
+    return;
+  }
+
+  event.preventDefault();
+  const rawHref = link.getAttribute("href") ?? "";
+  if (rawHref.startsWith("#")) {
+    comparePreviewEl.querySelector(`#${CSS.escape(rawHref.slice(1))}`)?.scrollIntoView({ block: "start" });
+    return;
+  }
+  followDocumentLink(rawHref, comparePayload?.filePath ?? null).catch((error) => {
+    console.error("Failed to follow link:", error);
+  });
+});
+
+previewEl.addEventListener("scroll", () => syncCompareScroll(previewEl, comparePreviewEl), { passive: true });
+comparePreviewEl.addEventListener("scroll", () => syncCompareScroll(comparePreviewEl, previewEl), { passive: true });
+// Late-loading images change section heights after alignment.
+previewEl.addEventListener("load", scheduleCompareEqualize, true);
+comparePreviewEl.addEventListener("load", scheduleCompareEqualize, true);
+window.addEventListener("resize", scheduleCompareEqualize);
+previewEl.addEventListener("load", scheduleBlameLayout, true);
+window.addEventListener("resize", scheduleBlameLayout);
+
+previewEl.addEventListener("mouseover", (event) => {
+  const entry = event.target.closest(".blame-entry");
+  if (entry) {
+    showBlameCard(entry);
+  }
+});
+
+previewEl.addEventListener("mouseout", (event) => {
+  if (event.target.closest(".blame-entry") && !event.relatedTarget?.closest?.(".blame-entry")) {
+    hideBlameCard();
+  }
+});
+previewEl.addEventListener("scroll", hideBlameCard, { passive: true });
+
+previewEl.addEventListener("click", (event) => {
+  const entry = event.target.closest(".blame-entry");
+  if (!entry || entry.classList.contains("is-uncommitted")) {
+    return;
+  }
+
+  hideBlameCard();
+  openGitRevision(entry.dataset.blameHash).catch((error) => {
+    console.error("Failed to open git revision:", error);
+  });
+});
+
+previewEl.addEventListener("auxclick", (event) => {
+  if (desktopAPI && event.target.closest("a[href]")) {
+    event.preventDefault();
   }
 });
 
@@ -774,23 +2892,124 @@ tocListEl.addEventListener("click", (event) => {
   heading.scrollIntoView({ behavior: "smooth", block: "start" });
 });
 
+toggleGitBlameButton.addEventListener("click", () => {
+  setBlameEnabled(!blameEnabled);
+});
+
+toggleGitHistoryButton.addEventListener("click", () => {
+  setGitHistoryOpen(gitHistoryListEl.hidden);
+});
+
+gitHistoryListEl.addEventListener("click", (event) => {
+  const button = event.target.closest("button[data-commit]");
+  if (!button) {
+    return;
+  }
+
+  openGitRevision(button.dataset.commit).catch((error) => {
+    console.error("Failed to open git revision:", error);
+  });
+});
+
+gitCompareForm.addEventListener("submit", (event) => {
+  event.preventDefault();
+  openGitDiff(gitCompareRevisionInput.value.trim()).catch((error) => {
+    console.error("Failed to compare with revision:", error);
+    gitCompareRevisionInput.setCustomValidity(error?.message ?? String(error));
+    gitCompareRevisionInput.reportValidity();
+  });
+});
+
+gitCompareRevisionInput.addEventListener("input", () => {
+  gitCompareRevisionInput.setCustomValidity("");
+});
+
+gitWorkingCopyButton.addEventListener("click", () => {
+  if (!currentFilePath) {
+    return;
+  }
+
+  openDesktopFileByPath(currentFilePath, { mode: getNavigationMode(), recordHistory: false }).catch((error) => {
+    console.error("Failed to return to working copy:", error);
+  });
+});
+
+toggleTimelineButton.addEventListener("click", () => {
+  setTimelineOpen(!isTimelineOpen());
+});
+
+localHistoryInput.addEventListener("change", () => {
+  if (!desktopAPI || typeof desktopAPI.setLocalHistoryEnabled !== "function") {
+    return;
+  }
+
+  desktopAPI
+    .setLocalHistoryEnabled(localHistoryInput.checked)
+    .then(refreshLocalHistory)
+    .catch((error) => {
+      console.error("Failed to change local history setting:", error);
+    });
+});
+
+timelineListEl.addEventListener("click", (event) => {
+  const diffButton = event.target.closest("button[data-diff-snapshot-id]");
+  if (diffButton) {
+    openSnapshotDiff(diffButton.dataset.diffSnapshotId).catch((error) => {
+      console.error("Failed to compare with snapshot:", error);
+    });
+    return;
+  }
+
+  const button = event.target.closest("button[data-snapshot-id]");
+  if (button) {
+    openSnapshot(button.dataset.snapshotId).catch((error) => {
+      console.error("Failed to open snapshot:", error);
+    });
+  }
+});
+
+timelineCurrentButton.addEventListener("click", () => {
+  if (!currentFilePath) {
+    return;
+  }
+
+  openDesktopFileByPath(currentFilePath, { mode: getNavigationMode(), recordHistory: false }).catch((error) => {
+    console.error("Failed to return to the current version:", error);
+  });
+});
+
 openVSCodeButton.addEventListener("click", () => {
   openInVSCodeAtCurrentPosition().catch((error) => {
     console.error("Unable to open VS Code:", error);
   });
 });
 
+loadRemoteImagesButton.addEventListener("click", () => {
+  for (const image of previewEl.querySelectorAll("img[data-blocked-src], img[data-blocked-srcset]")) {
+    loadBlockedImage(image);
+  }
+  updateSafeModeBanner();
+});
+
+trustFolderButton.addEventListener("click", () => {
+  trustCurrentFolder().catch((error) => {
+    console.error("Failed to trust folder:", error);
+  });
+});
+
 toggleAutoRefreshButton.addEventListener("click", () => {
   autoRefreshEnabled = !autoRefreshEnabled;
   updateAutoRefreshButton();
   syncAutoRefreshWatcher().catch((error) => {
     console.error("Failed to toggle auto-refresh watcher:", error);
   });
+  syncCompareWatcherWithLogging();
+  renderTimelineStrip();
 });
 
 toggleThemeButton.addEventListener("click", () => {
-  const isDarkTheme = document.body.classList.contains("vscode-dark");
-  setTheme(isDarkTheme ? "vscode-light" : "vscode-dark");
+  followSystemTheme = false;
+  setTheme(getNextThemeClass());
 });
 
 fileInput.addEventListener("change", async (event) => {
@@ -833,6 +3052,7 @@ async function initializeApp() {
 
   if (desktopAPI) {
     startupOptions = await initializeDesktopStartupOptions();
+    await loadVSCodeAppearance();
   }
 
   applyStartupOptions(startupOptions, { syncWatcher: false });
@@ -848,6 +3068,30 @@ async function initializeApp() {
       });
     }
 
+    if (typeof desktopAPI.onSystemThemeChanged === "function") {
+      desktopAPI.onSystemThemeChanged((theme) => {
+        if (followSystemTheme && isBaseThemeClass(theme)) {
+          setTheme(theme);
+        }
+      });
+    }
+
+    if (typeof desktopAPI.onRemoteScrollToLine === "function") {
+      desktopAPI.onRemoteScrollToLine((line) => {
+        if (Number.isFinite(line)) {
+          scrollToSourceLine(line);
+        }
+      });
+    }
+
+    if (typeof desktopAPI.onRemoteReload === "function") {
+      desktopAPI.onRemoteReload(() => {
+        reloadCurrentDocument().catch((error) => {
+          console.error("Failed to reload document:", error);
+        });
+      });
+    }
+
     if (typeof desktopAPI.onOpenOnLaunch === "function") {
       desktopAPI.onOpenOnLaunch((target) => {
         openDesktopTarget(target).catch((error) => {